@@ -10,9 +10,14 @@ use embedded_hal::digital::v2::{InputPin, OutputPin};
 use crate::hal::pac;
 use nrf52833_hal as hal;
 
-use hal::{clocks, gpio, timer::Instance};
+use hal::{gpio, gpiote::Gpiote, timer::Instance};
 use pac::{RTC0, TIMER0};
 
+use utilities::{
+    clock::{self, LfClkSource},
+    power,
+};
+
 #[app(device = crate::hal::pac, peripherals = true)]
 const APP: () = {
     struct Resources {
@@ -24,6 +29,7 @@ const APP: () = {
         led_2: gpio::Pin<gpio::Output<gpio::PushPull>>,
         led_3: gpio::Pin<gpio::Output<gpio::PushPull>>,
         led_4: gpio::Pin<gpio::Output<gpio::PushPull>>,
+        gpiote: Gpiote,
         #[init(false)]
         on_off: bool,
         rtc_0: hal::rtc::Rtc<RTC0>,
@@ -32,17 +38,15 @@ const APP: () = {
 
     #[init]
     fn init(cx: init::Context) -> init::LateResources {
-        // Configure to use external clocks, and start them
-        let _clocks = clocks::Clocks::new(cx.device.CLOCK)
-            .enable_ext_hfosc()
-            .set_lfclk_src_external(clocks::LfOscConfiguration::NoExternalNoBypass)
-            .start_lfclk();
+        // Start the high frequency crystal and the low frequency clock
+        clock::init_clocks(cx.device.CLOCK, LfClkSource::Crystal);
+        utilities::log::init();
 
         cx.device.TIMER0.set_periodic();
         cx.device.TIMER0.enable_interrupt();
         cx.device.TIMER0.timer_start(1_000_000u32);
 
-        defmt::info!("Initialize");
+        utilities::log!("Initialize");
 
         let rtc_0 = match hal::rtc::Rtc::new(cx.device.RTC0, 4095) {
             Ok(mut rtc) => {
@@ -78,6 +82,13 @@ const APP: () = {
             .into_push_pull_output(gpio::Level::High)
             .degrade();
 
+        // Button 2 is delivered by the GPIOTE PORT event so that idle can
+        // sleep, unlike an IN channel it doesn't keep HFCLK running. The
+        // button is active low, sense the press first.
+        let gpiote = Gpiote::new(cx.device.GPIOTE);
+        gpiote.port().input_pin(&button_2).low();
+        gpiote.port().enable_interrupt();
+
         init::LateResources {
             timer_0: cx.device.TIMER0,
             button_1,
@@ -89,6 +100,7 @@ const APP: () = {
             led_3,
             led_4,
             rtc_0,
+            gpiote,
         }
     }
 
@@ -114,7 +126,7 @@ const APP: () = {
 
         match button_4.is_low() {
             Ok(true) => {
-                defmt::info!("Button 4");
+                utilities::log!("Button 4");
                 let _ = led_4.set_low();
             }
             Ok(false) => {
@@ -124,23 +136,38 @@ const APP: () = {
         }
     }
 
-    #[idle(resources = [button_2, led_2])]
-    fn idle(cx: idle::Context) -> ! {
+    #[task(binds = GPIOTE, resources = [gpiote, button_2, led_2])]
+    fn button(cx: button::Context) {
+        let gpiote = cx.resources.gpiote;
+        if !gpiote.port().is_event_triggered() {
+            return;
+        }
+        gpiote.port().reset_events();
+
+        // The PORT event detects a level, sense the opposite level to catch
+        // the release after a press and the press after a release
         let button_2 = cx.resources.button_2;
-        let led_2 = cx.resources.led_2;
+        match button_2.is_low() {
+            Ok(true) => {
+                gpiote.port().input_pin(button_2).high();
+                let _ = cx.resources.led_2.set_low();
+            }
+            Ok(false) => {
+                gpiote.port().input_pin(button_2).low();
+                let _ = cx.resources.led_2.set_high();
+            }
+            Err(_) => {}
+        }
+    }
 
-        defmt::info!("Idle");
+    #[idle]
+    fn idle(_cx: idle::Context) -> ! {
+        utilities::log!("Idle");
 
+        // TIMER0, RTC0 and GPIOTE wake the CPU, all work is done in their
+        // tasks
         loop {
-            match button_2.is_low() {
-                Ok(true) => {
-                    let _ = led_2.set_low();
-                }
-                Ok(false) => {
-                    let _ = led_2.set_high();
-                }
-                Err(_) => {}
-            }
+            power::sleep();
         }
     }
 };
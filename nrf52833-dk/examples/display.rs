@@ -1,7 +1,10 @@
 #![no_main]
 #![no_std]
 
-use utilities::{spi, st7735s};
+use utilities::{
+    clock::{self, LfClkSource},
+    spi, st7735s, text,
+};
 
 use core::fmt::Write;
 
@@ -9,16 +12,22 @@ use nrf52833_dk as _;
 
 use rtic::app;
 
-use embedded_hal::digital::v2::{InputPin, OutputPin};
+use embedded_hal::{
+    blocking::delay::DelayMs,
+    digital::v2::{InputPin, OutputPin},
+};
+
+use cortex_m::peripheral::DWT;
 
 use crate::hal::pac;
 use nrf52833_hal as hal;
 
-use hal::{clocks, gpio, spim, timer::Instance, uarte};
+use hal::{gpio, spim, timer::Instance, uarte};
 use pac::{RTC0, RTC1, SPIM3, TIMER0, TIMER1, UARTE0};
 
 use embedded_graphics::{
     drawable::Drawable,
+    fonts::Font,
     geometry::Point,
     pixelcolor::{Rgb565, RgbColor},
     primitives::{rectangle::Rectangle, Primitive},
@@ -29,6 +38,13 @@ use profont::ProFont12Point;
 
 use st7735s::Orientation;
 
+/// Button polling interval in the idle loop, in milliseconds
+const POLL_MS: u16 = 10;
+/// Time a button is held for a long press, in milliseconds
+const LONG_PRESS_MS: u16 = 1000;
+/// CPU cycles per microsecond, at 64 MHz
+const CYCLES_PER_MICRO: u32 = 64;
+
 #[app(device = crate::hal::pac, peripherals = true)]
 const APP: () = {
     struct Resources {
@@ -57,14 +73,16 @@ const APP: () = {
 
     #[init]
     fn init(cx: init::Context) -> init::LateResources {
-        // Configure to use external clocks, and start them
-        let _clocks = clocks::Clocks::new(cx.device.CLOCK)
-            .enable_ext_hfosc()
-            .set_lfclk_src_external(clocks::LfOscConfiguration::NoExternalNoBypass)
-            .start_lfclk();
+        // Start the high frequency crystal and the low frequency clock
+        clock::init_clocks(cx.device.CLOCK, LfClkSource::Crystal);
 
         defmt::info!("Initialize...");
 
+        // The cycle counter times the display clear
+        let mut core = cx.core;
+        core.DCB.enable_trace();
+        core.DWT.enable_cycle_counter();
+
         cx.device.TIMER0.set_periodic();
         cx.device.TIMER0.enable_interrupt();
         cx.device.TIMER0.timer_start(1_000_000u32);
@@ -136,7 +154,7 @@ const APP: () = {
             uarte::Baudrate::BAUD115200,
         );
 
-        let delay = hal::Delay::new(cx.core.SYST);
+        let delay = hal::Delay::new(core.SYST);
         let spi = spi::Spim::new(
             cx.device.SPIM3,
             spi::Pins {
@@ -167,9 +185,17 @@ const APP: () = {
             spim::Frequency::M4,
             spim::MODE_0,
             0,
-        );
+        )
+        .unwrap();
 
-        let lcd = st7735s::ST7735::new(spi, false, true, 80, 160);
+        let lcd = st7735s::ST7735::new_with_orientation(
+            spi,
+            false,
+            true,
+            80,
+            160,
+            Orientation::Landscape,
+        );
 
         defmt::info!("... done");
 
@@ -216,9 +242,8 @@ const APP: () = {
             .rtc_0
             .is_event_triggered(hal::rtc::RtcInterrupt::Tick);
         let timer_last = *cx.resources.timer_1_last;
-        let timer_now = cx.resources.timer_1.read_counter();
-        let elapsed = timer_now.saturating_sub(timer_last);
-        defmt::info!("RTC 0: {}", elapsed);
+        let (timer_now, elapsed) = utilities::timer::elapsed_ms(cx.resources.timer_1, timer_last);
+        defmt::info!("RTC 0: {} ms", elapsed);
 
         let button_4 = cx.resources.button_4;
         let led_4 = cx.resources.led_4;
@@ -241,20 +266,43 @@ const APP: () = {
         let led_2 = cx.resources.led_2;
         let uart = cx.resources.uart;
         let lcd = cx.resources.lcd;
+        let delay = cx.resources.delay;
+
+        if let Err(error) = lcd.init(delay) {
+            defmt::error!("Failed to initialize display, {}", error);
+        }
+        // Initialize at 4 MHz, draw at 8 MHz
+        lcd.spi_mut().set_frequency(spim::Frequency::M8);
+        lcd.set_offset_centered();
+
+        // Compare clearing with a repeated buffer to packing every pixel
+        let pixels = 160 * 80;
+        let start = DWT::cycle_count();
+        let solid = lcd.fill_solid(0, 0, 159, 79, 0);
+        let solid_cycles = DWT::cycle_count().wrapping_sub(start);
+        let start = DWT::cycle_count();
+        let streamed = lcd.set_pixels(0, 0, 159, 79, core::iter::repeat(0).take(pixels));
+        let streamed_cycles = DWT::cycle_count().wrapping_sub(start);
+        if solid.and(streamed).is_ok() {
+            defmt::info!(
+                "Clear, fill_solid {=u32} us, set_pixels {=u32} us",
+                solid_cycles / CYCLES_PER_MICRO,
+                streamed_cycles / CYCLES_PER_MICRO
+            );
+        }
 
-        let _ = lcd.init(cx.resources.delay);
-        let dx = (st7735s::ST7735_ROWS - 160) / 2;
-        let dy = (st7735s::ST7735_COLS - 80) / 2;
-        lcd.set_offset(dx, dy);
-        let _ = lcd.set_orientation(Orientation::Landscape);
         let style = PrimitiveStyleBuilder::new()
             .fill_color(Rgb565::BLACK)
             .build();
         let backdrop = Rectangle::new(Point::new(0, 0), Point::new(160, 81)).into_styled(style);
-        let _ = backdrop.draw(lcd);
+        if let Err(error) = backdrop.draw(lcd) {
+            defmt::error!("Failed to draw backdrop, {}", error);
+        }
+        let title = "Rust on nRF52833-DK";
+        let x = text::center_x(title, ProFont12Point::CHARACTER_SIZE.width, 160);
         let _ = egtext!(
-            text = "Rust on nRF52833-DK\n\n",
-            top_left = (5, 0),
+            text = title,
+            top_left = (x, 0),
             style = text_style!(
                 font = ProFont12Point,
                 text_color = Rgb565::new(0xff, 0x8c, 0x00)
@@ -264,16 +312,35 @@ const APP: () = {
 
         let _ = write!(uart, "Idle\r\n");
 
+        // Holding button 2 for a second toggles display sleep
+        let mut held_ms = 0u16;
         loop {
             match button_2.is_low() {
                 Ok(true) => {
                     let _ = led_2.set_low();
+                    if held_ms < LONG_PRESS_MS {
+                        held_ms += POLL_MS;
+                        if held_ms >= LONG_PRESS_MS {
+                            let result = if lcd.is_sleeping() {
+                                defmt::info!("Wake display");
+                                lcd.wake(delay)
+                            } else {
+                                defmt::info!("Sleep display");
+                                lcd.sleep(delay)
+                            };
+                            if let Err(error) = result {
+                                defmt::error!("Failed to change display power, {}", error);
+                            }
+                        }
+                    }
                 }
                 Ok(false) => {
                     let _ = led_2.set_high();
+                    held_ms = 0;
                 }
                 Err(_) => {}
             }
+            delay.delay_ms(POLL_MS);
         }
     }
 };
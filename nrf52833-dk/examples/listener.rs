@@ -3,39 +3,117 @@
 
 use nrf52833_dk as _;
 
-use rtic::app;
+use rtic::{app, Mutex};
+
+use core::fmt::Write;
 
 use crate::hal::pac;
 use nrf52833_hal as hal;
 
-use hal::{clocks, gpio, timer::Instance, uarte};
+use hal::{gpio, timer::Instance, uarte};
 
 use bbqueue::{self, BBBuffer, ConstBBBuffer};
 
-use psila_nrf52::radio::{Radio, MAX_PACKET_LENGHT};
+use psila_nrf52::radio::MAX_PACKET_LENGHT;
+use utilities::clock::{self, LfClkSource};
+use utilities::radio::{validate_frame, Radio, Stats};
+use utilities::uart::{LineBuffer, UarteReceiver};
+
+/// Size of the receive timestamp stored in front of each packet
+const TIMESTAMP_LENGTH: usize = 4;
+
+/// Message type of frames forwarded to the host
+///
+/// Timestamped frames carry the little endian receive time in microseconds
+/// in front of the frame. Host tools reading `RadioReceive` expect the bare
+/// frame, so the timestamped payload uses a message type of its own.
+#[cfg(feature = "timestamp")]
+const RECEIVE_MESSAGE: esercom::MessageType = esercom::MessageType::RadioReceiveTimestamped;
+#[cfg(not(feature = "timestamp"))]
+const RECEIVE_MESSAGE: esercom::MessageType = esercom::MessageType::RadioReceive;
+
+/// Bytes of the stored timestamp forwarded with each frame
+#[cfg(feature = "timestamp")]
+const FORWARDED_TIMESTAMP_LENGTH: usize = TIMESTAMP_LENGTH;
+#[cfg(not(feature = "timestamp"))]
+const FORWARDED_TIMESTAMP_LENGTH: usize = 0;
 
 // Use a packet buffer that can hold 16 packages
 pub(crate) use bbqueue::consts::U2048 as PacketBufferSize;
 
 static PKT_BUFFER: BBBuffer<PacketBufferSize> = BBBuffer(ConstBBBuffer::new());
 
+/// Packet counters, logged every second
+static STATS: Stats = Stats::new();
+
+/// Maximum length of a command line received over UART
+const COMMAND_LINE_LENGTH: usize = 16;
+
+/// Commands received over UART
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Command {
+    /// Switch radio channel, `C<nn>`
+    Channel(u8),
+    /// Set transmission power in dBm, `P<n>`
+    Power(i8),
+}
+
+/// Parse a signed decimal number
+fn parse_number(text: &[u8]) -> Option<i32> {
+    let (negative, digits) = match text.split_first() {
+        Some((b'-', digits)) => (true, digits),
+        _ => (false, text),
+    };
+    if digits.is_empty() || digits.len() > 3 {
+        return None;
+    }
+    let mut value = 0i32;
+    for digit in digits {
+        if !digit.is_ascii_digit() {
+            return None;
+        }
+        value = value * 10 + i32::from(digit - b'0');
+    }
+    Some(if negative { -value } else { value })
+}
+
+/// Parse a command line, channels must be within 11 to 26 and power within
+/// -40 to 8 dBm
+fn parse_command(line: &[u8]) -> Option<Command> {
+    match line.split_first() {
+        Some((b'C', number)) | Some((b'c', number)) => match parse_number(number)? {
+            channel @ 11..=26 => Some(Command::Channel(channel as u8)),
+            _ => None,
+        },
+        Some((b'P', number)) | Some((b'p', number)) => match parse_number(number)? {
+            power @ -40..=8 => Some(Command::Power(power as i8)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
 #[app(device = crate::hal::pac, peripherals = true)]
 const APP: () = {
     struct Resources {
         uart: uarte::Uarte<pac::UARTE0>,
+        uart_rx: UarteReceiver<pac::UARTE0>,
+        /// Last command line received, an error for an invalid line
+        #[init(None)]
+        command: Option<Result<Command, ()>>,
         radio: Radio,
         rx_producer: bbqueue::Producer<'static, PacketBufferSize>,
         rx_consumer: bbqueue::Consumer<'static, PacketBufferSize>,
         timer: pac::TIMER0,
+        timestamp_timer: pac::TIMER2,
     }
 
     #[init]
     fn init(cx: init::Context) -> init::LateResources {
-        // Configure to use external clocks, and start them
-        let _clocks = clocks::Clocks::new(cx.device.CLOCK)
-            .enable_ext_hfosc()
-            .set_lfclk_src_external(clocks::LfOscConfiguration::NoExternalNoBypass)
-            .start_lfclk();
+        static mut RX_BUFFERS: [u8; 2] = [0; 2];
+
+        // Start the high frequency crystal and the low frequency clock
+        clock::init_clocks(cx.device.CLOCK, LfClkSource::Crystal);
 
         defmt::info!("Initialize");
 
@@ -43,6 +121,9 @@ const APP: () = {
         cx.device.TIMER0.enable_interrupt();
         cx.device.TIMER0.timer_start(1_000_000u32);
 
+        // Microsecond packet timestamps, wrapping after about 71.6 minutes
+        utilities::timer::start_free_running(&cx.device.TIMER2);
+
         let port0 = gpio::p0::Parts::new(cx.device.P0);
         let uart = uarte::Uarte::new(
             cx.device.UARTE0,
@@ -63,6 +144,8 @@ const APP: () = {
             uarte::Parity::EXCLUDED,
             uarte::Baudrate::BAUD115200,
         );
+        // Keep receiving while idle writes packets to the host
+        let uart_rx = UarteReceiver::start(&uart, RX_BUFFERS);
         let (q_producer, q_consumer) = PKT_BUFFER.try_split().unwrap();
 
         let mut radio = Radio::new(cx.device.RADIO);
@@ -72,28 +155,43 @@ const APP: () = {
 
         init::LateResources {
             uart,
+            uart_rx,
             radio,
             rx_producer: q_producer,
             rx_consumer: q_consumer,
             timer: cx.device.TIMER0,
+            timestamp_timer: cx.device.TIMER2,
         }
     }
 
-    #[task(binds = RADIO, resources = [radio, rx_producer])]
+    #[task(binds = RADIO, resources = [radio, rx_producer, timestamp_timer])]
     fn radio(cx: radio::Context) {
+        // Capture the time first, so that it is close to the end of the frame
+        let timestamp = cx.resources.timestamp_timer.read_counter();
         let radio = cx.resources.radio;
         let queue = cx.resources.rx_producer;
 
-        match queue.grant_exact(MAX_PACKET_LENGHT) {
+        match queue.grant_exact(TIMESTAMP_LENGTH + MAX_PACKET_LENGHT) {
             Ok(mut grant) => {
-                if grant.buf().len() < MAX_PACKET_LENGHT {
+                if grant.buf().len() < TIMESTAMP_LENGTH + MAX_PACKET_LENGHT {
                     defmt::error!("No room in the buffer");
+                    STATS.dropped();
                     grant.commit(0);
                 } else {
-                    match radio.receive_slice(grant.buf()) {
-                        Ok(packet_len) => {
-                            grant.commit(packet_len);
-                        }
+                    let (time, packet) = grant.buf().split_at_mut(TIMESTAMP_LENGTH);
+                    match radio.receive_slice(packet) {
+                        Ok(packet_len) => match validate_frame(&packet[..packet_len]) {
+                            Ok(()) => {
+                                time.copy_from_slice(&timestamp.to_le_bytes());
+                                STATS.received();
+                                grant.commit(TIMESTAMP_LENGTH + packet_len);
+                            }
+                            Err(_) => {
+                                defmt::warn!("Dropping malformed frame");
+                                STATS.malformed();
+                                grant.commit(0);
+                            }
+                        },
                         Err(_) => (),
                     }
                 }
@@ -103,6 +201,20 @@ const APP: () = {
                 let mut buffer = [0u8; MAX_PACKET_LENGHT];
                 let _ = radio.receive(&mut buffer);
                 defmt::error!("Failed to queue packet");
+                STATS.dropped();
+            }
+        }
+    }
+
+    #[task(binds = UARTE0_UART0, resources = [uart_rx, command])]
+    fn uart_rx(cx: uart_rx::Context) {
+        static mut LINE: LineBuffer<COMMAND_LINE_LENGTH> = LineBuffer::new();
+
+        if let Some(byte) = cx.resources.uart_rx.on_interrupt() {
+            match LINE.push(byte) {
+                Some(Ok(line)) => *cx.resources.command = Some(parse_command(line).ok_or(())),
+                Some(Err(_)) => *cx.resources.command = Some(Err(())),
+                None => (),
             }
         }
     }
@@ -110,20 +222,31 @@ const APP: () = {
     #[task(binds = TIMER0, resources = [timer])]
     fn timer(cx: timer::Context) {
         cx.resources.timer.timer_reset_event();
+        defmt::info!("Packets {}", STATS.snapshot());
     }
 
-    #[idle(resources = [rx_consumer, uart])]
-    fn idle(cx: idle::Context) -> ! {
+    #[idle(resources = [rx_consumer, uart, command, radio])]
+    fn idle(mut cx: idle::Context) -> ! {
         let mut host_packet = [0u8; MAX_PACKET_LENGHT * 2];
+        let mut payload = [0u8; TIMESTAMP_LENGTH + MAX_PACKET_LENGHT];
         let queue = cx.resources.rx_consumer;
         let uart = cx.resources.uart;
 
         loop {
             if let Ok(grant) = queue.read() {
-                let packet_length = grant[0] as usize;
+                // The payload is the optional receive timestamp followed by
+                // the frame without its length octet
+                let packet_length = grant[TIMESTAMP_LENGTH] as usize;
+                let timestamp =
+                    &grant[TIMESTAMP_LENGTH - FORWARDED_TIMESTAMP_LENGTH..TIMESTAMP_LENGTH];
+                let payload_length = timestamp.len() + packet_length - 1;
+                payload[..timestamp.len()].copy_from_slice(timestamp);
+                payload[timestamp.len()..payload_length].copy_from_slice(
+                    &grant[TIMESTAMP_LENGTH + 1..TIMESTAMP_LENGTH + packet_length],
+                );
                 match esercom::com_encode(
-                    esercom::MessageType::RadioReceive,
-                    &grant[1..packet_length],
+                    RECEIVE_MESSAGE,
+                    &payload[..payload_length],
                     &mut host_packet,
                 ) {
                     Ok(written) => {
@@ -133,7 +256,28 @@ const APP: () = {
                         defmt::error!("Failed to encode packet");
                     }
                 }
-                grant.release(packet_length);
+                grant.release(TIMESTAMP_LENGTH + packet_length);
+            }
+
+            // Command lines are collected by the UARTE interrupt
+            match cx.resources.command.lock(|command| command.take()) {
+                Some(Ok(Command::Channel(channel))) => {
+                    cx.resources.radio.lock(|radio| {
+                        radio.set_channel(channel);
+                        radio.receive_prepare();
+                    });
+                    let _ = write!(uart, "Channel {}\r\n", channel);
+                }
+                Some(Ok(Command::Power(power))) => {
+                    cx.resources
+                        .radio
+                        .lock(|radio| radio.set_transmission_power(power));
+                    let _ = write!(uart, "Power {} dBm\r\n", power);
+                }
+                Some(Err(())) => {
+                    let _ = write!(uart, "Invalid command\r\n");
+                }
+                None => (),
             }
         }
     }
@@ -0,0 +1,185 @@
+#![no_main]
+#![no_std]
+
+//! Radio helper self-test
+//!
+//! Checks the IEEE 802.15.4 frame helpers against known frames. Nothing has
+//! to be connected and nothing is transmitted.
+
+use nrf52833_dk as _;
+
+use cortex_m_rt::entry;
+
+use nrf52833_hal as hal;
+
+use hal::pac;
+
+use utilities::{
+    clock::{self, LfClkSource},
+    radio::{self, Address, Radio, RadioError, Stats, StatsSnapshot},
+};
+
+/// Beacon request with sequence number 0x2a
+const BEACON_REQUEST: [u8; 8] = [0x03, 0x08, 0x2a, 0xff, 0xff, 0xff, 0xff, 0x07];
+
+/// Data request with sequence number 1 from 00:11:22:33:44:55:66:77 to
+/// the coordinator of PAN 0x1a62
+const DATA_REQUEST: [u8; 16] = [
+    0x63, 0xc8, 0x01, 0x62, 0x1a, 0x00, 0x00, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11, 0x00, 0x04,
+];
+
+/// Acknowledge for sequence number 0x56, followed by its FCS
+const ACKNOWLEDGE: [u8; 5] = [0x02, 0x00, 0x56, 0x0b, 0x82];
+
+/// Logs the outcome of a test, returns true on pass
+fn report(name: &str, result: Result<bool, RadioError>) -> bool {
+    match result {
+        Ok(true) => {
+            defmt::info!("{}: pass", name);
+            true
+        }
+        Ok(false) => {
+            defmt::error!("{}: fail, unexpected output", name);
+            false
+        }
+        Err(error) => {
+            defmt::error!("{}: fail, {}", name, defmt::Debug2Format(&error));
+            false
+        }
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let peripherals = pac::Peripherals::take().unwrap();
+    clock::init_clocks(peripherals.CLOCK, LfClkSource::Crystal);
+
+    defmt::info!("Radio helper self-test");
+
+    let mut passed = 0;
+    let mut failed = 0;
+    let mut count = |pass: bool| {
+        if pass {
+            passed += 1;
+        } else {
+            failed += 1;
+        }
+    };
+
+    // The CRC-16/KERMIT check value
+    count(report(
+        "fcs, check value",
+        Ok(radio::fcs(b"123456789") == 0x2189),
+    ));
+
+    count(report(
+        "check_fcs, acknowledge",
+        Ok(radio::check_fcs(&ACKNOWLEDGE)),
+    ));
+
+    let mut corrupted = ACKNOWLEDGE;
+    corrupted[2] ^= 0x01;
+    count(report(
+        "check_fcs, corrupted acknowledge",
+        Ok(!radio::check_fcs(&corrupted)),
+    ));
+
+    let mut frame = [0u8; 5];
+    frame[..3].copy_from_slice(&ACKNOWLEDGE[..3]);
+    let result = radio::append_fcs(&mut frame, 3).map(|length| length == 5 && frame == ACKNOWLEDGE);
+    count(report("append_fcs, acknowledge", result));
+
+    let result = match radio::append_fcs(&mut frame[..4], 3) {
+        Err(RadioError::BufferTooShort) => Ok(true),
+        Err(error) => Err(error),
+        Ok(_) => Ok(false),
+    };
+    count(report("append_fcs, no room", result));
+
+    // Only a valid FCS is removed
+    let result = radio::strip_fcs(&ACKNOWLEDGE) == &ACKNOWLEDGE[..3]
+        && radio::strip_fcs(&corrupted) == &corrupted[..];
+    count(report("strip_fcs", Ok(result)));
+
+    // The radio appends the FCS, the frame ends with the command
+    let mut frame = [0u8; radio::BEACON_REQUEST_LENGTH];
+    let result = radio::beacon_request(&mut frame, 0x2a)
+        .map(|length| length == BEACON_REQUEST.len() && frame == BEACON_REQUEST);
+    count(report("beacon_request", result));
+
+    let mut frame = [0u8; 32];
+    let result = radio::data_request(
+        &mut frame,
+        1,
+        0x1a62,
+        Address::Short(0x0000),
+        0x0011_2233_4455_6677,
+    )
+    .map(|length| frame[..length] == DATA_REQUEST);
+    count(report("data_request", result));
+
+    // Nothing was received, the CRC status isn't OK
+    let mut radio = Radio::new(peripherals.RADIO);
+    count(report("crc_ok, nothing received", Ok(!radio.crc_ok())));
+
+    // The data request as received, a length octet, the frame and the FCS
+    let mut packet = [0u8; DATA_REQUEST.len() + 3];
+    packet[0] = packet.len() as u8;
+    packet[1..=DATA_REQUEST.len()].copy_from_slice(&DATA_REQUEST);
+    let result = radio::append_fcs(&mut packet[1..], DATA_REQUEST.len()).map(|_| {
+        let any = radio.accepts(&packet);
+        radio.set_address(0x1a62, 0x0000);
+        let own = radio.accepts(&packet);
+        radio.set_address(0x1a62, 0x1234);
+        let other_address = radio.accepts(&packet);
+        radio.set_address(0x0bad, 0x0000);
+        let other_pan = radio.accepts(&packet);
+        radio.clear_address();
+        any && own && !other_address && !other_pan
+    });
+    count(report("accepts, short address", result));
+
+    let result = radio::validate_frame(&packet).map(|_| true);
+    count(report("validate_frame, data request", result));
+
+    // A length beyond the buffer, a frame cut within the MAC header and an
+    // empty buffer are all truncated
+    let truncated =
+        |packet: &[u8]| radio::validate_frame(packet) == Err(RadioError::TruncatedFrame);
+    let mut short = packet;
+    short[0] = 4;
+    count(report(
+        "validate_frame, short buffer",
+        Ok(truncated(&packet[..10]) && truncated(&short) && truncated(&[])),
+    ));
+
+    // Each counter counts on its own, a reset returns the counts and clears
+    // them
+    let stats = Stats::new();
+    stats.received();
+    stats.received();
+    stats.transmitted();
+    stats.dropped();
+    stats.cca_busy();
+    stats.malformed();
+    let expected = StatsSnapshot {
+        received: 2,
+        transmitted: 1,
+        dropped: 1,
+        cca_busy: 1,
+        malformed: 1,
+    };
+    let counted = stats.snapshot() == expected;
+    let reset = stats.reset() == expected;
+    count(report(
+        "Stats, increments and reset",
+        Ok(counted && reset && stats.snapshot() == StatsSnapshot::default()),
+    ));
+
+    if failed == 0 {
+        defmt::info!("All {} tests passed", passed);
+    } else {
+        defmt::error!("{} of {} tests failed", failed, passed + failed);
+    }
+    nrf52833_dk::exit()
+}
@@ -0,0 +1,184 @@
+#![no_main]
+#![no_std]
+
+//! Active scan for IEEE 802.15.4 networks
+//!
+//! Broadcasts a beacon request on each channel, 11 to 26, and collects the
+//! beacons received in response. Found networks are logged through defmt as
+//! they are heard and listed over UART when the scan is done.
+
+use nrf52833_dk as _;
+
+use rtic::app;
+
+use core::fmt::Write;
+
+use crate::hal::pac;
+use nrf52833_hal as hal;
+
+use hal::{gpio, timer::Instance, uarte};
+
+use psila_nrf52::radio::MAX_PACKET_LENGHT;
+use utilities::clock::{self, LfClkSource};
+use utilities::radio::{
+    beacon_request, parse_beacon, scan_duration_micros, Beacon, Radio, BEACON_REQUEST_LENGTH,
+};
+
+/// First channel scanned
+const FIRST_CHANNEL: u8 = 11;
+/// Last channel scanned
+const LAST_CHANNEL: u8 = 26;
+/// Scan duration exponent, about 138 ms per channel
+const SCAN_EXPONENT: u8 = 3;
+/// Number of networks remembered
+const MAX_NETWORKS: usize = 16;
+
+/// Network found during the scan
+#[derive(Clone, Copy)]
+struct Network {
+    channel: u8,
+    beacon: Beacon,
+}
+
+/// Switches to `channel` and broadcasts a beacon request
+fn request_beacons(radio: &mut Radio, channel: u8, sequence: u8) {
+    let mut frame = [0u8; BEACON_REQUEST_LENGTH];
+    radio.set_channel(channel);
+    radio.receive_prepare();
+    if let Ok(length) = beacon_request(&mut frame, sequence) {
+        let _ = radio.queue_transmission(&frame[..length]);
+    }
+}
+
+#[app(device = crate::hal::pac, peripherals = true)]
+const APP: () = {
+    struct Resources {
+        uart: uarte::Uarte<pac::UARTE0>,
+        radio: Radio,
+        timer: pac::TIMER0,
+        #[init(FIRST_CHANNEL)]
+        channel: u8,
+        #[init(0)]
+        sequence: u8,
+        #[init([None; MAX_NETWORKS])]
+        networks: [Option<Network>; MAX_NETWORKS],
+    }
+
+    #[init]
+    fn init(cx: init::Context) -> init::LateResources {
+        // Start the high frequency crystal and the low frequency clock
+        clock::init_clocks(cx.device.CLOCK, LfClkSource::Crystal);
+
+        defmt::info!("Initialize");
+
+        let port0 = gpio::p0::Parts::new(cx.device.P0);
+        let uart = uarte::Uarte::new(
+            cx.device.UARTE0,
+            uarte::Pins {
+                txd: port0
+                    .p0_06
+                    .into_push_pull_output(gpio::Level::High)
+                    .degrade(),
+                rxd: port0.p0_08.into_floating_input().degrade(),
+                cts: Some(port0.p0_07.into_floating_input().degrade()),
+                rts: Some(
+                    port0
+                        .p0_05
+                        .into_push_pull_output(gpio::Level::High)
+                        .degrade(),
+                ),
+            },
+            uarte::Parity::EXCLUDED,
+            uarte::Baudrate::BAUD115200,
+        );
+
+        let mut radio = Radio::new(cx.device.RADIO);
+        radio.set_transmission_power(8);
+        request_beacons(&mut radio, FIRST_CHANNEL, 0);
+
+        // Move on to the next channel when the scan duration has passed
+        cx.device.TIMER0.set_periodic();
+        cx.device.TIMER0.enable_interrupt();
+        cx.device
+            .TIMER0
+            .timer_start(scan_duration_micros(SCAN_EXPONENT));
+
+        init::LateResources {
+            uart,
+            radio,
+            timer: cx.device.TIMER0,
+        }
+    }
+
+    #[task(binds = RADIO, resources = [radio, channel, networks])]
+    fn radio(cx: radio::Context) {
+        let mut packet = [0u8; MAX_PACKET_LENGHT];
+        let packet_len = match cx.resources.radio.receive(&mut packet) {
+            Ok(packet_len) if packet_len > 0 => packet_len,
+            _ => return,
+        };
+        let beacon = match parse_beacon(&packet[..packet_len]) {
+            Ok(Some(beacon)) => beacon,
+            Ok(None) => return,
+            Err(_) => {
+                defmt::warn!("Dropping malformed beacon");
+                return;
+            }
+        };
+        let channel = *cx.resources.channel;
+        let networks = cx.resources.networks;
+        // Several requests may be answered by the same device
+        let known = networks.iter().flatten().any(|network| {
+            network.channel == channel
+                && network.beacon.pan_id == beacon.pan_id
+                && network.beacon.address == beacon.address
+        });
+        if known {
+            return;
+        }
+        match networks.iter_mut().find(|network| network.is_none()) {
+            Some(slot) => {
+                defmt::info!("Channel {} {}", channel, beacon);
+                *slot = Some(Network { channel, beacon });
+            }
+            None => {
+                defmt::warn!("Too many networks, dropping PAN {=u16:04x}", beacon.pan_id);
+            }
+        }
+    }
+
+    #[task(binds = TIMER0, resources = [timer, radio, channel, sequence, networks, uart])]
+    fn timer(cx: timer::Context) {
+        cx.resources.timer.timer_reset_event();
+        let channel = cx.resources.channel;
+        if *channel < LAST_CHANNEL {
+            *channel += 1;
+            *cx.resources.sequence = cx.resources.sequence.wrapping_add(1);
+            request_beacons(cx.resources.radio, *channel, *cx.resources.sequence);
+            return;
+        }
+
+        cx.resources.timer.disable_interrupt();
+        let uart = cx.resources.uart;
+        let mut count = 0;
+        for network in cx.resources.networks.iter().flatten() {
+            let beacon = &network.beacon;
+            let _ = write!(
+                uart,
+                "Channel {} PAN {:04x} permit join {} protocol {:?}",
+                network.channel, beacon.pan_id, beacon.permit_join, beacon.protocol_id
+            );
+            if let Some(zigbee) = beacon.zigbee {
+                let _ = write!(
+                    uart,
+                    " extended PAN {:016x} stack profile {}",
+                    zigbee.extended_pan_id, zigbee.stack_profile
+                );
+            }
+            let _ = write!(uart, "\r\n");
+            count += 1;
+        }
+        let _ = write!(uart, "Scan done, {} networks found\r\n", count);
+        defmt::info!("Scan done, {} networks found", count);
+    }
+};
@@ -0,0 +1,385 @@
+#![no_main]
+#![no_std]
+
+//! SPIM loopback self-test
+//!
+//! Connect P0.03 (MOSI) to P0.04 (MISO) with a jumper. Every byte sent is
+//! received back, so the received data shows what was clocked out, including
+//! the `orc` fill when the receive buffer is the longer one. P0.28 is used
+//! as chip select and can be left unconnected. The test runs on SPIM3, the
+//! only instance with a hardware chip select.
+
+use nrf52833_dk as _;
+
+use cortex_m_rt::entry;
+
+use bbqueue::BBBuffer;
+
+use embedded_hal::digital::v2::OutputPin;
+
+use nrf52833_hal as hal;
+
+use hal::{gpio, pac, spim};
+
+use utilities::{
+    clock::{self, LfClkSource},
+    spi,
+};
+
+/// Byte clocked out once the transmit buffer is exhausted
+const ORC: u8 = 0xa5;
+
+/// Logs the outcome of a test, returns true on pass
+fn report(name: &str, result: Result<bool, spi::Error>) -> bool {
+    match result {
+        Ok(true) => {
+            defmt::info!("{}: pass", name);
+            true
+        }
+        Ok(false) => {
+            defmt::error!("{}: fail, data mismatch", name);
+            false
+        }
+        Err(error) => {
+            defmt::error!("{}: fail, {}", name, defmt::Debug2Format(&error));
+            false
+        }
+    }
+}
+
+/// Chip select recording each level it is driven to, with the number of
+/// bytes the SPIM had transmitted at that point
+struct CsRecorder {
+    levels: [(bool, u32); 2],
+    count: usize,
+}
+
+impl CsRecorder {
+    fn new() -> Self {
+        Self {
+            levels: [(true, 0); 2],
+            count: 0,
+        }
+    }
+
+    fn record(&mut self, high: bool) -> Result<(), ()> {
+        let amount = unsafe { (*pac::SPIM3::ptr()).txd.amount.read().bits() };
+        if let Some(level) = self.levels.get_mut(self.count) {
+            *level = (high, amount);
+        }
+        self.count += 1;
+        Ok(())
+    }
+}
+
+impl OutputPin for CsRecorder {
+    type Error = ();
+
+    fn set_low(&mut self) -> Result<(), ()> {
+        self.record(false)
+    }
+
+    fn set_high(&mut self) -> Result<(), ()> {
+        self.record(true)
+    }
+}
+
+/// Small queue for the `read_into_queue` test
+static QUEUE: BBBuffer<16> = BBBuffer::new();
+
+/// Chip select pin
+const CS_PIN: u32 = 28;
+
+/// Is the chip select driven low as a GPIO?
+fn cs_driven_low() -> bool {
+    unsafe { (*pac::P0::ptr()).out.read().bits() }
+    &(1 << CS_PIN) == 0
+}
+
+/// Bytes 0, 1, 2, ... offset by `seed`
+fn pattern(buffer: &mut [u8], seed: u8) {
+    for (n, byte) in buffer.iter_mut().enumerate() {
+        *byte = seed.wrapping_add(n as u8);
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    // Two DMA transfers long, in RAM as EasyDMA can't read from flash
+    static mut LARGE: [u8; spi::EASY_DMA_SIZE + 100] = [0; spi::EASY_DMA_SIZE + 100];
+
+    let peripherals = pac::Peripherals::take().unwrap();
+    clock::init_clocks(peripherals.CLOCK, LfClkSource::Crystal);
+
+    let port0 = gpio::p0::Parts::new(peripherals.P0);
+    let pins = spi::Pins {
+        sck: port0
+            .p0_27
+            .into_push_pull_output(gpio::Level::Low)
+            .degrade(),
+        mosi: Some(
+            port0
+                .p0_03
+                .into_push_pull_output(gpio::Level::Low)
+                .degrade(),
+        ),
+        miso: Some(port0.p0_04.into_floating_input().degrade()),
+        csn: Some(
+            port0
+                .p0_28
+                .into_push_pull_output(gpio::Level::High)
+                .degrade(),
+        ),
+        dcx: None,
+    };
+    let clean = pins.validate().map(|_| true);
+
+    // SCK given as MOSI too, the SPIM is handed back with the error
+    let conflicting = spi::Pins {
+        sck: unsafe { gpio::Pin::from_psel_bits(27) },
+        mosi: Some(unsafe { gpio::Pin::from_psel_bits(27) }),
+        miso: None,
+        csn: None,
+        dcx: None,
+    };
+    let (conflict, spim3) = match spi::Spim::new(
+        peripherals.SPIM3,
+        conflicting,
+        spim::Frequency::M4,
+        spim::MODE_0,
+        ORC,
+    ) {
+        Err(spi::NewError { error, spim }) => (error, spim),
+        Ok(_) => defmt::panic!("conflicting pins accepted"),
+    };
+
+    let mut spim = spi::Spim::new(spim3, pins, spim::Frequency::M4, spim::MODE_0, ORC).unwrap();
+
+    // SPIM2 has no DCX pin, only its clock is connected, to P0.29
+    let data_only = spi::Pins {
+        sck: port0
+            .p0_29
+            .into_push_pull_output(gpio::Level::Low)
+            .degrade(),
+        mosi: None,
+        miso: None,
+        csn: None,
+        dcx: None,
+    };
+    let mut spim2 = spi::Spim::new(
+        peripherals.SPIM2,
+        data_only,
+        spim::Frequency::M4,
+        spim::MODE_0,
+        ORC,
+    )
+    .unwrap();
+
+    defmt::info!("SPIM loopback, P0.03 must be connected to P0.04");
+
+    let mut passed = 0;
+    let mut failed = 0;
+    let mut count = |pass: bool| {
+        if pass {
+            passed += 1;
+        } else {
+            failed += 1;
+        }
+    };
+
+    count(report(
+        "Pins::validate, clean",
+        clean.map_err(spi::Error::PinConflict),
+    ));
+    let expected = spi::PinConflict {
+        first: spi::PinRole::Sck,
+        second: spi::PinRole::Mosi,
+    };
+    count(report(
+        "Spim::new, conflicting pins",
+        Ok(matches!(conflict, spi::Error::PinConflict(found) if found == expected)),
+    ));
+
+    // In place transfer, the buffer is received back unchanged
+    let mut expected = [0u8; 64];
+    pattern(&mut expected, 0x10);
+    let mut buffer = expected;
+    let result = spim.transfer(&mut buffer).map(|_| buffer == expected);
+    count(report("transfer", result));
+
+    // Separate buffers of the same length
+    let mut tx = [0u8; 32];
+    pattern(&mut tx, 0x40);
+    let mut rx = [0u8; 32];
+    let result = spim.transfer_split_even(&tx, &mut rx).map(|_| rx == tx);
+    count(report("transfer_split_even", result));
+
+    // The transaction stops at the shorter buffer
+    let mut rx = [0u8; 16];
+    let result = spim
+        .transfer_split_even(&tx, &mut rx)
+        .map(|_| rx[..] == tx[..16]);
+    count(report("transfer_split_even, short rx", result));
+
+    // The receive buffer is longer, the rest is filled with the ORC value
+    let mut tx = [0u8; 8];
+    pattern(&mut tx, 0x80);
+    let mut rx = [0u8; 24];
+    let result = spim
+        .transfer_split_uneven(&tx, &mut rx)
+        .map(|_| rx[..8] == tx[..] && rx[8..].iter().all(|b| *b == ORC));
+    count(report("transfer_split_uneven, ORC fill", result));
+
+    // The over-read character can be changed between transfers
+    spim.set_orc(0xff);
+    let mut rx = [0u8; 24];
+    let result = spim
+        .transfer_split_uneven(&tx, &mut rx)
+        .map(|_| rx[..8] == tx[..] && rx[8..].iter().all(|b| *b == 0xff));
+    count(report("set_orc, 0xff fill", result));
+    spim.set_orc(ORC);
+
+    // A three byte filler continues across the copy buffer boundaries,
+    // which don't fall on a pattern boundary
+    let fill = [0xff, 0x00, 0x55];
+    let mut rx = [0u8; 2 * spi::FORCE_COPY_BUFFER_SIZE + 100];
+    let result = spim
+        .transfer_split_uneven_fill(&tx, &mut rx, &fill)
+        .map(|_| {
+            rx[..8] == tx[..]
+                && rx[8..]
+                    .iter()
+                    .enumerate()
+                    .all(|(n, b)| *b == fill[n % fill.len()])
+        });
+    count(report(
+        "transfer_split_uneven_fill, pattern across chunks",
+        result,
+    ));
+
+    // The transmit buffer is longer, the extra received bytes are discarded
+    let mut tx = [0u8; 24];
+    pattern(&mut tx, 0xc0);
+    let mut rx = [0u8; 8];
+    let result = spim
+        .transfer_split_uneven(&tx, &mut rx)
+        .map(|_| rx[..] == tx[..8]);
+    count(report("transfer_split_uneven, discard", result));
+
+    // Transmit only
+    let result = spim.write(&tx).map(|_| true);
+    count(report("write", result));
+
+    // The looped back command is discarded, the response holds the bytes
+    // clocked in after it, which are the ORC fill
+    let mut command = [0u8; 4];
+    pattern(&mut command, 0x20);
+    let mut response = [0u8; 6];
+    let result = spim
+        .command_read(&command, &mut response)
+        .map(|_| response.iter().all(|b| *b == ORC));
+    count(report("command_read, response after the command", result));
+
+    // The bytes are received into queue grants, limited by the room left
+    // at the end of the queue. A full queue receives nothing.
+    let (mut producer, mut consumer) = QUEUE.try_split().unwrap();
+    let mut command = [0u8; 4];
+    pattern(&mut command, 0x30);
+    let result =
+        spim.read_into_queue(&mut producer, &command, 10)
+            .and_then(|first| {
+                let second = spim.read_into_queue(&mut producer, &command, 10)?;
+                let full = spim.read_into_queue(&mut producer, &command, 10);
+                let received = match consumer.read() {
+                    Ok(grant) => {
+                        grant.len() == 16
+                            && grant[..4] == command
+                            && grant[4..10].iter().all(|b| *b == ORC)
+                            && grant[10..14] == command
+                    }
+                    Err(_) => false,
+                };
+                Ok(first == 10
+                    && second == 6
+                    && matches!(full, Err(spi::Error::QueueFull))
+                    && received)
+            });
+    count(report("read_into_queue, short grant", result));
+
+    // The callback runs after each of the two DMA transfers with the total
+    // written so far
+    let mut progress = [0usize; 3];
+    let mut calls = 0;
+    let result = spim
+        .write_with_progress(&LARGE[..], |done| {
+            if let Some(entry) = progress.get_mut(calls) {
+                *entry = done;
+            }
+            calls += 1;
+        })
+        .map(|_| calls == 2 && progress == [spi::EASY_DMA_SIZE, LARGE.len(), 0]);
+    count(report("write_with_progress, callback per chunk", result));
+
+    // A polled transfer sets the END event, which stays set until cleared
+    let mut tx = [0u8; 16];
+    pattern(&mut tx, 0x60);
+    let mut rx = [0u8; 16];
+    let idle = !spim.transfer_done();
+    let result = unsafe { spim.start_transfer(&tx, &mut rx) }.map(|_| {
+        let mut polls = 0;
+        while !spim.transfer_done() && polls < 100_000 {
+            polls += 1;
+        }
+        let done = spim.transfer_done() && spim.transfer_done();
+        spim.clear_transfer_done();
+        spim.flush();
+        idle && done && !spim.transfer_done() && spim.last_amounts() == (16, 16) && rx == tx
+    });
+    count(report("transfer_done, event read and clear", result));
+
+    // Flush waits for a started transfer and clears its END event
+    let mut rx = [0u8; 16];
+    let result = unsafe { spim.start_transfer(&tx, &mut rx) }.map(|_| {
+        spim.flush();
+        !spim.transfer_done() && rx == tx
+    });
+    count(report("flush, waits for a started transfer", result));
+
+    // The chip select goes low before the 5 byte transfer, while the amount
+    // still is from the 3 byte write, and high after it
+    let result = spim.write(&tx[..3]).and_then(|_| {
+        let mut cs = CsRecorder::new();
+        let mut buffer = [0u8; 5];
+        spim.transfer_with_cs(&mut cs, &mut buffer)?;
+        Ok(cs.count == 2 && cs.levels == [(false, 3), (true, 5)])
+    });
+    count(report("transfer_with_cs, chip select order", result));
+
+    // The chip select is held low across both writes and released after
+    let result = spim
+        .with_cs(|spim| {
+            spim.write(&tx)?;
+            let first = cs_driven_low();
+            spim.write(&tx)?;
+            Ok(first && cs_driven_low())
+        })
+        .map(|held| held && !cs_driven_low());
+    count(report("with_cs, held across two writes", result));
+
+    // Without a DCX pin command bytes are refused, data is still sent
+    let result = match spim2.transfer_dcx(&tx[..4], 1) {
+        Err(spi::Error::UnsupportedPin(spi::PinRole::Dcx)) => {
+            spim2.transfer_dcx(&tx[..4], 0).map(|_| true)
+        }
+        Err(error) => Err(error),
+        Ok(_) => Ok(false),
+    };
+    count(report("transfer_dcx, command bytes without DCX", result));
+
+    if failed == 0 {
+        defmt::info!("All {} tests passed", passed);
+    } else {
+        defmt::error!("{} of {} tests failed", failed, passed + failed);
+    }
+    nrf52833_dk::exit()
+}
@@ -0,0 +1,117 @@
+#![no_main]
+#![no_std]
+
+//! SSD1306 driver self-test
+//!
+//! The driver writes to a recording SPI sink instead of a panel, the tests
+//! check the commands and the page buffer the driver sends. Nothing has to
+//! be connected.
+
+use nrf52833_dk as _;
+
+use cortex_m_rt::entry;
+
+use embedded_graphics::{geometry::Size, DrawTarget};
+
+use utilities::{spi::SpiSendCommandData, ssd1306::Ssd1306};
+
+/// Set page address
+const PAGEADDR: u8 = 0x22;
+
+/// SPI sink keeping the last command and the last data transfer
+struct Recorder {
+    /// Last page address command with its parameters
+    pages: [u8; 3],
+    /// Last data transfer
+    data: [u8; 1024],
+    /// Length of the last data transfer
+    length: usize,
+}
+
+impl Recorder {
+    fn new() -> Self {
+        Self {
+            pages: [0; 3],
+            data: [0; 1024],
+            length: 0,
+        }
+    }
+}
+
+impl SpiSendCommandData for Recorder {
+    type Error = ();
+
+    fn send_command_data(&mut self, data: &[u8], command_bytes: u8) -> Result<(), ()> {
+        if command_bytes > 0 {
+            if data[0] == PAGEADDR && data.len() == self.pages.len() {
+                self.pages.copy_from_slice(data);
+            }
+        } else {
+            self.data[..data.len()].copy_from_slice(data);
+            self.length = data.len();
+        }
+        Ok(())
+    }
+}
+
+/// Logs the outcome of a test, returns true on pass
+fn report(name: &str, pass: bool) -> bool {
+    if pass {
+        defmt::info!("{}: pass", name);
+    } else {
+        defmt::error!("{}: fail, unexpected output", name);
+    }
+    pass
+}
+
+#[entry]
+fn main() -> ! {
+    defmt::info!("SSD1306 self-test");
+
+    let mut passed = 0;
+    let mut failed = 0;
+    let mut count = |pass: bool| {
+        if pass {
+            passed += 1;
+        } else {
+            failed += 1;
+        }
+    };
+
+    // Each byte is a column of eight rows, the top row in bit 0, and the
+    // pages follow each other
+    let mut display = Ssd1306::new(Recorder::new(), 128, 64);
+    display.set_pixel(0, 0, true);
+    display.set_pixel(1, 9, true);
+    display.set_pixel(127, 63, true);
+    let result = display.flush().is_ok();
+    let spi = display.free();
+    let lit = spi.data[..spi.length].iter().filter(|b| **b != 0).count();
+    count(report(
+        "flush, page encoding",
+        result
+            && spi.length == 1024
+            && spi.pages == [PAGEADDR, 0, 7]
+            && spi.data[0] == 0x01
+            && spi.data[128 + 1] == 0x02
+            && spi.data[7 * 128 + 127] == 0x80
+            && lit == 3,
+    ));
+
+    // A height below one page is raised to a page instead of underflowing
+    let mut display = Ssd1306::new(Recorder::new(), 128, 4);
+    let size = display.size();
+    let result = display.init().is_ok();
+    let spi = display.free();
+    count(report(
+        "new, height below a page",
+        result && size == Size::new(128, 8) && spi.pages == [PAGEADDR, 0, 0] && spi.length == 128,
+    ));
+
+    if failed == 0 {
+        defmt::info!("All {} tests passed", passed);
+    } else {
+        defmt::error!("{} of {} tests failed", failed, passed + failed);
+    }
+    nrf52833_dk::exit()
+}
@@ -0,0 +1,637 @@
+#![no_main]
+#![no_std]
+
+//! ST7735 driver self-test
+//!
+//! The driver writes to a recording SPI sink instead of a panel, the tests
+//! check the command stream the driver produces. Nothing has to be
+//! connected.
+
+use nrf52833_dk as _;
+
+use cortex_m_rt::entry;
+
+use embedded_graphics::{
+    drawable::Pixel,
+    geometry::{Point, Size},
+    pixelcolor::{Rgb565, RgbColor},
+    primitives::{Primitive, Rectangle},
+    style::PrimitiveStyle,
+    DrawTarget,
+};
+
+use embedded_hal::blocking::delay::DelayMs;
+
+use heapless::Vec;
+
+use profont::ProFont12Point;
+
+use utilities::{
+    display_queue::{DisplayCommand, DisplayDrain, DisplayQueue, PIXELS_PER_COMMAND},
+    spi::SpiSendCommandData,
+    st7735s::{self, ClipMode, DisplayError, Orientation, FILL_BUFFER_PIXELS},
+    text::BufferedText,
+};
+
+/// Column address set
+const CASET: u8 = 0x2a;
+/// Row address set
+const RASET: u8 = 0x2b;
+/// Memory write
+const RAMWR: u8 = 0x2c;
+/// Memory access control
+const MADCTL: u8 = 0x36;
+
+/// SPI sink recording the command stream sent by the driver
+struct Recorder {
+    /// Number of transfers
+    transfers: usize,
+    /// Number of bytes sent
+    bytes: usize,
+    /// FNV-1a hash of the stream, with the data / command state of each
+    /// byte
+    hash: u32,
+    /// Number of times each command was sent
+    commands: [u16; 256],
+    /// Parameter of the last MADCTL command
+    madctl: Option<u8>,
+}
+
+impl Recorder {
+    fn new() -> Self {
+        Self {
+            transfers: 0,
+            bytes: 0,
+            hash: 0x811c_9dc5,
+            commands: [0; 256],
+            madctl: None,
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Number of times `command` was sent
+    fn count(&self, command: u8) -> usize {
+        usize::from(self.commands[usize::from(command)])
+    }
+
+    fn update_hash(&mut self, byte: u8, command: bool) {
+        for value in [byte, command as u8].iter() {
+            self.hash = (self.hash ^ u32::from(*value)).wrapping_mul(0x0100_0193);
+        }
+    }
+}
+
+impl SpiSendCommandData for Recorder {
+    type Error = ();
+
+    fn send_command_data(&mut self, data: &[u8], command_bytes: u8) -> Result<(), ()> {
+        self.transfers += 1;
+        self.bytes += data.len();
+        let (command, data) = data.split_at(usize::from(command_bytes).min(data.len()));
+        for byte in command {
+            self.update_hash(*byte, true);
+            self.commands[usize::from(*byte)] += 1;
+        }
+        for byte in data {
+            self.update_hash(*byte, false);
+        }
+        if command == [MADCTL] {
+            self.madctl = data.first().copied();
+        }
+        Ok(())
+    }
+}
+
+type Display = st7735s::ST7735<Recorder>;
+
+/// Delay adding up the requested milliseconds instead of waiting
+struct DelayRecorder {
+    total: u32,
+}
+
+impl DelayMs<u16> for DelayRecorder {
+    fn delay_ms(&mut self, ms: u16) {
+        self.total += u32::from(ms);
+    }
+}
+
+/// Bytes of free stack filled with a pattern by `stack_usage`
+const STACK_PROBE: usize = 40 * 1024;
+
+/// Logs the outcome of a test, returns true on pass
+fn report(name: &str, result: Result<bool, DisplayError>) -> bool {
+    match result {
+        Ok(true) => {
+            defmt::info!("{}: pass", name);
+            true
+        }
+        Ok(false) => {
+            defmt::error!("{}: fail, unexpected output", name);
+            false
+        }
+        Err(error) => {
+            defmt::error!("{}: fail, {}", name, error);
+            false
+        }
+    }
+}
+
+/// Stack bytes used by `f`, found by filling the free stack with a pattern
+/// and checking how deep it was overwritten
+fn stack_usage<F: FnOnce()>(f: F) -> usize {
+    const PATTERN: u32 = 0xa5a5_a5a5;
+    // Leave room for the frames of this function
+    let top = (cortex_m::register::msp::read() as usize - 256) & !3;
+    let bottom = top - STACK_PROBE;
+    for address in (bottom..top).step_by(4) {
+        unsafe { core::ptr::write_volatile(address as *mut u32, PATTERN) };
+    }
+    f();
+    let lowest = (bottom..top)
+        .step_by(4)
+        .find(|&address| unsafe { core::ptr::read_volatile(address as *const u32) } != PATTERN)
+        .unwrap_or(top);
+    top - lowest
+}
+
+/// Colors 0, 1, 2, ...
+fn gradient(count: usize) -> impl Iterator<Item = u16> {
+    (0..count).map(|n| n as u16)
+}
+
+/// `Pixels` command with `count` colors starting at `first`
+fn pixels_command(first: u16, count: usize) -> DisplayCommand {
+    let mut pixels = Vec::<u16, PIXELS_PER_COMMAND>::new();
+    for color in first..first + count as u16 {
+        let _ = pixels.push(color);
+    }
+    DisplayCommand::Pixels(pixels)
+}
+
+/// Writes a window through the display queue, draining after each
+/// command, returns the stream hash and the number of RAMWR commands
+fn queued_stream(display: &mut Display) -> Result<(u32, usize, usize), DisplayError> {
+    let mut queue: DisplayQueue<4> = DisplayQueue::new();
+    let (mut producer, consumer) = queue.split();
+    let mut drain = DisplayDrain::new(consumer);
+    display.spi_mut().reset();
+    let mut executed = 0;
+    let commands = [
+        DisplayCommand::SetWindow {
+            sx: 0,
+            sy: 0,
+            ex: 7,
+            ey: 7,
+        },
+        pixels_command(0, 32),
+        pixels_command(32, 32),
+    ];
+    for command in commands.iter() {
+        let _ = producer.enqueue(command.clone());
+        executed += drain.drain(display)?;
+    }
+    let spi = display.spi_mut();
+    Ok((spi.hash, spi.count(RAMWR), executed))
+}
+
+/// Writes an 80 by 10 pixel window with `chunk` pixels per transfer,
+/// returns the stream hash and the number of transfers
+fn chunked_stream(display: &mut Display, chunk: usize) -> Result<(u32, usize), DisplayError> {
+    display.set_flush_chunk(chunk);
+    display.spi_mut().reset();
+    display.set_pixels(0, 0, 79, 9, gradient(800))?;
+    let spi = display.spi_mut();
+    Ok((spi.hash, spi.transfers))
+}
+
+/// Sets the bottom right pixel of the 80 by 160 panel and the pixels one
+/// past it, returns the number of transfers for each
+fn boundary_pixels(display: &mut Display) -> Result<(usize, usize), DisplayError> {
+    display.spi_mut().reset();
+    display.set_pixel(79, 159, 0xffff)?;
+    let inside = display.spi_mut().transfers;
+    display.spi_mut().reset();
+    display.set_pixel(80, 159, 0xffff)?;
+    display.set_pixel(79, 160, 0xffff)?;
+    Ok((inside, display.spi_mut().transfers))
+}
+
+/// Draws pixels one past each edge of the panel, returns the number of
+/// transfers
+fn draw_outside(display: &mut Display) -> Result<usize, DisplayError> {
+    display.spi_mut().reset();
+    for &(x, y) in [(-1, 0), (0, -1), (80, 0), (0, 160)].iter() {
+        display.draw_pixel(Pixel(Point::new(x, y), Rgb565::WHITE))?;
+    }
+    Ok(display.spi_mut().transfers)
+}
+
+/// Draws a white filled rectangle from `top_left` to `bottom_right` and
+/// compares the hash with a fill of the visible part, `visible`
+fn filled_past_edge(
+    display: &mut Display,
+    top_left: Point,
+    bottom_right: Point,
+    visible: (u16, u16, u16, u16),
+) -> Result<bool, DisplayError> {
+    display.spi_mut().reset();
+    let rectangle = Rectangle::new(top_left, bottom_right)
+        .into_styled(PrimitiveStyle::with_fill(Rgb565::WHITE));
+    display.draw_rectangle(&rectangle)?;
+    let drawn = display.spi_mut().hash;
+    display.spi_mut().reset();
+    let (sx, sy, ex, ey) = visible;
+    display.fill_solid(sx, sy, ex, ey, 0xffff)?;
+    Ok(drawn == display.spi_mut().hash)
+}
+
+/// Draws through a 10 by 10 clip at 10, 10, returns the number of transfers
+/// for pixels outside the clip and the hash of a clipped 30 by 30 window
+fn clipped_stream(display: &mut Display) -> Result<(usize, u32), DisplayError> {
+    let mut clipped = display.with_clip(Rectangle::new(Point::new(10, 10), Point::new(19, 19)));
+    clipped.spi_mut().reset();
+    clipped.set_pixel(5, 5, 0xffff)?;
+    clipped.draw_pixel(Pixel(Point::new(25, 12), Rgb565::WHITE))?;
+    let outside = clipped.spi_mut().transfers;
+    clipped.spi_mut().reset();
+    clipped.set_pixels(0, 0, 29, 29, gradient(900))?;
+    Ok((outside, clipped.spi_mut().hash))
+}
+
+/// Draws ten white pixels stepping by `step`, returns the number of RAMWR
+/// commands
+fn draw_line(display: &mut Display, step: Point) -> Result<usize, DisplayError> {
+    display.spi_mut().reset();
+    let pixels = (0..10).map(|n| Pixel(Point::new(10, 10) + step * n, Rgb565::WHITE));
+    display.draw_iter(pixels)?;
+    Ok(display.spi_mut().count(RAMWR))
+}
+
+/// Sets the top left pixel, returns the stream hash, which includes the
+/// offset window
+fn origin_hash(display: &mut Display) -> Result<u32, DisplayError> {
+    display.spi_mut().reset();
+    display.set_pixel(0, 0, 0xffff)?;
+    Ok(display.spi_mut().hash)
+}
+
+/// Sends `command` without parameters, returns the delay waited after it
+fn timed_delay(display: &mut Display, command: u8) -> Result<u32, DisplayError> {
+    let mut delay = DelayRecorder { total: 0 };
+    display.send_raw_timed(command, &[], &mut delay)?;
+    Ok(delay.total)
+}
+
+/// Draws a short text at `top_left`, returns the number of transfers and
+/// of RAMWR commands
+fn draw_text(display: &mut Display, top_left: Point) -> Result<(usize, usize), DisplayError> {
+    let text: BufferedText<512> =
+        BufferedText::new("Hi", ProFont12Point, Rgb565::WHITE, Rgb565::BLACK)?;
+    display.spi_mut().reset();
+    text.draw(display, top_left)?;
+    let spi = display.spi_mut();
+    Ok((spi.transfers, spi.count(RAMWR)))
+}
+
+#[entry]
+fn main() -> ! {
+    defmt::info!("ST7735 self-test");
+
+    let mut passed = 0;
+    let mut failed = 0;
+    let mut count = |pass: bool| {
+        if pass {
+            passed += 1;
+        } else {
+            failed += 1;
+        }
+    };
+
+    let mut display = Display::new(Recorder::new(), false, true, 80, 160);
+
+    // The same bytes are sent whatever the chunk size, only the number of
+    // transfers differ
+    let result = chunked_stream(&mut display, 800).and_then(|(hash, transfers)| {
+        let (small_hash, small_transfers) = chunked_stream(&mut display, 7)?;
+        let (single_hash, _) = chunked_stream(&mut display, 1)?;
+        Ok(hash == small_hash && hash == single_hash && small_transfers > transfers)
+    });
+    count(report("set_flush_chunk, identical output", result));
+
+    // Commands are executed in order, pixels drained by a later call
+    // continue the write instead of starting over
+    let result = queued_stream(&mut display).and_then(|(hash, writes, executed)| {
+        display.spi_mut().reset();
+        display.set_pixels(0, 0, 7, 7, gradient(64))?;
+        Ok(executed == 3 && writes == 1 && hash == display.spi_mut().hash)
+    });
+    count(report("display queue, ordering", result));
+
+    // Pixels one past the edge are dropped when clamping
+    let result = boundary_pixels(&mut display).map(|(inside, outside)| inside > 0 && outside == 0);
+    count(report("set_pixel, boundary, clamp", result));
+
+    // A window one past the edge is clamped, the pixel outside is dropped
+    display.spi_mut().reset();
+    let result = display
+        .set_pixels(0, 0, 80, 0, gradient(81))
+        .and_then(|_| {
+            let clamped = display.spi_mut().hash;
+            display.spi_mut().reset();
+            display.set_pixels(0, 0, 79, 0, gradient(80))?;
+            Ok(clamped)
+        })
+        .map(|clamped| clamped == display.spi_mut().hash);
+    count(report("set_pixels, one past the edge, clamp", result));
+
+    // Pixels and windows one past the edge fail in error mode, the last
+    // pixel on the panel is still written
+    display.set_clip_mode(ClipMode::Error);
+    let result = match boundary_pixels(&mut display) {
+        Err(DisplayError::OutOfBounds) => display.set_pixel(79, 159, 0xffff).map(|_| true),
+        Err(error) => Err(error),
+        Ok(_) => Ok(false),
+    };
+    count(report("set_pixel, boundary, error", result));
+
+    let result = match display.set_pixels(0, 0, 80, 0, gradient(81)) {
+        Err(DisplayError::OutOfBounds) => Ok(true),
+        Err(error) => Err(error),
+        Ok(_) => Ok(false),
+    };
+    count(report("set_pixels, one past the edge, error", result));
+
+    // The embedded-graphics draw target clips in both modes
+    let result = draw_outside(&mut display).map(|transfers| transfers == 0);
+    count(report("draw_pixel, outside, error", result));
+    // A filled rectangle crossing the corner, or with an edge past the u16
+    // range, is cut to the panel instead of failing
+    let result = filled_past_edge(
+        &mut display,
+        Point::new(70, 150),
+        Point::new(89, 169),
+        (70, 150, 79, 159),
+    )
+    .and_then(|corner| {
+        let wide = filled_past_edge(
+            &mut display,
+            Point::new(0, 0),
+            Point::new(70_000, 0),
+            (0, 0, 79, 0),
+        )?;
+        Ok(corner && wide)
+    });
+    count(report(
+        "draw_rectangle, filled past the edge, error",
+        result,
+    ));
+    display.set_clip_mode(ClipMode::Clamp);
+    let result = draw_outside(&mut display).map(|transfers| transfers == 0);
+    count(report("draw_pixel, outside, clamp", result));
+
+    // Pixels outside the clip are dropped, the clip ends with the guard
+    let result = clipped_stream(&mut display).and_then(|(outside, hash)| {
+        display.spi_mut().reset();
+        let inside =
+            gradient(900).filter(|n| (10..20).contains(&(n % 30)) && (10..20).contains(&(n / 30)));
+        display.set_pixels(10, 10, 19, 19, inside)?;
+        let expected = display.spi_mut().hash;
+        display.spi_mut().reset();
+        display.set_pixel(5, 5, 0xffff)?;
+        Ok(outside == 0 && hash == expected && display.spi_mut().transfers > 0)
+    });
+    count(report("with_clip, pixels outside dropped", result));
+
+    // A horizontal line is a single run, a diagonal line has no adjacent
+    // pixels and a vertical line none on the same row
+    let result = draw_line(&mut display, Point::new(1, 0)).and_then(|horizontal| {
+        let diagonal = draw_line(&mut display, Point::new(1, 1))?;
+        let vertical = draw_line(&mut display, Point::new(0, 1))?;
+        Ok(horizontal == 1 && diagonal == 10 && vertical == 10)
+    });
+    count(report("draw_iter, run coalescing", result));
+
+    // Two regions side by side, given right one first, are written as one
+    // window with their rows interleaved. Written one by one they need two.
+    let left: [u16; 8] = [0, 1, 2, 3, 8, 9, 10, 11];
+    let right: [u16; 8] = [4, 5, 6, 7, 12, 13, 14, 15];
+    let regions = [
+        (
+            Rectangle::new(Point::new(4, 0), Point::new(7, 1)),
+            &right[..],
+        ),
+        (
+            Rectangle::new(Point::new(0, 0), Point::new(3, 1)),
+            &left[..],
+        ),
+    ];
+    display.spi_mut().reset();
+    let result = display.flush_regions(&regions).and_then(|windows| {
+        let spi = display.spi_mut();
+        let (hash, writes) = (spi.hash, spi.count(RAMWR));
+        spi.reset();
+        display.set_pixels(0, 0, 7, 1, gradient(16))?;
+        let merged = hash == display.spi_mut().hash;
+        display.spi_mut().reset();
+        display.flush_regions(&regions[..1])?;
+        display.flush_regions(&regions[1..])?;
+        let naive = display.spi_mut().count(RAMWR);
+        Ok(windows == 1 && writes == 1 && merged && naive == 2)
+    });
+    count(report("flush_regions, horizontal merge", result));
+
+    // Pre-encoded pixels are sent as they are, the same stream as the
+    // buffered write without its copy buffer on the stack
+    let mut bytes = [0u8; 2048];
+    for (n, pixel) in bytes.chunks_exact_mut(2).enumerate() {
+        pixel.copy_from_slice(&(n as u16).to_be_bytes());
+    }
+    display.spi_mut().reset();
+    let mut buffered = Ok(());
+    let copied = stack_usage(|| buffered = display.write_pixels_buffered(gradient(1024)));
+    let buffered_hash = display.spi_mut().hash;
+    display.spi_mut().reset();
+    let mut direct = Ok(());
+    let zero_copy = stack_usage(|| direct = display.write_pixel_bytes(&bytes));
+    defmt::info!(
+        "Stack usage, buffered {} bytes, zero copy {} bytes",
+        copied,
+        zero_copy
+    );
+    let result = buffered
+        .and(direct)
+        .map(|_| display.spi_mut().hash == buffered_hash && zero_copy + 512 < copied);
+    count(report("write_pixel_bytes, stack usage", result));
+
+    // More pixels than the pixel buffer holds are sent in several transfers
+    // after a single RAMWR
+    display.spi_mut().reset();
+    let result = display.write_pixels_buffered(gradient(20_000)).map(|_| {
+        let spi = display.spi_mut();
+        spi.count(RAMWR) == 1 && spi.bytes == 1 + 20_000 * 2
+    });
+    count(report("write_pixels_buffered, 20000 pixels", result));
+
+    // The command and up to 127 parameters fit the command buffer
+    let params = [0u8; 128];
+    let result = match display.send_raw(0xb1, &params) {
+        Err(DisplayError::OutOfBounds) => display.send_raw(0xb1, &params[..127]).map(|_| true),
+        Err(error) => Err(error),
+        Ok(_) => Ok(false),
+    };
+    count(report("send_raw, parameter limit", result));
+
+    // The command and up to 63 words fit the command buffer
+    let words = [0x1234u16; 64];
+    display.spi_mut().reset();
+    let result = display.send_raw_words(CASET, &words[..63]).map(|_| {
+        let spi = display.spi_mut();
+        spi.transfers == 1 && spi.bytes == 1 + 63 * 2
+    });
+    let result = match (result, display.send_raw_words(CASET, &words)) {
+        (Ok(fits), Err(DisplayError::OutOfBounds)) => Ok(fits && display.spi_mut().transfers == 1),
+        (Err(error), _) | (_, Err(error)) => Err(error),
+        (Ok(_), Ok(_)) => Ok(false),
+    };
+    count(report("send_raw_words, parameter limit", result));
+
+    // The 80 by 160 panel fits the 132 by 162 frame memory with offsets up
+    // to 52 by 2, a rejected offset keeps the one set before
+    let result = display.set_offset(26, 1).and_then(|_| {
+        let centered = origin_hash(&mut display)?;
+        let edge = display.set_offset(52, 2).is_ok();
+        display.set_offset(26, 1)?;
+        let too_wide = display.set_offset(53, 0) == Err(DisplayError::OutOfBounds);
+        let too_high = display.set_offset(0, 3) == Err(DisplayError::OutOfBounds);
+        let kept = origin_hash(&mut display)? == centered;
+        display.set_offset(0, 0)?;
+        Ok(edge && too_wide && too_high && kept)
+    });
+    count(report("set_offset, valid and over-large", result));
+
+    // Reset and sleep out wait their datasheet minimum, window and pixel
+    // commands don't wait and an unknown command gets the default
+    let delays = [
+        (0x01, 120),
+        (0x11, 120),
+        (0x29, 100),
+        (0x13, 10),
+        (CASET, 0),
+        (RAMWR, 0),
+        (0xfe, u32::from(st7735s::DEFAULT_COMMAND_DELAY_MS)),
+    ];
+    let mut result = Ok(true);
+    for &(command, expected) in delays.iter() {
+        result = result.and_then(|pass| {
+            let delay = timed_delay(&mut display, command)?;
+            Ok(pass
+                && delay == expected
+                && u32::from(st7735s::command_delay_ms(command)) == expected)
+        });
+    }
+    count(report("send_raw_timed, command delays", result));
+
+    // Text partly left of and above the panel is cut, text entirely outside
+    // is skipped instead of wrapping around
+    let result = draw_text(&mut display, Point::new(-3, -2)).and_then(|(_, writes)| {
+        let (transfers, _) = draw_text(&mut display, Point::new(-200, 0))?;
+        Ok(writes == 1 && transfers == 0)
+    });
+    count(report("BufferedText, negative position", result));
+
+    // Clearing is a single window and memory write followed by full fill
+    // buffers, not a command per pixel
+    display.spi_mut().reset();
+    let result = display.clear(Rgb565::BLACK).map(|_| {
+        let spi = display.spi_mut();
+        spi.count(CASET) == 1
+            && spi.count(RASET) == 1
+            && spi.count(RAMWR) == 1
+            && spi.transfers == 3 + 80 * 160 / FILL_BUFFER_PIXELS
+    });
+    count(report("clear, single windowed write", result));
+
+    // The pixel data follows CASET and RASET with two words each and RAMWR
+    display.spi_mut().reset();
+    let result = display
+        .fill_rect(10, 20, 30, 40, 0xf800)
+        .map(|_| display.spi_mut().bytes == 30 * 40 * 2 + 2 * 5 + 1);
+    count(report("fill_rect, byte count", result));
+
+    // A panel without pixels has nothing to clear
+    let mut empty = Display::new(Recorder::new(), false, true, 0, 0);
+    let result = empty
+        .clear(Rgb565::BLACK)
+        .map(|_| empty.spi_mut().transfers == 0);
+    count(report("clear, zero sized panel", result));
+
+    // Each orientation sends its MV, MX and MY bits, with the BGR bit for
+    // the BGR panel
+    let orientations = [
+        (Orientation::Portrait, 0x00),
+        (Orientation::Landscape, 0x60),
+        (Orientation::PortraitSwapped, 0xc0),
+        (Orientation::LandscapeSwapped, 0xa0),
+    ];
+    let mut result = Ok(true);
+    for &(orientation, bits) in orientations.iter() {
+        result = result.and_then(|pass| {
+            display.set_orientation(orientation)?;
+            Ok(pass
+                && orientation.madctl(false, false, true) == bits
+                && display.spi_mut().madctl == Some(bits | 0x08))
+        });
+    }
+    let result = result.and_then(|pass| {
+        display.set_orientation(Orientation::Portrait)?;
+        Ok(pass)
+    });
+    count(report("Orientation, MADCTL bytes", result));
+
+    // The colour order only changes the BGR bit, the landscape bits stay
+    let result = display
+        .set_orientation(Orientation::Landscape)
+        .and_then(|_| {
+            display.set_color_order(true)?;
+            let rgb = display.spi_mut().madctl;
+            display.set_color_order(false)?;
+            let bgr = display.spi_mut().madctl;
+            display.set_orientation(Orientation::Portrait)?;
+            Ok(rgb == Some(0x60) && bgr == Some(0x68))
+        });
+    count(report("set_color_order, BGR bit", result));
+
+    // Rotating the 80 by 160 panel to landscape swaps the reported size,
+    // rotating back restores it
+    let mut rotated = Display::new(Recorder::new(), false, true, 80, 160);
+    let result = rotated
+        .set_orientation(Orientation::Landscape)
+        .and_then(|_| {
+            let landscape = rotated.size();
+            rotated.set_orientation(Orientation::Portrait)?;
+            Ok(landscape == Size::new(160, 80) && rotated.size() == Size::new(80, 160))
+        });
+    count(report("set_orientation, landscape size", result));
+    let rotated = Display::new_with_orientation(
+        Recorder::new(),
+        false,
+        true,
+        80,
+        160,
+        Orientation::Landscape,
+    );
+    count(report(
+        "new_with_orientation, landscape size",
+        Ok(rotated.size() == Size::new(160, 80)),
+    ));
+
+    if failed == 0 {
+        defmt::info!("All {} tests passed", passed);
+    } else {
+        defmt::error!("{} of {} tests failed", failed, passed + failed);
+    }
+    nrf52833_dk::exit()
+}
@@ -0,0 +1,145 @@
+#![no_main]
+#![no_std]
+
+//! UARTE loopback self-test
+//!
+//! Connect P0.03 (TXD) to P0.04 (RXD) with a jumper. Every byte sent is
+//! received back and assembled into newline terminated lines.
+
+use nrf52833_dk as _;
+
+use cortex_m_rt::entry;
+
+use nrf52833_hal as hal;
+
+use hal::{gpio, pac, uarte, Timer};
+
+use utilities::{
+    clock::{self, LfClkSource},
+    uart::{self, LineBuffer, UarteReceiver},
+};
+
+/// Longest line kept by the line buffer
+const LINE_LENGTH: usize = 8;
+
+/// Interrupt polls before giving up on a looped back byte
+const POLLS: u32 = 1_000_000;
+
+/// Logs the outcome of a test, returns true on pass
+fn report(name: &str, pass: bool) -> bool {
+    if pass {
+        defmt::info!("{}: pass", name);
+    } else {
+        defmt::error!("{}: fail, unexpected output", name);
+    }
+    pass
+}
+
+/// Outcome of a line fed to the line buffer
+#[derive(Clone, Copy, PartialEq)]
+enum Line {
+    Text([u8; LINE_LENGTH], usize),
+    TooLong,
+}
+
+#[entry]
+fn main() -> ! {
+    static mut RX_BUFFERS: [u8; 2] = [0; 2];
+
+    let peripherals = pac::Peripherals::take().unwrap();
+    clock::init_clocks(peripherals.CLOCK, LfClkSource::Crystal);
+
+    defmt::info!("UARTE loopback self-test");
+
+    let port0 = gpio::p0::Parts::new(peripherals.P0);
+    let mut uart = uarte::Uarte::new(
+        peripherals.UARTE0,
+        uarte::Pins {
+            txd: port0
+                .p0_03
+                .into_push_pull_output(gpio::Level::High)
+                .degrade(),
+            rxd: port0.p0_04.into_floating_input().degrade(),
+            cts: None,
+            rts: None,
+        },
+        uarte::Parity::EXCLUDED,
+        uarte::Baudrate::BAUD115200,
+    );
+    let mut timer = Timer::new(peripherals.TIMER0);
+
+    let mut passed = 0;
+    let mut failed = 0;
+    let mut count = |pass: bool| {
+        if pass {
+            passed += 1;
+        } else {
+            failed += 1;
+        }
+    };
+
+    // Nothing is sent, so the read times out without any bytes
+    let mut line = [0u8; LINE_LENGTH];
+    let result = uart::read_line_timeout(&mut uart, &mut line, &mut timer, 1_000);
+    count(report(
+        "read_line_timeout, idle line",
+        matches!(result, Err(uart::Error::Timeout(0))),
+    ));
+
+    // Bytes are sent one at a time and picked up from the receiver. The
+    // empty line between the carriage return and the newline is skipped and
+    // the over-long line is reported on its own.
+    let mut receiver = UarteReceiver::start(&uart, RX_BUFFERS);
+    let mut buffer: LineBuffer<LINE_LENGTH> = LineBuffer::new();
+    let mut lines = [Line::TooLong; 4];
+    let mut received = 0;
+    let mut looped = true;
+    for byte in b"C15\r\nP-4\nC123456789\nP3\n".iter() {
+        // EasyDMA can't read from flash, send from the stack
+        looped &= uart.write(&[*byte]).is_ok();
+        let mut echo = None;
+        let mut polls = 0;
+        while echo.is_none() && polls < POLLS {
+            echo = receiver.on_interrupt();
+            polls += 1;
+        }
+        looped &= echo == Some(*byte);
+        let line = match echo {
+            Some(echo) => buffer.push(echo),
+            None => None,
+        };
+        match line {
+            Some(Ok(text)) if received < lines.len() => {
+                let mut line = [0u8; LINE_LENGTH];
+                line[..text.len()].copy_from_slice(text);
+                lines[received] = Line::Text(line, text.len());
+                received += 1;
+            }
+            Some(_) if received < lines.len() => {
+                lines[received] = Line::TooLong;
+                received += 1;
+            }
+            _ => {}
+        }
+    }
+    let is_line = |line: Line, text: &[u8]| match line {
+        Line::Text(line, length) => &line[..length] == text,
+        Line::TooLong => false,
+    };
+    count(report(
+        "UarteReceiver, newline terminated lines",
+        looped
+            && received == 4
+            && is_line(lines[0], b"C15")
+            && is_line(lines[1], b"P-4")
+            && lines[2] == Line::TooLong
+            && is_line(lines[3], b"P3"),
+    ));
+
+    if failed == 0 {
+        defmt::info!("All {} tests passed", passed);
+    } else {
+        defmt::error!("{} of {} tests failed", failed, passed + failed);
+    }
+    nrf52833_dk::exit()
+}
@@ -0,0 +1,263 @@
+#![no_main]
+#![no_std]
+
+//! Self-test of the utilities helpers that don't need any hardware
+//!
+//! Each test compares the output of a helper with known values. Nothing has
+//! to be connected.
+
+use nrf52833_dk as _;
+
+use cortex_m_rt::entry;
+
+use embedded_graphics::{
+    drawable::Pixel,
+    geometry::{Point, Size},
+    pixelcolor::{Rgb565, RgbColor},
+    DrawTarget,
+};
+
+use utilities::{
+    anim::Animation,
+    eui, image,
+    repl::{self, Repl, ReplError},
+    rotated::{RotatedDrawTarget, Rotation},
+    sdcard, spi, text, timer,
+};
+
+/// 80 by 160 draw target keeping the position of the last pixel drawn
+struct LastPixel {
+    point: Point,
+}
+
+impl DrawTarget<Rgb565> for LastPixel {
+    type Error = ();
+
+    fn draw_pixel(&mut self, pixel: Pixel<Rgb565>) -> Result<(), ()> {
+        self.point = pixel.0;
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        Size::new(80, 160)
+    }
+}
+
+/// Draws the top left pixel rotated by `rotation`, returns where it ended up
+/// on the unrotated target and the rotated size
+fn rotated_corner(rotation: Rotation) -> (Point, Size) {
+    let mut target = RotatedDrawTarget::new(
+        LastPixel {
+            point: Point::new(-1, -1),
+        },
+        rotation,
+    );
+    let _ = target.draw_pixel(Pixel(Point::zero(), Rgb565::WHITE));
+    let size = target.size();
+    (target.free().point, size)
+}
+
+/// Does `line` split into `expected` tokens?
+fn tokenizes(line: &[u8], expected: &[&[u8]]) -> bool {
+    repl::tokens(line).count() == expected.len()
+        && repl::tokens(line)
+            .zip(expected.iter())
+            .all(|(t, e)| t == *e)
+}
+
+/// Console command storing its single argument in the context
+fn set(value: &mut u32, args: &mut repl::Args) -> Result<(), ReplError> {
+    *value = args.next_u32()?;
+    args.finish()
+}
+
+/// Runs `retry_short_count` with a transfer failing `failures` times with
+/// `error`, returns the result and the number of attempts
+fn retried(
+    retries: u8,
+    failures: usize,
+    error: fn() -> spi::Error,
+) -> (Result<(), spi::Error>, usize) {
+    let mut attempts = 0;
+    let result = spi::retry_short_count(retries, || {
+        attempts += 1;
+        if attempts > failures {
+            Ok(())
+        } else {
+            Err(error())
+        }
+    });
+    (result, attempts)
+}
+
+/// Logs the outcome of a test, returns true on pass
+fn report(name: &str, pass: bool) -> bool {
+    if pass {
+        defmt::info!("{}: pass", name);
+    } else {
+        defmt::error!("{}: fail, unexpected output", name);
+    }
+    pass
+}
+
+#[entry]
+fn main() -> ! {
+    defmt::info!("Utilities self-test");
+
+    let mut passed = 0;
+    let mut failed = 0;
+    let mut count = |pass: bool| {
+        if pass {
+            passed += 1;
+        } else {
+            failed += 1;
+        }
+    };
+
+    // GO_IDLE_STATE and SEND_IF_COND with the frames given in the SD
+    // specification
+    count(report(
+        "sdcard command_frame, CMD0",
+        sdcard::command_frame(0, 0) == [0x40, 0x00, 0x00, 0x00, 0x00, 0x95],
+    ));
+    count(report(
+        "sdcard command_frame, CMD8",
+        sdcard::command_frame(8, 0x1aa) == [0x48, 0x00, 0x00, 0x01, 0xaa, 0x87],
+    ));
+
+    // FF FE is inserted between the third and the fourth octet
+    count(report(
+        "eui64_from_eui48",
+        eui::eui64_from_eui48(0x0123_4567_89ab) == 0x0123_45ff_fe67_89ab,
+    ));
+
+    // Black, the grey 141 of level 5 and white, levels above 9 are white
+    count(report(
+        "grey_to_rgb565",
+        image::grey_to_rgb565(0) == 0x0000
+            && image::grey_to_rgb565(5) == 0x8c71
+            && image::grey_to_rgb565(9) == 0xffff
+            && image::grey_to_rgb565(12) == 0xffff,
+    ));
+
+    // Short counts are retried until the transfer succeeds or the retries
+    // run out, other errors and the default of no retries fail directly
+    let (result, attempts) = retried(2, 2, || spi::Error::Receive);
+    count(report(
+        "retry_short_count, recovers",
+        result.is_ok() && attempts == 3,
+    ));
+    let (result, attempts) = retried(1, 5, || spi::Error::Transmit);
+    count(report(
+        "retry_short_count, exhausted",
+        matches!(result, Err(spi::Error::Transmit)) && attempts == 2,
+    ));
+    let (result, attempts) = retried(0, 1, || spi::Error::Receive);
+    count(report(
+        "retry_short_count, no retries",
+        matches!(result, Err(spi::Error::Receive)) && attempts == 1,
+    ));
+    let (result, attempts) = retried(3, 1, || spi::Error::ChipSelect);
+    count(report(
+        "retry_short_count, other error",
+        matches!(result, Err(spi::Error::ChipSelect)) && attempts == 1,
+    ));
+
+    // Each frame is shown for two ticks, the sequence wraps after the last
+    let frames = [0u8, 1, 2];
+    let mut animation = Animation::new(&frames, 2);
+    let mut shown = [0u8; 8];
+    for frame in shown.iter_mut() {
+        *frame = *animation.next_frame();
+    }
+    count(report(
+        "Animation, divisor and wrap",
+        shown == [0, 0, 1, 1, 2, 2, 0, 0] && animation.frame_index() == 1,
+    ));
+
+    // A divisor of zero advances at every tick
+    let mut animation = Animation::new(&frames, 0);
+    let mut shown = [0u8; 4];
+    for frame in shown.iter_mut() {
+        *frame = *animation.next_frame();
+    }
+    count(report("Animation, zero divisor", shown == [0, 1, 2, 0]));
+
+    // The top left corner moves clockwise around the 80 by 160 panel
+    let corners = [
+        (Rotation::Rotate0, Point::new(0, 0), Size::new(80, 160)),
+        (Rotation::Rotate90, Point::new(79, 0), Size::new(160, 80)),
+        (Rotation::Rotate180, Point::new(79, 159), Size::new(80, 160)),
+        (Rotation::Rotate270, Point::new(0, 159), Size::new(160, 80)),
+    ];
+    count(report(
+        "RotatedDrawTarget, corner mapping",
+        corners
+            .iter()
+            .all(|&(rotation, point, size)| rotated_corner(rotation) == (point, size)),
+    ));
+
+    // Five characters of a 7 pixel wide font are 35 pixels, the longest
+    // line counts and text wider than the panel starts at the left edge
+    count(report(
+        "center_x and right_align, 160 pixel panel",
+        text::center_x("Hello", 7, 160) == 62
+            && text::right_align("Hello", 7, 160) == 125
+            && text::center_x("Hi\nHello", 7, 160) == 62
+            && text::center_x("A line far too long for the panel", 7, 160) == 0,
+    ));
+
+    // At 1 MHz a millisecond is 1000 ticks, partial milliseconds are cut and
+    // the largest counter value doesn't overflow
+    count(report(
+        "ticks_to_ms, 1 MHz",
+        timer::ticks_to_ms(1_000_000) == 1000
+            && timer::ticks_to_ms(1_999) == 1
+            && timer::ticks_to_ms(999) == 0
+            && timer::ticks_to_ms(u32::MAX) == 4_294_967,
+    ));
+
+    // Any run of whitespace separates tokens
+    count(report(
+        "repl::tokens",
+        tokenizes(b"  led  1\ton ", &[b"led", b"1", b"on"])
+            && tokenizes(b"channel 15", &[b"channel", b"15"])
+            && tokenizes(b"", &[])
+            && tokenizes(b" \t ", &[]),
+    ));
+
+    // Backspace erases the last byte, bytes past the line length are dropped
+    let mut console: Repl<6> = Repl::new();
+    for byte in b"ledx\x08 1".iter() {
+        console.feed(*byte);
+    }
+    let edited = console.line() == b"led 1";
+    let full = console.feed(b'2') == repl::Input::Stored(b'2')
+        && console.feed(b'3') == repl::Input::Ignored;
+    count(report(
+        "Repl, backspace and line length",
+        edited && full && console.feed(b'\r') == repl::Input::Line,
+    ));
+
+    let commands = [repl::Command {
+        name: "set",
+        help: "Set the value",
+        handler: set,
+    }];
+    let mut value = 0;
+    let ran = repl::run(b"set 42", &mut value, &commands).is_ok() && value == 42;
+    count(report(
+        "repl::run, dispatch and errors",
+        ran && repl::run(b"set", &mut value, &commands) == Err(ReplError::MissingArgument)
+            && repl::run(b"set 1 2", &mut value, &commands) == Err(ReplError::TooManyArguments)
+            && repl::run(b"get", &mut value, &commands) == Err(ReplError::UnknownCommand)
+            && repl::run(b"  ", &mut value, &commands) == Err(ReplError::Empty),
+    ));
+
+    if failed == 0 {
+        defmt::info!("All {} tests passed", passed);
+    } else {
+        defmt::error!("{} of {} tests failed", failed, passed + failed);
+    }
+    nrf52833_dk::exit()
+}
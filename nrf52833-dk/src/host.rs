@@ -0,0 +1,90 @@
+//! Decoding of esercom frames received from the host.
+
+use esercom::MessageType;
+
+/// Byte ending each esercom frame
+pub const FRAME_DELIMITER: u8 = 0x00;
+
+/// Frame decoding errors
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum DecodeError {
+    /// The frame didn't fit the decoder buffer and was dropped
+    Overflow,
+    /// The frame is malformed or the checksum doesn't match
+    Invalid,
+}
+
+/// Streaming esercom frame decoder
+///
+/// Bytes are pushed one at a time as they are received, so a frame may be
+/// split over any number of UART reads or interrupts. Frames are collected
+/// up to the delimiter and then decoded, frames longer than `N` bytes are
+/// dropped. After an error the decoder continues with the next frame.
+pub struct FrameDecoder<const N: usize> {
+    /// Encoded frame received so far
+    frame: [u8; N],
+    length: usize,
+    /// The current frame is dropped, it didn't fit `frame`
+    overflow: bool,
+    /// Decoded payload of the last frame
+    payload: [u8; N],
+}
+
+impl<const N: usize> FrameDecoder<N> {
+    /// Creates a decoder waiting for the start of a frame
+    pub const fn new() -> Self {
+        Self {
+            frame: [0u8; N],
+            length: 0,
+            overflow: false,
+            payload: [0u8; N],
+        }
+    }
+
+    /// Drops any partially received frame
+    pub fn reset(&mut self) {
+        self.length = 0;
+        self.overflow = false;
+    }
+
+    /// Pushes a received byte
+    ///
+    /// Returns the message type and payload when the byte completes a
+    /// frame, the payload is valid until the next call. Empty frames, such
+    /// as repeated delimiters, are skipped.
+    pub fn push(&mut self, byte: u8) -> Option<Result<(MessageType, &[u8]), DecodeError>> {
+        if byte != FRAME_DELIMITER {
+            if self.length < N {
+                self.frame[self.length] = byte;
+                self.length += 1;
+            } else {
+                self.overflow = true;
+            }
+            return None;
+        }
+        if self.overflow {
+            self.reset();
+            return Some(Err(DecodeError::Overflow));
+        }
+        if self.length == 0 {
+            return None;
+        }
+        let length = self.length;
+        self.reset();
+        // The decoder expects the complete frame, including the delimiter
+        if length == N {
+            return Some(Err(DecodeError::Overflow));
+        }
+        self.frame[length] = FRAME_DELIMITER;
+        match esercom::com_decode(&self.frame[..=length], &mut self.payload) {
+            Ok((message_type, _, written)) => Some(Ok((message_type, &self.payload[..written]))),
+            Err(_) => Some(Err(DecodeError::Invalid)),
+        }
+    }
+}
+
+impl<const N: usize> Default for FrameDecoder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -2,8 +2,11 @@
 
 use core::sync::atomic::{AtomicUsize, Ordering};
 
+pub mod host;
+
 use defmt_rtt as _;
-use panic_probe as _;
+// Panic handler, selected through the utilities panic features
+use utilities as _;
 
 defmt::timestamp! {
     "{=u64}", {
@@ -16,7 +19,13 @@ defmt::timestamp! {
 }
 
 /// Terminates the application and makes `probe-run` exit with exit-code = 0
+///
+/// Pending defmt log frames are flushed before a breakpoint is hit. Under a
+/// debugger, such as `probe-run`, the breakpoint halts the core and ends the
+/// run. Without a debugger attached the breakpoint escalates to a hard fault
+/// and the core stays halted there.
 pub fn exit() -> ! {
+    defmt::flush();
     loop {
         cortex_m::asm::bkpt();
     }
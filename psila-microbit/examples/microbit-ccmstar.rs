@@ -5,117 +5,11 @@ use psila_microbit as _;
 
 use rtic::app;
 
-use microbit::hal as hal;
-
-use aes;
-use ccm::{
-    aead::{generic_array::GenericArray, AeadInPlace, KeyInit},
-    consts::{U13, U16, U4, U8},
-    Ccm,
-};
-
-type AesCcmMic4 = Ccm<aes::Aes128, U4, U13>;
-type AesCcmMic8 = Ccm<aes::Aes128, U8, U13>;
-type AesCcmMic16 = Ccm<aes::Aes128, U16, U13>;
-
-fn decode(
-    key: &[u8; 16],
-    nonce: &[u8; 13],
-    payload: &[u8],
-    mic: &[u8],
-    aad: &[u8],
-    output: &mut [u8],
-) -> Result<usize, i32> {
-    let key: &GenericArray<u8, U16> = GenericArray::from_slice(key);
-    let nonce: &GenericArray<u8, U13> = GenericArray::from_slice(nonce);
-    let payload_len = payload.len();
-    output[..payload_len].copy_from_slice(&payload);
-    match mic.len() {
-        0 => {
-            unimplemented!();
-        }
-        4 => {
-            let tag: &GenericArray<u8, U4> = GenericArray::from_slice(mic);
-            let cipher = AesCcmMic4::new(key);
-            match cipher.decrypt_in_place_detached(nonce, aad, &mut output[..payload_len], tag) {
-                Ok(_) => Ok(payload_len),
-                Err(_e) => Err(-1),
-            }
-        }
-        8 => {
-            let tag: &GenericArray<u8, U8> = GenericArray::from_slice(mic);
-            let cipher = AesCcmMic8::new(key);
-            match cipher.decrypt_in_place_detached(nonce, aad, &mut output[..payload_len], tag) {
-                Ok(_) => Ok(payload_len),
-                Err(_e) => Err(-1),
-            }
-        }
-        16 => {
-            let tag: &GenericArray<u8, U16> = GenericArray::from_slice(mic);
-            let cipher = AesCcmMic16::new(key);
-            match cipher.decrypt_in_place_detached(nonce, aad, &mut output[..payload_len], tag) {
-                Ok(_) => Ok(payload_len),
-                Err(_e) => Err(-1),
-            }
-        }
-        _ => Err(-2),
-    }
-}
-
-fn encode(
-    key: &[u8; 16],
-    nonce: &[u8; 13],
-    payload: &[u8],
-    mic: &mut [u8],
-    aad: &[u8],
-    output: &mut [u8],
-) -> Result<usize, i32> {
-    let key: &GenericArray<u8, U16> = GenericArray::from_slice(key);
-    let nonce: &GenericArray<u8, U13> = GenericArray::from_slice(nonce);
-    let payload_len = payload.len();
-    output[..payload_len].copy_from_slice(&payload);
-    match mic.len() {
-        0 => {
-            unimplemented!();
-        }
-        4 => {
-            let cipher = AesCcmMic4::new(key);
-            match cipher.encrypt_in_place_detached(nonce, aad, &mut output[..payload_len]) {
-                Ok(tag) => {
-                    mic.copy_from_slice(tag.as_slice());
-                    Ok(payload_len)
-                }
-                Err(_e) => Err(-1),
-            }
-        }
-        8 => {
-            let cipher = AesCcmMic8::new(key);
-            match cipher.encrypt_in_place_detached(nonce, aad, &mut output[..payload_len]) {
-                Ok(tag) => {
-                    mic.copy_from_slice(tag.as_slice());
-                    Ok(payload_len)
-                }
-                Err(_e) => Err(-1),
-            }
-        }
-        16 => {
-            let cipher = AesCcmMic16::new(key);
-            match cipher.encrypt_in_place_detached(nonce, aad, &mut output[..payload_len]) {
-                Ok(tag) => {
-                    mic.copy_from_slice(tag.as_slice());
-                    Ok(payload_len)
-                }
-                Err(_e) => Err(-1),
-            }
-        }
-        _ => Err(-2),
-    }
-}
-
 #[app(device = microbit::pac, peripherals = true)]
 mod app {
-    use crate::hal as hal;
-    use hal::clocks;
+    use utilities::clock::{self, LfClkSource};
+
+    use psila_microbit::ccmstar;
 
     use psila_data::{
         application_service::ApplicationServiceHeader,
@@ -130,11 +24,8 @@ mod app {
     struct SharedResources {}
     #[init]
     fn init(cx: init::Context) -> (SharedResources, LocalResources, init::Monotonics) {
-        // Configure to use external clocks, and start them
-        let _clocks = clocks::Clocks::new(cx.device.CLOCK)
-            .enable_ext_hfosc()
-            .set_lfclk_src_synth()
-            .start_lfclk();
+        // Start the high frequency crystal and the low frequency clock
+        clock::init_clocks(cx.device.CLOCK, LfClkSource::Synth);
         defmt::info!("Initialize");
 
         (
@@ -172,7 +63,7 @@ mod app {
 
             let (payload, mic) = message.split_at(message.len() - MIC_LENGTH);
 
-            match crate::decode(&key, &nonce, payload, mic, &additional_data, &mut output) {
+            match ccmstar::decode(&key, &nonce, payload, mic, &additional_data, &mut output) {
                 Ok(size) => {
                     if output[..size] == clear_text {
                         defmt::info!("CCM Test 1 succeded");
@@ -180,8 +71,8 @@ mod app {
                         defmt::error!("CCM Test 1 failed, Mismatching output");
                     }
                 }
-                Err(_e) => {
-                    defmt::error!("CCM Test 1 failed");
+                Err(e) => {
+                    defmt::error!("CCM Test 1 failed, {}", e);
                 }
             }
         }
@@ -224,7 +115,7 @@ mod app {
 
             let mut output = [0u8; 128];
 
-            match crate::decode(&key, &nonce, &payload, &mic, &aad, &mut output) {
+            match ccmstar::decode(&key, &nonce, &payload, &mic, &aad, &mut output) {
                 Ok(size) => {
                     if size == 35 {
                         let correct_output = [
@@ -243,8 +134,8 @@ mod app {
                         defmt::error!("CCM Test 2, Incorrect length {=usize}", size);
                     }
                 }
-                Err(_e) => {
-                    defmt::error!("Failed to decrypt payload");
+                Err(e) => {
+                    defmt::error!("Failed to decrypt payload, {}", e);
                 }
             }
         }
@@ -265,7 +156,7 @@ mod app {
             let mut output = [0u8; 128];
             let mut mic = [0u8; 8];
 
-            match crate::encode(&key, &nonce, &message, &mut mic, &aad, &mut output) {
+            match ccmstar::encode(&key, &nonce, &message, &mut mic, &aad, &mut output) {
                 Ok(size) => {
                     if size == 23 {
                         let correct_output = [
@@ -286,8 +177,8 @@ mod app {
                         defmt::error!("CCM Test 3, Incorrect length {=usize}", size);
                     }
                 }
-                Err(_e) => {
-                    defmt::error!("Failed to decrypt payload");
+                Err(e) => {
+                    defmt::error!("Failed to encrypt payload, {}", e);
                 }
             }
         }
@@ -0,0 +1,487 @@
+#![no_main]
+#![no_std]
+
+//! Joins a Zigbee network step by step
+//!
+//! Scans for a network permitting joining, associates with the coordinator
+//! or router sending the beacon and waits for the trust center to send the
+//! network key, secured with the key-transport key derived from the default
+//! link key. Each state transition is logged.
+//!
+//! The example stops once the network key has been decrypted, announcing
+//! the device and the rest of the start-up is left to `PsilaService`, see
+//! the microbit-light example.
+
+use psila_microbit as _;
+use rtic::app;
+
+#[app(device = microbit::pac, peripherals = true, dispatchers = [I2S])]
+mod app {
+    use microbit::pac;
+
+    use psila_data::{
+        application_service::ApplicationServiceHeader,
+        pack::{Pack, PackFixed},
+        security::{SecurityHeader, SecurityLevel},
+    };
+    use psila_microbit::ccmstar;
+    use psila_nrf52::radio::MAX_PACKET_LENGHT;
+
+    use utilities::clock::{self, LfClkSource};
+    use utilities::eui;
+    use utilities::mono::{self, TimerMonotonic};
+    use utilities::radio::{
+        association_request, beacon_request, data_request, mac_payload, parse_association_response,
+        parse_beacon, scan_duration_micros, Address, AssociationStatus, Beacon, Radio,
+        CAPABILITY_ALLOCATE_ADDRESS, CAPABILITY_MAINS_POWERED, CAPABILITY_RX_ON_WHEN_IDLE,
+        RESPONSE_WAIT_MICROS,
+    };
+
+    /// First channel scanned
+    const FIRST_CHANNEL: u8 = 11;
+    /// Last channel scanned
+    const LAST_CHANNEL: u8 = 26;
+    /// Scan duration exponent, about 138 ms per channel
+    const SCAN_EXPONENT: u8 = 3;
+    /// Association requests sent before giving up on a network
+    const MAX_ATTEMPTS: u8 = 3;
+    /// Time to wait for the network key after associating
+    const KEY_WAIT_MICROS: u32 = 5_000_000;
+    /// Interval at which timeouts are checked
+    const TICK_MICROS: u32 = 10_000;
+    /// Number of networks that refused joining to remember
+    const MAX_REFUSED: usize = 4;
+
+    /// Joining as a mains powered end device with the receiver always on
+    const CAPABILITY: u8 =
+        CAPABILITY_ALLOCATE_ADDRESS | CAPABILITY_RX_ON_WHEN_IDLE | CAPABILITY_MAINS_POWERED;
+
+    /// Key-transport key derived from the default trust center link key,
+    /// ZigBeeAlliance09
+    const KEY_TRANSPORT_KEY: [u8; 16] = [
+        0x4b, 0xab, 0x0f, 0x17, 0x3e, 0x14, 0x34, 0xa2, 0xd5, 0x72, 0xe1, 0xc1, 0xef, 0x47, 0x87,
+        0x82,
+    ];
+
+    /// APS command identifier of transport key
+    const APS_TRANSPORT_KEY: u8 = 0x05;
+    /// Transport key type, standard network key
+    const KEY_TYPE_NETWORK: u8 = 0x01;
+    /// Length of a transport key command carrying a network key
+    const TRANSPORT_KEY_LENGTH: usize = 35;
+
+    /// Network selected for joining
+    #[derive(Clone, Copy, defmt::Format)]
+    pub struct Network {
+        channel: u8,
+        pan_id: u16,
+        coordinator: Address,
+    }
+
+    /// Joining progress
+    #[derive(Clone, Copy, defmt::Format)]
+    pub enum JoinState {
+        /// Not started
+        Idle,
+        /// Beacon request sent, listening for beacons
+        Scanning { channel: u8 },
+        /// Association request sent, waiting for the coordinator to prepare
+        /// the response
+        Associating { attempt: u8 },
+        /// Data request sent, waiting for the association response
+        Polling { attempt: u8 },
+        /// Associated, waiting for the trust center to send the network key
+        WaitingForKey { short_address: u16 },
+        /// The network key is known
+        Joined { short_address: u16 },
+    }
+
+    /// Association and network key state machine
+    ///
+    /// Frames received are passed to `on_packet` and `on_tick` is called
+    /// periodically to handle timeouts. Requests that aren't answered are
+    /// retried, networks refusing the device are skipped when scanning
+    /// again.
+    pub struct Join {
+        state: JoinState,
+        deadline: Option<mono::Instant>,
+        sequence: u8,
+        extended_address: u64,
+        network: Option<Network>,
+        refused: [Option<u16>; MAX_REFUSED],
+    }
+
+    impl Join {
+        pub fn new(extended_address: u64) -> Self {
+            Self {
+                state: JoinState::Idle,
+                deadline: None,
+                sequence: 0,
+                extended_address,
+                network: None,
+                refused: [None; MAX_REFUSED],
+            }
+        }
+
+        /// Enters `state`, timing out after `timeout` microseconds
+        fn set_state(&mut self, state: JoinState, timeout: Option<u32>, now: mono::Instant) {
+            defmt::info!("{} -> {}", self.state, state);
+            self.state = state;
+            self.deadline = timeout.map(|micros| now + mono::Duration::from_ticks(micros));
+        }
+
+        fn next_sequence(&mut self) -> u8 {
+            self.sequence = self.sequence.wrapping_add(1);
+            self.sequence
+        }
+
+        /// Broadcasts a beacon request on `channel`
+        fn scan(&mut self, radio: &mut Radio, channel: u8, now: mono::Instant) {
+            let mut frame = [0u8; 16];
+            radio.set_channel(channel);
+            radio.receive_prepare();
+            if let Ok(length) = beacon_request(&mut frame, self.next_sequence()) {
+                let _ = radio.queue_transmission(&frame[..length]);
+            }
+            let timeout = scan_duration_micros(SCAN_EXPONENT);
+            self.set_state(JoinState::Scanning { channel }, Some(timeout), now);
+        }
+
+        /// Sends an association request to the selected network
+        fn associate(&mut self, radio: &mut Radio, attempt: u8, now: mono::Instant) {
+            let network = match self.network {
+                Some(network) => network,
+                None => return self.give_up(radio, now),
+            };
+            let mut frame = [0u8; 32];
+            let sequence = self.next_sequence();
+            if let Ok(length) = association_request(
+                &mut frame,
+                sequence,
+                network.pan_id,
+                network.coordinator,
+                self.extended_address,
+                CAPABILITY,
+            ) {
+                let _ = radio.queue_transmission(&frame[..length]);
+            }
+            let state = JoinState::Associating { attempt };
+            self.set_state(state, Some(RESPONSE_WAIT_MICROS), now);
+        }
+
+        /// Polls the coordinator for the association response
+        fn poll(&mut self, radio: &mut Radio, attempt: u8, now: mono::Instant) {
+            let network = match self.network {
+                Some(network) => network,
+                None => return self.give_up(radio, now),
+            };
+            let mut frame = [0u8; 32];
+            let sequence = self.next_sequence();
+            if let Ok(length) = data_request(
+                &mut frame,
+                sequence,
+                network.pan_id,
+                network.coordinator,
+                self.extended_address,
+            ) {
+                let _ = radio.queue_transmission(&frame[..length]);
+            }
+            let state = JoinState::Polling { attempt };
+            self.set_state(state, Some(RESPONSE_WAIT_MICROS), now);
+        }
+
+        /// Drops the selected network and scans again from the next channel
+        fn give_up(&mut self, radio: &mut Radio, now: mono::Instant) {
+            radio.clear_address();
+            let channel = match self.network.take() {
+                Some(network) if network.channel < LAST_CHANNEL => network.channel + 1,
+                _ => FIRST_CHANNEL,
+            };
+            self.scan(radio, channel, now);
+        }
+
+        /// Can the device join the network sending `beacon`?
+        fn accept(&self, beacon: &Beacon) -> bool {
+            let capacity = match beacon.zigbee {
+                Some(zigbee) => zigbee.end_device_capacity,
+                None => false,
+            };
+            beacon.permit_join
+                && capacity
+                && !self
+                    .refused
+                    .iter()
+                    .flatten()
+                    .any(|pan_id| *pan_id == beacon.pan_id)
+        }
+
+        /// Remembers a network that refused the device
+        fn refuse(&mut self, pan_id: u16) {
+            self.refused.rotate_right(1);
+            self.refused[0] = Some(pan_id);
+        }
+
+        /// Handles timeouts
+        pub fn on_tick(&mut self, radio: &mut Radio, now: mono::Instant) {
+            if let Some(deadline) = self.deadline {
+                if now < deadline {
+                    return;
+                }
+            }
+            match self.state {
+                JoinState::Idle => self.scan(radio, FIRST_CHANNEL, now),
+                JoinState::Scanning { channel } => {
+                    if channel < LAST_CHANNEL {
+                        self.scan(radio, channel + 1, now);
+                    } else {
+                        defmt::warn!("No network permitting joining found");
+                        self.scan(radio, FIRST_CHANNEL, now);
+                    }
+                }
+                JoinState::Associating { attempt } => self.poll(radio, attempt, now),
+                JoinState::Polling { attempt } => {
+                    if attempt + 1 < MAX_ATTEMPTS {
+                        defmt::warn!("No association response, retrying");
+                        self.associate(radio, attempt + 1, now);
+                    } else {
+                        defmt::warn!("No association response, giving up");
+                        self.give_up(radio, now);
+                    }
+                }
+                JoinState::WaitingForKey { .. } => {
+                    defmt::warn!("No network key received");
+                    self.give_up(radio, now);
+                }
+                JoinState::Joined { .. } => {}
+            }
+        }
+
+        /// Handles a received packet, a length octet followed by the frame
+        pub fn on_packet(&mut self, radio: &mut Radio, packet: &[u8], now: mono::Instant) {
+            match self.state {
+                JoinState::Idle | JoinState::Joined { .. } => {}
+                JoinState::Scanning { channel } => {
+                    if let Ok(Some(beacon)) = parse_beacon(packet) {
+                        defmt::info!("Beacon {}", beacon);
+                        if self.accept(&beacon) {
+                            self.network = Some(Network {
+                                channel,
+                                pan_id: beacon.pan_id,
+                                coordinator: beacon.address,
+                            });
+                            self.associate(radio, 0, now);
+                        }
+                    }
+                }
+                // The response may be sent directly, without waiting for a
+                // data request
+                JoinState::Associating { .. } | JoinState::Polling { .. } => {
+                    let response = match parse_association_response(packet, self.extended_address) {
+                        Ok(Some(response)) => response,
+                        _ => return,
+                    };
+                    match response.status {
+                        AssociationStatus::Success => {
+                            let short_address = response.short_address;
+                            defmt::info!("Associated, short address {=u16:04x}", short_address);
+                            if let Some(network) = self.network {
+                                radio.set_address(network.pan_id, short_address);
+                            }
+                            let state = JoinState::WaitingForKey { short_address };
+                            self.set_state(state, Some(KEY_WAIT_MICROS), now);
+                        }
+                        status => {
+                            defmt::warn!("Join refused, {}", status);
+                            if let Some(network) = self.network {
+                                self.refuse(network.pan_id);
+                            }
+                            self.give_up(radio, now);
+                        }
+                    }
+                }
+                JoinState::WaitingForKey { short_address } => {
+                    // Data frames addressed to the device
+                    if packet.len() < 2 || packet[1] & 0b111 != 0b001 || !radio.accepts(packet) {
+                        return;
+                    }
+                    let payload = match mac_payload(packet) {
+                        Ok(payload) => payload,
+                        Err(_) => return,
+                    };
+                    // The network layer would keep the key, joining ends here
+                    if let Some((_key, sequence)) = network_key(payload, self.extended_address) {
+                        defmt::info!("Network key received, sequence {=u8}", sequence);
+                        self.set_state(JoinState::Joined { short_address }, None, now);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Size of the NWK header of a data frame without network security
+    ///
+    /// Frames secured at the network level are skipped, those need the
+    /// network key that is being waited for.
+    fn network_header_size(frame: &[u8]) -> Option<usize> {
+        if frame.len() < 8 {
+            return None;
+        }
+        let frame_control = u16::from_le_bytes([frame[0], frame[1]]);
+        if frame_control & 0b11 != 0b00 || frame_control & 0x0200 == 0x0200 {
+            return None;
+        }
+        // Frame control, destination, source, radius and sequence number
+        let mut size = 8;
+        // Destination and source IEEE addresses
+        if frame_control & 0x0800 == 0x0800 {
+            size += 8;
+        }
+        if frame_control & 0x1000 == 0x1000 {
+            size += 8;
+        }
+        // Multicast control
+        if frame_control & 0x0100 == 0x0100 {
+            size += 1;
+        }
+        // Source route, relay count, relay index and relay list
+        if frame_control & 0x0400 == 0x0400 {
+            let relay_count = usize::from(*frame.get(size)?);
+            size += 2 + 2 * relay_count;
+        }
+        if size > frame.len() {
+            None
+        } else {
+            Some(size)
+        }
+    }
+
+    /// Decrypts a transport key command carrying the network key to
+    /// `extended_address`, returns the key and the key sequence number
+    fn network_key(frame: &[u8], extended_address: u64) -> Option<([u8; 16], u8)> {
+        let aps_frame = &frame[network_header_size(frame)?..];
+        // APS command with APS security
+        if aps_frame.first()? & 0x23 != 0x21 {
+            return None;
+        }
+        let (_aps, aps_used) = ApplicationServiceHeader::unpack(aps_frame).ok()?;
+        let (mut security, security_used) = SecurityHeader::unpack(&aps_frame[aps_used..]).ok()?;
+
+        // The security level is sent as zero, the nonce and the additional
+        // data use the actual level
+        security
+            .control
+            .set_level(SecurityLevel::EncryptedIntegrity32);
+        let mut buffer = [0u8; MAX_PACKET_LENGHT as usize];
+        if aps_frame.len() > buffer.len() {
+            return None;
+        }
+        buffer[..aps_frame.len()].copy_from_slice(aps_frame);
+        security
+            .control
+            .pack(&mut buffer[aps_used..=aps_used])
+            .ok()?;
+
+        let mut nonce = [0u8; 13];
+        let _ = security.get_nonce(&mut nonce);
+        let mic_bytes = security.control.level.mic_bytes();
+        let aad_size = aps_used + security_used;
+        if aps_frame.len() < aad_size + mic_bytes {
+            return None;
+        }
+        let payload_end = aps_frame.len() - mic_bytes;
+
+        let mut output = [0u8; MAX_PACKET_LENGHT as usize];
+        let size = match ccmstar::decode(
+            &KEY_TRANSPORT_KEY,
+            &nonce,
+            &buffer[aad_size..payload_end],
+            &buffer[payload_end..aps_frame.len()],
+            &buffer[..aad_size],
+            &mut output,
+        ) {
+            Ok(size) => size,
+            Err(e) => {
+                defmt::warn!("Failed to decrypt APS command, {}", e);
+                return None;
+            }
+        };
+
+        // Command identifier, key type, key, key sequence number,
+        // destination and source extended addresses
+        if size < TRANSPORT_KEY_LENGTH
+            || output[0] != APS_TRANSPORT_KEY
+            || output[1] != KEY_TYPE_NETWORK
+        {
+            return None;
+        }
+        let mut destination = [0u8; 8];
+        destination.copy_from_slice(&output[19..27]);
+        if u64::from_le_bytes(destination) != extended_address {
+            return None;
+        }
+        let mut key = [0u8; 16];
+        key.copy_from_slice(&output[2..18]);
+        Some((key, output[18]))
+    }
+
+    #[monotonic(binds = TIMER1, default = true)]
+    type Mono = TimerMonotonic<pac::TIMER1>;
+
+    #[local]
+    struct LocalResources {}
+
+    #[shared]
+    struct SharedResources {
+        radio: Radio,
+        join: Join,
+    }
+
+    #[init]
+    fn init(cx: init::Context) -> (SharedResources, LocalResources, init::Monotonics) {
+        // Start the high frequency crystal and the low frequency clock
+        clock::init_clocks(cx.device.CLOCK, LfClkSource::Synth);
+        defmt::info!("Initialize");
+
+        let extended_address = eui::eui64_from_ficr(&cx.device.FICR);
+
+        let monotonic = TimerMonotonic::new(cx.device.TIMER1);
+        tick::spawn().unwrap();
+
+        let mut radio = Radio::new(cx.device.RADIO);
+        radio.set_transmission_power(8);
+        radio.set_extended_address(extended_address);
+
+        (
+            SharedResources {
+                radio,
+                join: Join::new(extended_address),
+            },
+            LocalResources {},
+            init::Monotonics(monotonic),
+        )
+    }
+
+    #[task(shared = [radio, join])]
+    fn tick(cx: tick::Context) {
+        let now = monotonics::now();
+        (cx.shared.radio, cx.shared.join).lock(|radio, join| join.on_tick(radio, now));
+        let _ = tick::spawn_after(mono::Duration::from_ticks(TICK_MICROS));
+    }
+
+    #[task(binds = RADIO, shared = [radio, join])]
+    fn radio(cx: radio::Context) {
+        let now = monotonics::now();
+        (cx.shared.radio, cx.shared.join).lock(|radio, join| {
+            let mut packet = [0u8; MAX_PACKET_LENGHT as usize];
+            match radio.receive(&mut packet) {
+                Ok(packet_len) if packet_len > 0 => {
+                    join.on_packet(radio, &packet[..packet_len], now)
+                }
+                Ok(_) => {}
+                Err(_) => {
+                    defmt::warn!("CCA Busy");
+                }
+            }
+        });
+    }
+}
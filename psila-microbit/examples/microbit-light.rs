@@ -28,6 +28,33 @@ mod app {
     /// Basic cluster attribute, power source
     const BASIC_ATTR_POWER_SOURCE: u16 = 0x0007;
 
+    /// Number of basic cluster attributes
+    const BASIC_ATTRIBUTE_COUNT: usize = 4;
+    /// Basic cluster attributes, all read only
+    const BASIC_ATTRIBUTES: [Attribute; BASIC_ATTRIBUTE_COUNT] = [
+        Attribute::read_only(
+            CLUSTER_BASIC,
+            BASIC_ATTR_LIBRARY_VERSION,
+            AttributeValue::Unsigned8(0x02),
+        ),
+        Attribute::read_only(
+            CLUSTER_BASIC,
+            BASIC_ATTR_MANUFACTURER_NAME,
+            AttributeValue::CharacterString(MANUFACTURER_NAME),
+        ),
+        Attribute::read_only(
+            CLUSTER_BASIC,
+            BASIC_ATTR_MODEL_IDENTIFIER,
+            AttributeValue::CharacterString(MODEL_IDENTIFIER),
+        ),
+        // Mains, single phase
+        Attribute::read_only(
+            CLUSTER_BASIC,
+            BASIC_ATTR_POWER_SOURCE,
+            AttributeValue::Enumeration8(0x01),
+        ),
+    ];
+
     /// On/off cluster
     const CLUSTER_ON_OFF: u16 = 0x0006;
     /// On/off cluster attribute, on/off state
@@ -60,32 +87,49 @@ mod app {
     /// Level control cluster command, stop with on/off
     const LEVEL_CONTROL_CMD_STOP_ON_OFF: u8 = 0x07;
 
+    use core::sync::atomic::{AtomicU8, Ordering};
+
     use microbit::pac as pac;
 
     use bbqueue::{self, BBBuffer};
     use byteorder::{ByteOrder, LittleEndian};
 
-    use microbit::{Board, hal::{clocks, rtc::{Rtc, RtcInterrupt}}, display::nonblocking::{Display, GreyscaleImage} };
+    use microbit::{Board, hal::rtc::{Rtc, RtcInterrupt}, display::nonblocking::{Display, GreyscaleImage} };
 
     use psila_crypto_rust_crypto::RustCryptoBackend;
-    use psila_data::{security::DEFAULT_LINK_KEY, ExtendedAddress, Key, cluster_library::{AttributeDataType, ClusterLibraryStatus, Destination}, device_profile::SimpleDescriptor};
-    use psila_nrf52::{
-        radio::{Radio, MAX_PACKET_LENGHT},
-        timer::Timer,
-    };
+    use psila_data::{security::DEFAULT_LINK_KEY, Key, cluster_library::{AttributeDataType, ClusterLibraryStatus, Destination}, device_profile::SimpleDescriptor};
+    use psila_nrf52::radio::{Radio, MAX_PACKET_LENGHT};
     use psila_service::{self, PsilaService, ClusterLibraryHandler};
 
+    use utilities::clock::{self, LfClkSource};
+    use utilities::eui;
+    use utilities::mono::{self, TimerMonotonic};
+    use utilities::radio::Stats;
+    use utilities::watchdog::Watchdog;
+    use utilities::zcl::{transition_ticks, Attribute, AttributeStore, AttributeValue, LevelControl};
+
     const TIMER_SECOND: u32 = 1_000_000;
 
+    /// Watchdog timeout, generous compared to the one second service tick
+    /// so that slow network operations don't reset the device
+    const WATCHDOG_TIMEOUT_MS: u32 = 5_000;
+
     const TX_BUFFER_SIZE: usize = 1024;
     const RX_BUFFER_SIZE: usize = 1024;
 
     static RX_BUFFER: BBBuffer<RX_BUFFER_SIZE> = BBBuffer::new();
     static TX_BUFFER: BBBuffer<TX_BUFFER_SIZE> = BBBuffer::new();
 
+    /// Packet counters, logged with the service tick
+    static STATS: Stats = Stats::new();
+
+    /// Level shown on the display, updated on each animation tick
+    static SHOWN_LEVEL: AtomicU8 = AtomicU8::new(0);
+
     pub struct ClusterHandler {
         on_off: bool,
         level: u8,
+        basic: AttributeStore<BASIC_ATTRIBUTE_COUNT>,
     }
 
     impl ClusterHandler {
@@ -93,78 +137,49 @@ mod app {
             Self {
                 on_off: false,
                 level: 127,
+                basic: AttributeStore::from_table(&BASIC_ATTRIBUTES).unwrap(),
             }
         }
 
-        fn update_led(&mut self) {
+        /// Moves the LED level, `transition_time` is given in tenths of a
+        /// second
+        fn update_led(&mut self, transition_time: u16) {
             let level = if self.on_off { self.level } else { 0 };
-            let _ = level_update::spawn(level);
+            let _ = level_update::spawn(level, transition_time);
         }
 
         pub fn set_on_off(&mut self, enable: bool) {
             self.on_off = enable;
-            self.update_led();
+            self.update_led(0);
         }
 
         pub fn get_level(&self) -> u8 {
             self.level
         }
 
-        pub fn set_level(&mut self, level: u8) {
+        pub fn move_to_level(&mut self, level: u8, transition_time: u16) {
             self.level = level;
-            self.update_led();
+            self.update_led(transition_time);
         }
-    }
 
-    impl ClusterLibraryHandler for ClusterHandler {
-        fn active_endpoints(&self) -> &[u8] {
-            &[0x01]
-        }
-        fn get_simple_descriptor(&self, endpoint: u8) -> Option<SimpleDescriptor> {
-            match endpoint {
-                0x01 => Some(SimpleDescriptor::new(
-                    0x01,
-                    PROFILE_HOME_AUTOMATION,
-                    DEVICE_DIMMABLE_LIGHT,
-                    0,
-                    &[
-                        CLUSTER_BASIC,
-                        CLUSTER_ON_OFF,
-                        CLUSTER_LEVEL_CONTROL,
-                    ],
-                    &[],
-                )),
-                _ => None,
+        /// Stops a level transition at the level currently shown
+        pub fn stop(&mut self) {
+            if self.on_off {
+                self.level = SHOWN_LEVEL.load(Ordering::Relaxed);
             }
+            let _ = level_stop::spawn();
         }
-        fn read_attribute(
+
+        fn read_attribute_value(
             &self,
             profile: u16,
             cluster: u16,
-            _destination: Destination,
             attribute: u16,
             value: &mut [u8],
         ) -> Result<(AttributeDataType, usize), ClusterLibraryStatus> {
             match (profile, cluster, attribute) {
-                (PROFILE_HOME_AUTOMATION, CLUSTER_BASIC, BASIC_ATTR_LIBRARY_VERSION) => {
-                    value[0] = 0x02;
-                    Ok((AttributeDataType::Unsigned8, 1))
-                }
-                (PROFILE_HOME_AUTOMATION, CLUSTER_BASIC, BASIC_ATTR_MANUFACTURER_NAME) => {
-                    value[0] = MANUFACTURER_NAME.len() as u8;
-                    let end = MANUFACTURER_NAME.len() + 1;
-                    value[1..end].copy_from_slice(MANUFACTURER_NAME.as_bytes());
-                    Ok((AttributeDataType::CharacterString, end))
-                }
-                (PROFILE_HOME_AUTOMATION, CLUSTER_BASIC, BASIC_ATTR_MODEL_IDENTIFIER) => {
-                    value[0] = MODEL_IDENTIFIER.len() as u8;
-                    let end = MODEL_IDENTIFIER.len() + 1;
-                    value[1..end].copy_from_slice(MODEL_IDENTIFIER.as_bytes());
-                    Ok((AttributeDataType::CharacterString, end))
-                }
-                (PROFILE_HOME_AUTOMATION, CLUSTER_BASIC, BASIC_ATTR_POWER_SOURCE) => {
-                    value[0] = 0x01;
-                    Ok((AttributeDataType::Enumeration8, 1))
+                (PROFILE_HOME_AUTOMATION, CLUSTER_BASIC, _) => {
+                    self.basic.read(cluster, attribute, value)
                 }
                 (PROFILE_HOME_AUTOMATION, CLUSTER_ON_OFF, ON_OFF_ATTR_ON_OFF_STATE) => {
                     value[0] = if self.on_off { 0x01 } else { 0x00 };
@@ -187,6 +202,39 @@ mod app {
                 }
             }
         }
+    }
+
+    impl ClusterLibraryHandler for ClusterHandler {
+        fn active_endpoints(&self) -> &[u8] {
+            &[0x01]
+        }
+        fn get_simple_descriptor(&self, endpoint: u8) -> Option<SimpleDescriptor> {
+            match endpoint {
+                0x01 => Some(SimpleDescriptor::new(
+                    0x01,
+                    PROFILE_HOME_AUTOMATION,
+                    DEVICE_DIMMABLE_LIGHT,
+                    0,
+                    &[
+                        CLUSTER_BASIC,
+                        CLUSTER_ON_OFF,
+                        CLUSTER_LEVEL_CONTROL,
+                    ],
+                    &[],
+                )),
+                _ => None,
+            }
+        }
+        fn read_attribute(
+            &self,
+            profile: u16,
+            cluster: u16,
+            _destination: Destination,
+            attribute: u16,
+            value: &mut [u8],
+        ) -> Result<(AttributeDataType, usize), ClusterLibraryStatus> {
+            self.read_attribute_value(profile, cluster, attribute, value)
+        }
         fn write_attribute(
             &mut self,
             profile: u16,
@@ -196,10 +244,9 @@ mod app {
             data_type: AttributeDataType,
             value: &[u8],
         ) -> Result<(), ClusterLibraryStatus> {
-            match (profile, cluster, attribute, data_type) {
-                (PROFILE_HOME_AUTOMATION, CLUSTER_BASIC, BASIC_ATTR_LIBRARY_VERSION, _)
-                | (PROFILE_HOME_AUTOMATION, CLUSTER_BASIC, BASIC_ATTR_POWER_SOURCE, _) => {
-                    Err(ClusterLibraryStatus::ReadOnly)
+            let result = match (profile, cluster, attribute, data_type) {
+                (PROFILE_HOME_AUTOMATION, CLUSTER_BASIC, _, _) => {
+                    self.basic.write(cluster, attribute, data_type, value)
                 }
                 (
                     PROFILE_HOME_AUTOMATION,
@@ -214,7 +261,16 @@ mod app {
                     Err(ClusterLibraryStatus::InvalidValue)
                 }
                 (_, _, _, _) => Err(ClusterLibraryStatus::UnsupportedAttribute),
+            };
+            if let Err(status) = result {
+                defmt::warn!(
+                    "Write attribute {=u16:04x} {=u16:04x} failed, {=str}",
+                    cluster,
+                    attribute,
+                    status_name(status)
+                );
             }
+            result
         }
         fn run(
             &mut self,
@@ -224,7 +280,7 @@ mod app {
             command: u8,
             arguments: &[u8],
         ) -> Result<(), ClusterLibraryStatus> {
-            match (profile, cluster, command) {
+            let result = match (profile, cluster, command) {
                 (PROFILE_HOME_AUTOMATION, CLUSTER_ON_OFF, ON_OFF_CMD_OFF) => {
                     // set off
                     self.set_on_off(false);
@@ -246,7 +302,7 @@ mod app {
                         let level = arguments[0];
                         let transition_time = LittleEndian::read_u16(&arguments[1..=2]);
                         defmt::info!("Move to level: {=u8} {=u16}", level, transition_time);
-                        self.set_level(level);
+                        self.move_to_level(level, transition_time);
                     } else {
                         defmt::warn!("Move to level ?");
                     }
@@ -270,13 +326,14 @@ mod app {
                         1 => self.level.saturating_sub(step),
                         _ => self.level,
                     };
-                    self.set_level(level);
+                    self.move_to_level(level, transition_time);
                     Ok(())
                 }
                 (PROFILE_HOME_AUTOMATION, CLUSTER_LEVEL_CONTROL, LEVEL_CONTROL_CMD_STOP)
                 | (PROFILE_HOME_AUTOMATION, CLUSTER_LEVEL_CONTROL, LEVEL_CONTROL_CMD_STOP_ON_OFF) => {
                     // stop
                     defmt::info!("Stop");
+                    self.stop();
                     Ok(())
                 }
                 (
@@ -286,9 +343,9 @@ mod app {
                 ) => {
                     // move to level, on / off
                     let level = arguments[0];
-                    let _transition_time = LittleEndian::read_u16(&arguments[1..=2]);
-                    self.set_on_off(level > 0);
-                    self.set_level(level);
+                    let transition_time = LittleEndian::read_u16(&arguments[1..=2]);
+                    self.on_off = level > 0;
+                    self.move_to_level(level, transition_time);
                     Ok(())
                 }
                 (PROFILE_HOME_AUTOMATION, CLUSTER_LEVEL_CONTROL, LEVEL_CONTROL_CMD_MOVE_ON_OFF) => {
@@ -314,15 +371,40 @@ mod app {
                         1 => self.level.saturating_sub(step),
                         _ => self.level,
                     };
-                    self.set_on_off(level > 0);
-                    self.set_level(level);
+                    self.on_off = level > 0;
+                    self.move_to_level(level, transition_time);
                     Ok(())
                 }
                 (_, _, _) => {
                     defmt::info!("Command {=u16:04x} {=u16:04x} {=u8:04x}", profile, cluster, command);
                     Err(ClusterLibraryStatus::UnsupportedClusterCommand)
                 }
+            };
+            if let Err(status) = result {
+                defmt::warn!(
+                    "Command {=u16:04x} {=u8:02x} failed, {=str}",
+                    cluster,
+                    command,
+                    status_name(status)
+                );
             }
+            result
+        }
+    }
+
+    /// Name of a cluster library status, for logging
+    fn status_name(s: ClusterLibraryStatus) -> &'static str {
+        match s {
+            ClusterLibraryStatus::Success => "Success",
+            ClusterLibraryStatus::Failure => "Failure",
+            ClusterLibraryStatus::MalformedCommand => "Malformed command",
+            ClusterLibraryStatus::UnsupportedClusterCommand => "Unsupported cluster command",
+            ClusterLibraryStatus::UnsupportedAttribute => "Unsupported attribute",
+            ClusterLibraryStatus::InvalidValue => "Invalid value",
+            ClusterLibraryStatus::ReadOnly => "Read only",
+            ClusterLibraryStatus::InsufficientSpace => "Insufficient space",
+            ClusterLibraryStatus::InvalidDataType => "Invalid data type",
+            _ => "Other status",
         }
     }
 
@@ -356,17 +438,20 @@ mod app {
         rx_consumer: bbqueue::Consumer<'static, RX_BUFFER_SIZE>,
         tx_consumer: bbqueue::Consumer<'static, TX_BUFFER_SIZE>,
         anim_timer: Rtc<pac::RTC0>,
+        watchdog: Watchdog,
     }
 
     #[shared]
     struct SharedResources {
-        level: u8,
+        level: LevelControl,
         display: Display<pac::TIMER0>,
-        timer: pac::TIMER1,
         radio: Radio,
         service: PsilaService<'static, RustCryptoBackend, ClusterHandler, TX_BUFFER_SIZE>,
     }
 
+    #[monotonic(binds = TIMER1, default = true)]
+    type Mono = TimerMonotonic<pac::TIMER1>;
+
     #[init]
     fn init(cx: init::Context) -> (SharedResources, LocalResources, init::Monotonics) {
         let board = Board::new(cx.device, cx.core);
@@ -378,32 +463,19 @@ mod app {
 
         let display = Display::new(board.TIMER0, board.display_pins);
 
-        // Configure to use external clocks, and start them
-        let _clocks = clocks::Clocks::new(board.CLOCK)
-            .enable_ext_hfosc()
-            .set_lfclk_src_external(clocks::LfOscConfiguration::NoExternalNoBypass)
-            .start_lfclk();
+        // Start the high frequency crystal and the low frequency clock
+        clock::init_clocks(board.CLOCK, LfClkSource::Synth);
 
-        let level = 127;
+        let level = LevelControl::new(127);
         let handler = ClusterHandler::new();
 
-        // MAC (EUI-48) address to EUI-64
-        // Add FF FE in the middle
-        //
-        //    01 23 45 67 89 AB
-        //  /  /  /       \  \  \
-        // 01 23 45 FF FE 67 89 AB
-        let devaddr_lo = board.FICR.deviceaddr[0].read().bits();
-        let devaddr_hi = board.FICR.deviceaddr[1].read().bits() as u16;
-        let extended_address = u64::from(devaddr_hi) << 48
-            | u64::from(devaddr_lo & 0xff00_0000) << 40
-            | u64::from(devaddr_lo & 0x00ff_ffff)
-            | 0x0000_00ff_fe00_0000u64;
-        let extended_address = ExtendedAddress::new(extended_address);
-
-        let mut timer1 = board.TIMER1;
-        timer1.init();
-        timer1.fire_in(1, TIMER_SECOND);
+        let extended_address = eui::extended_address_from_ficr(&board.FICR);
+
+        // Petted by the service tick every second
+        let watchdog = Watchdog::start(board.WDT, WATCHDOG_TIMEOUT_MS);
+
+        let monotonic = TimerMonotonic::new(board.TIMER1);
+        service_tick::spawn_after(mono::Duration::from_ticks(TIMER_SECOND)).unwrap();
 
         let mut radio = Radio::new(board.RADIO);
         radio.set_channel(11);
@@ -419,7 +491,6 @@ mod app {
         (
             SharedResources {
                 level,
-                timer: timer1,
                 radio,
                 service: PsilaService::new(
                     crypto_backend,
@@ -435,21 +506,22 @@ mod app {
                 rx_consumer,
                 tx_consumer,
                 anim_timer: rtc0,
+                watchdog,
             },
-            init::Monotonics(),
+            init::Monotonics(monotonic),
         )
     }
 
-    #[task(binds = TIMER1, shared = [service, timer])]
-    fn timer(cx: timer::Context) {
-        (cx.shared.timer, cx.shared.service).lock(|timer, service| {
-            if timer.is_compare_event(1) {
-                timer.ack_compare_event(1);
-                let _ = service.update(timer.now());
-                timer.fire_in(1, TIMER_SECOND);
-            }
-            let _ = radio_tx::spawn();
+    #[task(shared = [service], local = [watchdog])]
+    fn service_tick(mut cx: service_tick::Context) {
+        cx.local.watchdog.pet();
+        let timestamp = monotonics::now().duration_since_epoch().ticks();
+        cx.shared.service.lock(|service| {
+            let _ = service.update(timestamp);
         });
+        defmt::debug!("Packets {}", STATS.snapshot());
+        let _ = service_tick::spawn_after(mono::Duration::from_ticks(TIMER_SECOND));
+        let _ = radio_tx::spawn();
     }
 
     #[task(binds = RADIO, shared = [radio, service], local = [rx_producer])]
@@ -460,20 +532,26 @@ mod app {
             match radio.receive(&mut packet) {
                 Ok(packet_len) => {
                     if packet_len > 0 {
+                        STATS.received();
                         match service.handle_acknowledge(&packet[1..packet_len - 1]) {
                             Ok(to_me) => {
                                 if to_me {
                                     if let Ok(mut grant) = queue.grant_exact(packet_len) {
                                         grant.copy_from_slice(&packet[..packet_len]);
                                         grant.commit(packet_len);
+                                    } else {
+                                        STATS.dropped();
                                     }
                                 }
                             }
                             Err(e) => match e {
                                 psila_service::Error::MalformedPacket => {
+                                    STATS.malformed();
                                     defmt::warn!(
                                         "service handle acknowledge failed, malformed package"
                                     );
+                                    #[cfg(feature = "trace")]
+                                    utilities::trace::log_frame(&packet[1..packet_len - 1]);
                                 }
                                 psila_service::Error::NotEnoughSpace => {
                                     defmt::warn!("service handle acknowledge failed, queue full");
@@ -487,21 +565,24 @@ mod app {
                 }
                 Err(psila_nrf52::radio::Error::CcaBusy) => {
                     defmt::warn!("CCA Busy");
+                    STATS.cca_busy();
                 }
             }
             let _ = radio_tx::spawn();
         });
     }
 
-    #[task(shared = [service, timer], local = [rx_consumer])]
+    #[task(shared = [service], local = [rx_consumer])]
     fn radio_rx(mut cx: radio_rx::Context) {
         let queue = cx.local.rx_consumer;
-        let timestamp = cx.shared.timer.lock(|timer| timer.now());
+        let timestamp = monotonics::now().duration_since_epoch().ticks();
         cx.shared.service.lock(|service| {
             if let Ok(grant) = queue.read() {
                 let packet_length = grant[0] as usize;
                 if let Err(_) = service.receive(timestamp, &grant[1..packet_length - 1]) {
                     defmt::warn!("service receive failed");
+                    #[cfg(feature = "trace")]
+                    utilities::trace::log_frame(&grant[1..packet_length - 1]);
                 }
                 grant.release(packet_length);
                 let _ = radio_tx::spawn();
@@ -519,11 +600,16 @@ mod app {
                     let no_cca = (grant[0] & NO_CCA_MARKER) == NO_CCA_MARKER;
                     let packet_length = (grant[0] & 0x7f) as usize;
                     let data = &grant[1..=packet_length];
-                    if no_cca {
-                        let _ = radio.queue_transmission_no_cca(data);
+                    let queued = if no_cca {
+                        radio.queue_transmission_no_cca(data)
                     }
                     else {
-                        let _ = radio.queue_transmission(data);
+                        radio.queue_transmission(data)
+                    };
+                    // Only frames handed to the radio count as transmitted
+                    match queued {
+                        Ok(_) => STATS.transmitted(),
+                        Err(_) => defmt::warn!("Failed to queue frame for transmission"),
                     }
                     grant.release(packet_length + 1);
                 }
@@ -542,15 +628,28 @@ mod app {
     #[task(binds = RTC0, priority = 2, shared = [display, level], local = [anim_timer])]
     fn rtc0(cx: rtc0::Context) {
         cx.local.anim_timer.reset_event(RtcInterrupt::Tick);
+        let now = monotonics::now().duration_since_epoch().ticks();
         (cx.shared.display, cx.shared.level).lock(|display, level| {
-            display.show(&image(*level));
+            let shown = level.tick(now);
+            SHOWN_LEVEL.store(shown, Ordering::Relaxed);
+            display.show(&image(shown));
         });
     }
 
     #[task(shared = [level], capacity = 10)]
-    fn level_update(mut cx: level_update::Context, new_level: u8) {
+    fn level_update(mut cx: level_update::Context, new_level: u8, transition_time: u16) {
+        let now = monotonics::now().duration_since_epoch().ticks();
+        let duration = transition_ticks(transition_time, mono::TICK_HZ);
+        (cx.shared.level).lock(|level| {
+            level.move_to(new_level, duration, now);
+        });
+    }
+
+    #[task(shared = [level])]
+    fn level_stop(mut cx: level_stop::Context) {
+        let now = monotonics::now().duration_since_epoch().ticks();
         (cx.shared.level).lock(|level| {
-            *level = new_level;
+            level.stop(now);
         });
     }
 }
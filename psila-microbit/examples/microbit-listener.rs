@@ -12,7 +12,8 @@ mod app {
     use microbit::pac as pac;
     use microbit::hal as hal;
 
-    use hal::{clocks, gpio, uarte};
+    use hal::{gpio, uarte};
+    use utilities::clock::{self, LfClkSource};
 
     use psila_nrf52::radio::{Radio, MAX_PACKET_LENGHT};
 
@@ -36,11 +37,8 @@ mod app {
     fn init(cx: init::Context) -> (SharedResources, LocalResources, init::Monotonics) {
         let port0 = gpio::p0::Parts::new(cx.device.P0);
         let port1 = gpio::p1::Parts::new(cx.device.P1);
-        // Configure to use external clocks, and start them
-        let _clocks = clocks::Clocks::new(cx.device.CLOCK)
-            .enable_ext_hfosc()
-            .set_lfclk_src_external(clocks::LfOscConfiguration::NoExternalNoBypass)
-            .start_lfclk();
+        // Start the high frequency crystal and the low frequency clock
+        clock::init_clocks(cx.device.CLOCK, LfClkSource::Synth);
 
         let uarte0 = uarte::Uarte::new(
             cx.device.UARTE0,
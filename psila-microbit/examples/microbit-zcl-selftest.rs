@@ -0,0 +1,193 @@
+#![no_main]
+#![no_std]
+
+//! Zigbee cluster library helper self-test
+//!
+//! Checks the ZCL helpers against known encodings. Nothing has to be
+//! connected and nothing is transmitted.
+
+use psila_microbit as _;
+
+use cortex_m_rt::entry;
+
+use psila_data::cluster_library::{AttributeDataType, ClusterLibraryStatus};
+
+use utilities::zcl::{self, Attribute, AttributeStore, AttributeValue, LevelControl};
+
+/// Basic cluster
+const CLUSTER_BASIC: u16 = 0x0000;
+/// Basic cluster attribute, manufacturer name
+const BASIC_ATTR_MANUFACTURER_NAME: u16 = 0x0004;
+/// Basic cluster attribute, model identifier
+const BASIC_ATTR_MODEL_IDENTIFIER: u16 = 0x0005;
+
+/// Level control cluster
+const CLUSTER_LEVEL_CONTROL: u16 = 0x0008;
+/// Level control cluster attribute, current level
+const LEVEL_ATTR_CURRENT_LEVEL: u16 = 0x0000;
+
+/// Basic cluster attributes
+const BASIC_ATTRIBUTES: [Attribute; 2] = [
+    Attribute::read_only(
+        CLUSTER_BASIC,
+        BASIC_ATTR_MANUFACTURER_NAME,
+        AttributeValue::CharacterString("ERIK"),
+    ),
+    Attribute::read_only(
+        CLUSTER_BASIC,
+        BASIC_ATTR_MODEL_IDENTIFIER,
+        AttributeValue::CharacterString("light"),
+    ),
+];
+
+/// Read attributes status records for the manufacturer name and the model
+/// identifier
+const BASIC_RECORDS: [u8; 19] = [
+    0x04, 0x00, 0x00, 0x42, 0x04, b'E', b'R', b'I', b'K', 0x05, 0x00, 0x00, 0x42, 0x05, b'l', b'i',
+    b'g', b'h', b't',
+];
+
+/// Logs the outcome of a test, returns true on pass
+fn report(name: &str, pass: bool) -> bool {
+    if pass {
+        defmt::info!("{}: pass", name);
+    } else {
+        defmt::error!("{}: fail, unexpected output", name);
+    }
+    pass
+}
+
+#[entry]
+fn main() -> ! {
+    defmt::info!("ZCL helper self-test");
+
+    let mut passed = 0;
+    let mut failed = 0;
+    let mut count = |pass: bool| {
+        if pass {
+            passed += 1;
+        } else {
+            failed += 1;
+        }
+    };
+
+    // The length octet and the string fill the buffer exactly
+    let mut buffer = [0u8; 5];
+    let result = zcl::write_zcl_string(&mut buffer, "ERIK");
+    count(report(
+        "write_zcl_string, exact fit",
+        matches!(result, Ok(5)) && buffer == [0x04, b'E', b'R', b'I', b'K'],
+    ));
+
+    // One byte short, nothing is written
+    let mut buffer = [0u8; 4];
+    let result = zcl::write_zcl_string(&mut buffer, "ERIK");
+    count(report(
+        "write_zcl_string, one byte too small",
+        matches!(result, Err(ClusterLibraryStatus::InsufficientSpace)) && buffer == [0; 4],
+    ));
+
+    let mut buffer = [0xffu8; 2];
+    let result = zcl::write_zcl_string(&mut buffer, "");
+    count(report(
+        "write_zcl_string, empty string",
+        matches!(result, Ok(1)) && buffer == [0x00, 0xff],
+    ));
+
+    // A length of 0xff would mark the string invalid
+    let long = [b'a'; zcl::MAX_STRING_LENGTH + 1];
+    let long = core::str::from_utf8(&long).unwrap();
+    let mut buffer = [0u8; zcl::MAX_STRING_LENGTH + 2];
+    let result = zcl::write_zcl_string(&mut buffer, long);
+    count(report(
+        "write_zcl_string, too long",
+        matches!(result, Err(ClusterLibraryStatus::InvalidValue)),
+    ));
+
+    let basic: AttributeStore<2> = AttributeStore::from_table(&BASIC_ATTRIBUTES).unwrap();
+    // A character string is encoded with its length first
+    let mut value = [0u8; 8];
+    let result = basic.read(CLUSTER_BASIC, BASIC_ATTR_MODEL_IDENTIFIER, &mut value);
+    count(report(
+        "AttributeStore, CharacterString encoding",
+        matches!(result, Ok((AttributeDataType::CharacterString, 6)))
+            && value[..6] == BASIC_RECORDS[13..],
+    ));
+
+    // An unsigned 8-bit value is a single octet, a set value is read back
+    let level_attributes = [Attribute::read_write(
+        CLUSTER_LEVEL_CONTROL,
+        LEVEL_ATTR_CURRENT_LEVEL,
+        AttributeValue::Unsigned8(0),
+    )];
+    let mut level: AttributeStore<1> = AttributeStore::from_table(&level_attributes).unwrap();
+    let set = level.set(
+        CLUSTER_LEVEL_CONTROL,
+        LEVEL_ATTR_CURRENT_LEVEL,
+        AttributeValue::Unsigned8(0xa5),
+    );
+    let mut value = [0u8; 2];
+    let result = level.read(CLUSTER_LEVEL_CONTROL, LEVEL_ATTR_CURRENT_LEVEL, &mut value);
+    count(report(
+        "AttributeStore, Unsigned8 encoding",
+        set.is_ok() && matches!(result, Ok((AttributeDataType::Unsigned8, 1))) && value[0] == 0xa5,
+    ));
+
+    let read = |attribute, value: &mut [u8]| basic.read(CLUSTER_BASIC, attribute, value);
+    let attributes = [BASIC_ATTR_MANUFACTURER_NAME, BASIC_ATTR_MODEL_IDENTIFIER];
+
+    let mut output = [0u8; 32];
+    let used = zcl::read_attributes(&attributes, &mut output, read);
+    count(report(
+        "read_attributes, basic cluster",
+        output[..used] == BASIC_RECORDS,
+    ));
+
+    // The model identifier doesn't fit and gets a status record, a further
+    // attribute is left out
+    let mut output = [0u8; 12];
+    let used = zcl::read_attributes(&[0x0004, 0x0005, 0x0004], &mut output, read);
+    let status = u8::from(ClusterLibraryStatus::InsufficientSpace);
+    count(report(
+        "read_attributes, no room",
+        used == 12 && output[..9] == BASIC_RECORDS[..9] && output[9..] == [0x05, 0x00, status],
+    ));
+
+    let mut output = [0u8; 8];
+    let used = zcl::read_attributes(&[0x0001], &mut output, read);
+    let status = u8::from(ClusterLibraryStatus::UnsupportedAttribute);
+    count(report(
+        "read_attributes, unsupported attribute",
+        output[..used] == [0x01, 0x00, status],
+    ));
+
+    // The level rises at every tick and reaches the target at the end
+    let mut level = LevelControl::new(0);
+    level.move_to(254, 10, 0);
+    let mut previous = level.level(0);
+    let mut rising = previous == 0;
+    for now in 1..=10 {
+        let current = level.level(now);
+        rising &= current > previous;
+        previous = current;
+    }
+    count(report(
+        "LevelControl, 0 to 254 over 10 ticks",
+        rising && previous == 254 && !level.is_moving(10),
+    ));
+
+    // A stopped transition stays at the level it had
+    let stopped = level.level(4);
+    level.stop(4);
+    count(report(
+        "LevelControl, stop",
+        level.level(10) == stopped && level.target() == stopped && !level.is_moving(10),
+    ));
+
+    if failed == 0 {
+        defmt::info!("All {} tests passed", passed);
+    } else {
+        defmt::error!("{} of {} tests failed", failed, passed + failed);
+    }
+    psila_microbit::exit()
+}
@@ -0,0 +1,104 @@
+//! AES-128 CCM* helpers as used by the IEEE 802.15.4 and Zigbee security.
+
+use ccm::{
+    aead::{generic_array::GenericArray, AeadInPlace, KeyInit},
+    consts::{U13, U16, U4, U8},
+    Ccm,
+};
+
+type AesCcmMic4 = Ccm<aes::Aes128, U4, U13>;
+type AesCcmMic8 = Ccm<aes::Aes128, U8, U13>;
+type AesCcmMic16 = Ccm<aes::Aes128, U16, U13>;
+
+/// CCM* errors
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Error {
+    /// The MIC length isn't one of 4, 8 or 16 octets
+    UnsupportedMicLength(usize),
+    /// The output buffer can't hold the payload
+    NotEnoughSpace,
+    /// Failed to authenticate the payload with the MIC
+    Authentication,
+    /// Failed to encrypt the payload
+    Encryption,
+}
+
+/// Decrypt and authenticate `payload` into `output`, returns the payload size
+pub fn decode(
+    key: &[u8; 16],
+    nonce: &[u8; 13],
+    payload: &[u8],
+    mic: &[u8],
+    aad: &[u8],
+    output: &mut [u8],
+) -> Result<usize, Error> {
+    let key: &GenericArray<u8, U16> = GenericArray::from_slice(key);
+    let nonce: &GenericArray<u8, U13> = GenericArray::from_slice(nonce);
+    let payload_len = payload.len();
+    if output.len() < payload_len {
+        return Err(Error::NotEnoughSpace);
+    }
+    let buffer = &mut output[..payload_len];
+    buffer.copy_from_slice(payload);
+    let result = match mic.len() {
+        4 => {
+            let tag: &GenericArray<u8, U4> = GenericArray::from_slice(mic);
+            AesCcmMic4::new(key).decrypt_in_place_detached(nonce, aad, buffer, tag)
+        }
+        8 => {
+            let tag: &GenericArray<u8, U8> = GenericArray::from_slice(mic);
+            AesCcmMic8::new(key).decrypt_in_place_detached(nonce, aad, buffer, tag)
+        }
+        16 => {
+            let tag: &GenericArray<u8, U16> = GenericArray::from_slice(mic);
+            AesCcmMic16::new(key).decrypt_in_place_detached(nonce, aad, buffer, tag)
+        }
+        length => return Err(Error::UnsupportedMicLength(length)),
+    };
+    match result {
+        Ok(_) => Ok(payload_len),
+        Err(_) => Err(Error::Authentication),
+    }
+}
+
+/// Encrypt `payload` into `output` and write the MIC to `mic`, returns the
+/// payload size
+pub fn encode(
+    key: &[u8; 16],
+    nonce: &[u8; 13],
+    payload: &[u8],
+    mic: &mut [u8],
+    aad: &[u8],
+    output: &mut [u8],
+) -> Result<usize, Error> {
+    let key: &GenericArray<u8, U16> = GenericArray::from_slice(key);
+    let nonce: &GenericArray<u8, U13> = GenericArray::from_slice(nonce);
+    let payload_len = payload.len();
+    if output.len() < payload_len {
+        return Err(Error::NotEnoughSpace);
+    }
+    let buffer = &mut output[..payload_len];
+    buffer.copy_from_slice(payload);
+    match mic.len() {
+        4 => {
+            let tag = AesCcmMic4::new(key)
+                .encrypt_in_place_detached(nonce, aad, buffer)
+                .map_err(|_| Error::Encryption)?;
+            mic.copy_from_slice(tag.as_slice());
+        }
+        8 => {
+            let tag = AesCcmMic8::new(key)
+                .encrypt_in_place_detached(nonce, aad, buffer)
+                .map_err(|_| Error::Encryption)?;
+            mic.copy_from_slice(tag.as_slice());
+        }
+        16 => {
+            let tag = AesCcmMic16::new(key)
+                .encrypt_in_place_detached(nonce, aad, buffer)
+                .map_err(|_| Error::Encryption)?;
+            mic.copy_from_slice(tag.as_slice());
+        }
+        length => return Err(Error::UnsupportedMicLength(length)),
+    }
+    Ok(payload_len)
+}
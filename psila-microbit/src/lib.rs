@@ -1,9 +1,13 @@
 #![no_std]
 
+pub mod ccmstar;
+
 use core::sync::atomic::{AtomicUsize, Ordering};
 
 use defmt_rtt as _; // global logger
-use panic_probe as _;
+
+// Panic handler, selected through the utilities panic features
+use utilities as _;
 // TODO(5) adjust HAL import
 // use some_hal as _; // memory layout
 
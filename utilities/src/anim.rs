@@ -0,0 +1,64 @@
+//! Frame sequencing for simple animations.
+
+/// Steps through a sequence of frames, driven by a periodic tick
+///
+/// The frame type is generic so that it can hold anything from a micro:bit
+/// `GreyscaleImage` to raw `[[u8; 5]; 5]` data. A new frame is shown every
+/// `divisor` ticks, after the last frame the sequence starts over.
+pub struct Animation<'a, F> {
+    frames: &'a [F],
+    divisor: u32,
+    ticks: u32,
+    index: usize,
+}
+
+impl<'a, F> Animation<'a, F> {
+    /// Creates an animation showing a new frame every `divisor` ticks
+    ///
+    /// A `divisor` of zero is treated as one. Panics if `frames` is empty.
+    pub fn new(frames: &'a [F], divisor: u32) -> Self {
+        assert!(!frames.is_empty());
+        Self {
+            frames,
+            divisor: divisor.max(1),
+            ticks: 0,
+            index: 0,
+        }
+    }
+
+    /// Set the number of ticks each frame is shown
+    pub fn set_divisor(&mut self, divisor: u32) {
+        self.divisor = divisor.max(1);
+        self.ticks = 0;
+    }
+
+    /// Restart the animation from the first frame
+    pub fn reset(&mut self) {
+        self.ticks = 0;
+        self.index = 0;
+    }
+
+    /// Index of the current frame
+    pub fn frame_index(&self) -> usize {
+        self.index
+    }
+
+    /// The current frame, without advancing
+    pub fn frame(&self) -> &'a F {
+        &self.frames[self.index]
+    }
+
+    /// Advance one tick and get the frame to show
+    ///
+    /// Call this once for each tick, for example from the RTC tick
+    /// interrupt.
+    pub fn next_frame(&mut self) -> &'a F {
+        let frame = &self.frames[self.index];
+        self.ticks += 1;
+        if self.ticks >= self.divisor {
+            self.ticks = 0;
+            self.index = (self.index + 1) % self.frames.len();
+        }
+        frame
+    }
+}
@@ -0,0 +1,242 @@
+//! Async delays on a TIMER peripheral.
+//!
+//! `Timer` runs a TIMER as a free running 1 MHz counter and hands out
+//! futures completing after a delay, woken from the TIMER compare interrupt.
+//! Any executor can poll the futures, the timer only needs its interrupt
+//! handler to call `Timer::on_interrupt`.
+//!
+//! The timer is shared between the tasks awaiting delays and the interrupt
+//! handler, so it is usually kept in a `static`. With embassy-executor, bind
+//! the TIMER interrupt to a handler calling `on_interrupt` and pass
+//! `&'static Timer<_>` to the tasks. Under RTIC, create the timer in `init`,
+//! for example with `cortex_m::singleton!`, keep the `&'static Timer<_>` as
+//! a resource and call `on_interrupt` from a task bound to the TIMER
+//! interrupt, RTIC unmasks the interrupt for bound tasks.
+//!
+//! ```ignore
+//! let timer: &'static Timer<TIMER2> =
+//!     cortex_m::singleton!(: Timer<TIMER2> = Timer::new(cx.device.TIMER2)).unwrap();
+//!
+//! // In the TIMER2 interrupt handler
+//! timer.on_interrupt();
+//!
+//! // In an async task
+//! timer.after_millis(120).await;
+//! ```
+
+use core::cell::RefCell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+use cortex_m::interrupt::{self, Mutex};
+
+use crate::hal::timer::Instance;
+
+/// Counter frequency, delays have a resolution of 1 us
+pub const TICK_HZ: u32 = 1_000_000;
+
+/// Longest delay in microseconds, half the counter range so that deadlines
+/// can be compared across the counter wrap, about 35 minutes
+pub const MAX_DELAY_MICROS: u32 = u32::MAX / 2;
+
+/// Number of delays that can wait for the compare interrupt at the same time
+pub const MAX_PENDING: usize = 4;
+
+/// Deadline and waker of each pending delay, the waker is taken when the
+/// delay is woken
+type Pending = [Option<(u32, Option<Waker>)>; MAX_PENDING];
+
+/// Async timer using a 32 bit TIMER running at 1 MHz
+///
+/// Capture/compare register 0 is used for the deadline, register 1 for
+/// reading the time. Up to `MAX_PENDING` delays keep their own waker and
+/// the compare register is armed for the earliest deadline. Further delays
+/// pending at the same time still complete, but they are polled again and
+/// again until a slot is released, so keep the number of concurrent delays
+/// within `MAX_PENDING`.
+pub struct Timer<T> {
+    timer: T,
+    pending: Mutex<RefCell<Pending>>,
+}
+
+// The wakers are only accessed in critical sections, and the compare
+// register is armed in the same critical sections by both the tasks and the
+// interrupt handler. Other register accesses write whole registers.
+unsafe impl<T> Sync for Timer<T> where T: Instance {}
+
+impl<T> Timer<T>
+where
+    T: Instance,
+{
+    /// Configures the timer as a 32 bit 1 MHz counter and starts it
+    pub fn new(timer: T) -> Self {
+        crate::timer::start_free_running(&timer);
+        timer.as_timer0().events_compare[0].write(|w| w);
+        Self {
+            timer,
+            pending: Mutex::new(RefCell::new(Default::default())),
+        }
+    }
+
+    /// Current counter value, in microseconds since the timer was started
+    pub fn now(&self) -> u32 {
+        let regs = self.timer.as_timer0();
+        regs.tasks_capture[1].write(|w| unsafe { w.bits(1) });
+        regs.cc[1].read().bits()
+    }
+
+    /// Future completing `micros` microseconds from now
+    ///
+    /// The delay starts when this is called, not when the future is first
+    /// polled. Delays are limited to `MAX_DELAY_MICROS`.
+    pub fn after_micros(&self, micros: u32) -> Delay<'_, T> {
+        Delay {
+            timer: self,
+            deadline: self.now().wrapping_add(micros.min(MAX_DELAY_MICROS)),
+            slot: None,
+        }
+    }
+
+    /// Future completing `millis` milliseconds from now
+    ///
+    /// Delays are limited to `MAX_DELAY_MICROS`.
+    pub fn after_millis(&self, millis: u32) -> Delay<'_, T> {
+        self.after_micros(millis.saturating_mul(1000))
+    }
+
+    /// Handles the TIMER interrupt, wakes the tasks awaiting the deadlines
+    /// that have passed
+    pub fn on_interrupt(&self) {
+        let regs = self.timer.as_timer0();
+        if regs.events_compare[0].read().bits() == 0 {
+            return;
+        }
+        regs.events_compare[0].write(|w| w);
+        let mut expired: [Option<Waker>; MAX_PENDING] = Default::default();
+        interrupt::free(|cs| self.update(&mut self.pending.borrow(cs).borrow_mut(), &mut expired));
+        for waker in expired.iter_mut().filter_map(Option::take) {
+            waker.wake();
+        }
+    }
+
+    /// Stops the timer and returns the TIMER peripheral
+    pub fn free(self) -> T {
+        let regs = self.timer.as_timer0();
+        regs.intenclr.write(|w| w.compare0().clear());
+        regs.tasks_stop.write(|w| unsafe { w.bits(1) });
+        self.timer
+    }
+
+    /// Has the counter passed `deadline`?
+    fn expired(&self, deadline: u32) -> bool {
+        self.now().wrapping_sub(deadline) <= MAX_DELAY_MICROS
+    }
+
+    /// Moves the wakers of the delays that have expired to `expired` and
+    /// arms the compare interrupt for the earliest remaining deadline
+    fn update(&self, pending: &mut Pending, expired: &mut [Option<Waker>; MAX_PENDING]) {
+        let regs = self.timer.as_timer0();
+        loop {
+            for (entry, woken) in pending.iter_mut().zip(expired.iter_mut()) {
+                if let Some((deadline, waker)) = entry {
+                    if waker.is_some() && self.expired(*deadline) {
+                        *woken = waker.take();
+                    }
+                }
+            }
+            let now = self.now();
+            let next = pending
+                .iter()
+                .filter_map(|entry| match entry {
+                    Some((deadline, Some(_))) => Some(*deadline),
+                    _ => None,
+                })
+                .min_by_key(|deadline| deadline.wrapping_sub(now));
+            let deadline = match next {
+                Some(deadline) => deadline,
+                None => {
+                    regs.intenclr.write(|w| w.compare0().clear());
+                    return;
+                }
+            };
+            regs.cc[0].write(|w| unsafe { w.bits(deadline) });
+            regs.events_compare[0].write(|w| w);
+            regs.intenset.write(|w| w.compare0().set());
+            // The deadline may have passed before the compare register was
+            // set, in which case there will be no compare event
+            if !self.expired(deadline) {
+                return;
+            }
+        }
+    }
+}
+
+/// Future returned by `Timer::after_micros` and `Timer::after_millis`
+pub struct Delay<'a, T> {
+    timer: &'a Timer<T>,
+    deadline: u32,
+    /// Index of the pending entry holding the waker
+    slot: Option<usize>,
+}
+
+impl<'a, T> Delay<'a, T> {
+    /// Frees the pending entry, if any
+    fn release(&mut self) {
+        if let Some(slot) = self.slot.take() {
+            interrupt::free(|cs| self.timer.pending.borrow(cs).borrow_mut()[slot] = None);
+        }
+    }
+}
+
+impl<'a, T> Drop for Delay<'a, T> {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+impl<'a, T> Future for Delay<'a, T>
+where
+    T: Instance,
+{
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let delay = self.get_mut();
+        if delay.timer.expired(delay.deadline) {
+            delay.release();
+            return Poll::Ready(());
+        }
+        let mut expired: [Option<Waker>; MAX_PENDING] = Default::default();
+        let timer = delay.timer;
+        let deadline = delay.deadline;
+        let slot = interrupt::free(|cs| {
+            let mut pending = timer.pending.borrow(cs).borrow_mut();
+            let slot = delay
+                .slot
+                .or_else(|| pending.iter().position(Option::is_none));
+            if let Some(slot) = slot {
+                match &pending[slot] {
+                    Some((_, Some(waker))) if waker.will_wake(cx.waker()) => {}
+                    _ => pending[slot] = Some((deadline, Some(cx.waker().clone()))),
+                }
+                timer.update(&mut pending, &mut expired);
+            }
+            slot
+        });
+        delay.slot = slot;
+        for waker in expired.iter_mut().filter_map(Option::take) {
+            waker.wake();
+        }
+        if slot.is_none() {
+            // No free entry, poll again instead of waiting for the interrupt
+            cx.waker().wake_by_ref();
+        }
+        if timer.expired(deadline) {
+            delay.release();
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Clock initialisation shared by the examples.
+
+use crate::hal::pac;
+
+/// Low frequency clock (LFCLK) source
+///
+/// Boards with a 32.768 kHz crystal, such as the nRF52833 DK, should use
+/// `Crystal`. Boards without one, such as the micro:bit v2, must use `Synth`
+/// or `Rc`. Selecting `Crystal` on a board without a crystal makes the
+/// LFCLK, and everything depending on it like the RTC, stall.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LfClkSource {
+    /// Internal 32.768 kHz RC oscillator
+    Rc,
+    /// Synthesized from the high frequency clock, requires the HFCLK to run
+    Synth,
+    /// External 32.768 kHz crystal
+    Crystal,
+}
+
+/// Longest time to wait for an oscillator to start, in milliseconds
+///
+/// The 32.768 kHz crystal typically starts within 250 ms, the high
+/// frequency crystal within 1 ms.
+pub const START_TIMEOUT_MS: u32 = 1000;
+
+/// CPU cycles per millisecond at 64 MHz
+const CYCLES_PER_MS: u32 = 64_000;
+
+/// Clock configuration
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClockConfig {
+    /// Low frequency clock source
+    pub lfclk: LfClkSource,
+    /// Run the high frequency clock from the external crystal (HFXO), needed
+    /// by the radio and for accurate UART and synthesized LFCLK timing
+    pub hfxo: bool,
+}
+
+impl Default for ClockConfig {
+    /// HFXO and the 32.768 kHz crystal, as fitted on the nRF52833 DK
+    fn default() -> Self {
+        Self {
+            lfclk: LfClkSource::Crystal,
+            hfxo: true,
+        }
+    }
+}
+
+/// Clock configuration errors
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum ClockError {
+    /// The high frequency crystal didn't start
+    HfxoTimeout,
+    /// The low frequency clock didn't start
+    LfclkTimeout,
+}
+
+/// Started clocks, returned by `configure_clocks`
+pub struct StartedClocks {
+    clock: pac::CLOCK,
+    lfclk: LfClkSource,
+}
+
+impl StartedClocks {
+    /// The low frequency clock source in use, `Rc` if the crystal was
+    /// requested but didn't start
+    pub fn lfclk_source(&self) -> LfClkSource {
+        self.lfclk
+    }
+
+    /// Is the high frequency clock running from the external crystal?
+    pub fn hfxo_running(&self) -> bool {
+        self.clock.hfclkstat.read().src().is_xtal()
+    }
+
+    /// Returns the CLOCK peripheral, the clocks keep running
+    pub fn free(self) -> pac::CLOCK {
+        self.clock
+    }
+}
+
+/// Waits for `started` to return true
+///
+/// Returns false if it didn't within `START_TIMEOUT_MS`.
+fn wait_started(started: impl Fn() -> bool) -> bool {
+    for _ in 0..START_TIMEOUT_MS {
+        if started() {
+            return true;
+        }
+        cortex_m::asm::delay(CYCLES_PER_MS);
+    }
+    started()
+}
+
+/// Starts the low frequency clock from `source`
+fn start_lfclk(clock: &pac::CLOCK, source: LfClkSource) -> bool {
+    clock.tasks_lfclkstop.write(|w| unsafe { w.bits(1) });
+    while clock.lfclkstat.read().state().is_running() {}
+    clock.lfclksrc.write(|w| match source {
+        LfClkSource::Rc => w.src().rc(),
+        LfClkSource::Synth => w.src().synth(),
+        LfClkSource::Crystal => w.src().xtal(),
+    });
+    clock.events_lfclkstarted.reset();
+    clock.tasks_lfclkstart.write(|w| unsafe { w.bits(1) });
+    let started = wait_started(|| clock.events_lfclkstarted.read().bits() != 0);
+    clock.events_lfclkstarted.reset();
+    started
+}
+
+/// Starts the high and low frequency clocks
+///
+/// When the 32.768 kHz crystal is requested but doesn't start within
+/// `START_TIMEOUT_MS`, as on a board without a crystal fitted, a warning is
+/// logged and the internal RC oscillator is used instead. Check
+/// `StartedClocks::lfclk_source` for the source in use.
+pub fn configure_clocks(
+    clock: pac::CLOCK,
+    config: ClockConfig,
+) -> Result<StartedClocks, ClockError> {
+    if config.hfxo {
+        clock.events_hfclkstarted.reset();
+        clock.tasks_hfclkstart.write(|w| unsafe { w.bits(1) });
+        let started = wait_started(|| clock.events_hfclkstarted.read().bits() != 0);
+        clock.events_hfclkstarted.reset();
+        if !started {
+            return Err(ClockError::HfxoTimeout);
+        }
+    }
+    let mut lfclk = config.lfclk;
+    if !start_lfclk(&clock, lfclk) {
+        if lfclk != LfClkSource::Crystal {
+            return Err(ClockError::LfclkTimeout);
+        }
+        defmt::warn!("32.768 kHz crystal didn't start, using the RC oscillator");
+        lfclk = LfClkSource::Rc;
+        if !start_lfclk(&clock, lfclk) {
+            return Err(ClockError::LfclkTimeout);
+        }
+    }
+    Ok(StartedClocks { clock, lfclk })
+}
+
+/// Starts the external high frequency oscillator and the low frequency
+/// clock from the given source
+///
+/// See `configure_clocks`, a crystal that doesn't start is replaced by the
+/// RC oscillator. Failures are logged.
+pub fn init_clocks(clock: pac::CLOCK, source: LfClkSource) {
+    let config = ClockConfig {
+        lfclk: source,
+        hfxo: true,
+    };
+    if let Err(error) = configure_clocks(clock, config) {
+        defmt::error!("Failed to start clocks, {}", error);
+    }
+}
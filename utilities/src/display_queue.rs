@@ -0,0 +1,107 @@
+//! Fixed capacity queue of display commands.
+//!
+//! Decouples drawing from flushing to SPI. A high priority task enqueues
+//! drawing commands through a `Producer` while a low priority task, owning
+//! the display, drains them through the `Consumer`.
+//!
+//! ```ignore
+//! static mut QUEUE: DisplayQueue<16> = DisplayQueue::new();
+//! let (producer, consumer) = unsafe { QUEUE.split() };
+//! let mut drain = DisplayDrain::new(consumer);
+//! ```
+
+use embedded_graphics::{
+    pixelcolor::{raw::RawU16, Rgb565},
+    DrawTarget,
+};
+use embedded_hal::digital::v2::InputPin;
+use heapless::spsc::{Consumer, Queue};
+use heapless::Vec;
+
+use crate::spi::SpiSendCommandData;
+use crate::st7735s::{DisplayError, Panel, TftDisplay};
+
+/// Maximum number of pixels carried by a single `Pixels` command
+pub const PIXELS_PER_COMMAND: usize = 32;
+
+/// Drawing command
+#[derive(Clone, Debug, PartialEq)]
+pub enum DisplayCommand {
+    /// Set the drawing window, start and end coordinates are inclusive
+    SetWindow { sx: u16, sy: u16, ex: u16, ey: u16 },
+    /// Pixel colors written sequentially into the drawing window
+    Pixels(Vec<u16, PIXELS_PER_COMMAND>),
+    /// Fill the whole display with a color
+    Clear(u16),
+}
+
+/// Queue holding up to `N - 1` display commands
+pub type DisplayQueue<const N: usize> = Queue<DisplayCommand, N>;
+
+/// Drains queued commands to the display
+///
+/// `Pixels` commands continue the memory write started by the first
+/// `Pixels` after a `SetWindow`, also when they are drained by a later
+/// `drain` call. Nothing else may be sent to the display between calls.
+pub struct DisplayDrain<'a, const N: usize> {
+    consumer: Consumer<'a, DisplayCommand, N>,
+    /// Window set by the last `SetWindow`
+    window: Option<(u16, u16, u16, u16)>,
+    /// Pixels were written since the window was set, following `Pixels`
+    /// continue the write
+    started: bool,
+}
+
+impl<'a, const N: usize> DisplayDrain<'a, N> {
+    /// Drain the commands queued through the producer of `consumer`
+    pub fn new(consumer: Consumer<'a, DisplayCommand, N>) -> Self {
+        Self {
+            consumer,
+            window: None,
+            started: false,
+        }
+    }
+
+    /// Drains all queued commands to the display.
+    ///
+    /// A `Clear` ends the pixel write, `Pixels` following it start over at
+    /// the beginning of the last window. Returns the number of commands
+    /// executed.
+    pub fn drain<SPI, P, TE>(
+        &mut self,
+        display: &mut TftDisplay<SPI, P, TE>,
+    ) -> Result<usize, DisplayError>
+    where
+        SPI: SpiSendCommandData,
+        P: Panel,
+        TE: InputPin,
+    {
+        let mut count = 0;
+        while let Some(command) = self.consumer.dequeue() {
+            match command {
+                DisplayCommand::SetWindow { sx, sy, ex, ey } => {
+                    display.set_address_window(sx, sy, ex, ey)?;
+                    self.window = Some((sx, sy, ex, ey));
+                    self.started = false;
+                }
+                DisplayCommand::Pixels(pixels) => {
+                    if self.started {
+                        display.write_pixel_data(pixels.iter().cloned())?;
+                    } else {
+                        display.write_pixels(pixels.iter().cloned())?;
+                        self.started = true;
+                    }
+                }
+                DisplayCommand::Clear(color) => {
+                    display.clear(Rgb565::from(RawU16::new(color)))?;
+                    if let Some((sx, sy, ex, ey)) = self.window {
+                        display.set_address_window(sx, sy, ex, ey)?;
+                    }
+                    self.started = false;
+                }
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+}
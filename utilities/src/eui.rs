@@ -0,0 +1,32 @@
+//! Device addresses derived from the factory information.
+
+use crate::hal::pac::FICR;
+
+#[cfg(feature = "psila-data")]
+use psila_data::ExtendedAddress;
+
+/// Expands a MAC (EUI-48) address to an EUI-64 address
+///
+/// FF FE is inserted in the middle.
+///
+/// ```text
+///    01 23 45 67 89 AB
+///  /  /  /       \  \  \
+/// 01 23 45 FF FE 67 89 AB
+/// ```
+pub fn eui64_from_eui48(address: u64) -> u64 {
+    (address & 0xffff_ff00_0000) << 16 | 0x0000_00ff_fe00_0000 | (address & 0x00ff_ffff)
+}
+
+/// Reads the EUI-64 address from the device address in FICR
+pub fn eui64_from_ficr(ficr: &FICR) -> u64 {
+    let devaddr_lo = ficr.deviceaddr[0].read().bits();
+    let devaddr_hi = ficr.deviceaddr[1].read().bits() as u16;
+    eui64_from_eui48(u64::from(devaddr_hi) << 32 | u64::from(devaddr_lo))
+}
+
+/// Reads the IEEE 802.15.4 extended address from the device address in FICR
+#[cfg(feature = "psila-data")]
+pub fn extended_address_from_ficr(ficr: &FICR) -> ExtendedAddress {
+    ExtendedAddress::new(eui64_from_ficr(ficr))
+}
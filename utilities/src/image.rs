@@ -0,0 +1,52 @@
+//! Helpers for rendering micro:bit style greyscale images on colour displays.
+
+use embedded_graphics::{
+    drawable::Drawable,
+    geometry::Point,
+    pixelcolor::{raw::RawU16, Rgb565},
+    primitives::{rectangle::Rectangle, Primitive},
+    style::PrimitiveStyle,
+    DrawTarget,
+};
+
+/// Highest greyscale level, as used by the micro:bit display
+pub const GREY_MAX: u8 = 9;
+
+/// Maps a greyscale level, 0 to 9, to a raw Rgb565 grey
+///
+/// Levels above 9 are treated as 9.
+pub fn grey_to_rgb565(level: u8) -> u16 {
+    let level = u16::from(level.min(GREY_MAX));
+    let value = level * 255 / u16::from(GREY_MAX);
+    let red = value >> 3;
+    let green = value >> 2;
+    let blue = value >> 3;
+    (red << 11) | (green << 5) | blue
+}
+
+/// Draws a 5×5 greyscale image as `block` × `block` pixel squares
+///
+/// `data` is indexed as `data[y][x]`, the same layout as the micro:bit
+/// `GreyscaleImage`.
+pub fn draw_greyscale<D>(
+    target: &mut D,
+    data: &[[u8; 5]; 5],
+    top_left: Point,
+    block: u32,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Rgb565>,
+{
+    let block = block as i32;
+    for (y, row) in data.iter().enumerate() {
+        for (x, level) in row.iter().enumerate() {
+            let color = Rgb565::from(RawU16::new(grey_to_rgb565(*level)));
+            let start = top_left + Point::new(x as i32 * block, y as i32 * block);
+            let end = start + Point::new(block - 1, block - 1);
+            Rectangle::new(start, end)
+                .into_styled(PrimitiveStyle::with_fill(color))
+                .draw(target)?;
+        }
+    }
+    Ok(())
+}
@@ -1,7 +1,49 @@
 #![no_std]
 
+#[cfg(any(
+    all(feature = "panic-defmt", feature = "panic-rtt"),
+    all(feature = "panic-defmt", feature = "panic-itm"),
+    all(feature = "panic-rtt", feature = "panic-itm"),
+))]
+compile_error!("Select only one of the panic-defmt, panic-rtt and panic-itm features");
+
+#[cfg(feature = "panic-itm")]
+use panic_itm as _;
+#[cfg(feature = "panic-defmt")]
+use panic_probe as _;
+#[cfg(feature = "panic-rtt")]
+use panic_rtt_target as _;
+
+pub mod anim;
+pub mod async_timer;
+pub mod clock;
+pub mod display_queue;
+pub mod eui;
 mod extended_enum;
+pub mod image;
+pub mod log;
+#[cfg(feature = "mono")]
+pub mod mono;
+pub mod power;
+#[cfg(feature = "radio")]
+pub mod radio;
+pub mod repl;
+pub mod rng;
+pub mod rotated;
+pub mod sdcard;
 pub mod spi;
+pub mod ssd1306;
 pub mod st7735s;
+pub mod st7789;
+pub mod text;
+pub mod timer;
+#[cfg(feature = "tone")]
+pub mod tone;
+#[cfg(feature = "trace")]
+pub mod trace;
+pub mod uart;
+pub mod watchdog;
+#[cfg(feature = "zcl")]
+pub mod zcl;
 
 use nrf52833_hal as hal;
@@ -0,0 +1,73 @@
+//! Logging facade selecting the transport with a cargo feature.
+//!
+//! `log!` takes a format string and arguments and writes one line to the
+//! selected sink.
+//!
+//!  * defmt, the default, logs at info level through the defmt logger the
+//!    application links, such as defmt-rtt.
+//!  * `log-rtt` prints over RTT with rtt-target, call `init` first. It can't
+//!    be combined with defmt-rtt as both own the RTT control block.
+//!  * `log-itm` prints over ITM stimulus port 0, the debugger must enable
+//!    tracing.
+//!
+//! The format string is passed on to the sink, so stick to plain `{}`
+//! placeholders with arguments implementing both `core::fmt::Display` and
+//! `defmt::Format` for the same code to work with every sink. Pick the panic
+//! handler feature matching the sink, `panic-defmt`, `panic-rtt` or
+//! `panic-itm`.
+
+#[cfg(all(feature = "log-rtt", feature = "log-itm"))]
+compile_error!("Select only one of the log-rtt and log-itm features");
+
+#[cfg(feature = "log-rtt")]
+#[doc(hidden)]
+pub use rtt_target;
+
+/// Sets up the log sink, call once at start-up before logging
+///
+/// Only RTT needs this, for the other sinks it does nothing.
+pub fn init() {
+    #[cfg(feature = "log-rtt")]
+    rtt_target::rtt_init_print!();
+}
+
+/// Writes a line to ITM stimulus port 0
+#[cfg(feature = "log-itm")]
+#[doc(hidden)]
+pub fn itm_write(args: core::fmt::Arguments) {
+    use cortex_m::peripheral::{itm::RegisterBlock, ITM};
+    cortex_m::interrupt::free(|_| {
+        // Ports are written whole words at a time with interrupts disabled,
+        // lines from different contexts can't interleave
+        let itm = unsafe { &mut *(ITM::PTR as *mut RegisterBlock) };
+        cortex_m::itm::write_fmt(&mut itm.stim[0], args);
+        cortex_m::itm::write_str(&mut itm.stim[0], "\n");
+    });
+}
+
+/// Logs a line to the sink selected by the log features, defmt by default
+#[cfg(not(any(feature = "log-rtt", feature = "log-itm")))]
+#[macro_export]
+macro_rules! log {
+    ($($arg:tt)*) => {
+        defmt::info!($($arg)*)
+    };
+}
+
+/// Logs a line to the sink selected by the log features, defmt by default
+#[cfg(feature = "log-rtt")]
+#[macro_export]
+macro_rules! log {
+    ($($arg:tt)*) => {
+        $crate::log::rtt_target::rprintln!($($arg)*)
+    };
+}
+
+/// Logs a line to the sink selected by the log features, defmt by default
+#[cfg(feature = "log-itm")]
+#[macro_export]
+macro_rules! log {
+    ($($arg:tt)*) => {
+        $crate::log::itm_write(format_args!($($arg)*))
+    };
+}
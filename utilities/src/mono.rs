@@ -0,0 +1,80 @@
+//! RTIC monotonic timer on a TIMER peripheral.
+
+use crate::hal::timer::Instance;
+use rtic_monotonic::Monotonic;
+
+/// Timer frequency, the tick resolution is 1 us
+pub const TICK_HZ: u32 = 1_000_000;
+
+/// Point in time, in microseconds since the timer was started
+pub type Instant = fugit::TimerInstantU32<TICK_HZ>;
+/// Time span in microseconds
+pub type Duration = fugit::TimerDurationU32<TICK_HZ>;
+
+/// RTIC monotonic using a 32 bit TIMER running at 1 MHz
+///
+/// The counter wraps around after about 71 minutes. Instants are compared
+/// with wrapping arithmetic, so scheduling works across the wrap as long as
+/// no task is scheduled more than half the range, about 35 minutes, ahead.
+///
+/// Capture/compare register 0 is used for the scheduling, register 1 for
+/// reading the time.
+pub struct TimerMonotonic<T> {
+    timer: T,
+}
+
+impl<T> TimerMonotonic<T>
+where
+    T: Instance,
+{
+    /// Configures the timer as a 32 bit 1 MHz counter, the timer is started
+    /// by RTIC
+    pub fn new(timer: T) -> Self {
+        let regs = timer.as_timer0();
+        regs.tasks_stop.write(|w| unsafe { w.bits(1) });
+        regs.mode.write(|w| w.mode().timer());
+        regs.bitmode.write(|w| w.bitmode()._32bit());
+        // 16 MHz / 2^4 = 1 MHz
+        regs.prescaler.write(|w| unsafe { w.prescaler().bits(4) });
+        Self { timer }
+    }
+
+    /// Return the TIMER peripheral
+    pub fn free(self) -> T {
+        self.timer
+    }
+}
+
+impl<T> Monotonic for TimerMonotonic<T>
+where
+    T: Instance,
+{
+    type Instant = Instant;
+    type Duration = Duration;
+
+    fn now(&mut self) -> Self::Instant {
+        let regs = self.timer.as_timer0();
+        regs.tasks_capture[1].write(|w| unsafe { w.bits(1) });
+        Instant::from_ticks(regs.cc[1].read().bits())
+    }
+
+    fn set_compare(&mut self, instant: Self::Instant) {
+        self.timer.as_timer0().cc[0]
+            .write(|w| unsafe { w.cc().bits(instant.duration_since_epoch().ticks()) });
+    }
+
+    fn clear_compare_flag(&mut self) {
+        self.timer.as_timer0().events_compare[0].write(|w| w);
+    }
+
+    fn zero() -> Self::Instant {
+        Instant::from_ticks(0)
+    }
+
+    unsafe fn reset(&mut self) {
+        let regs = self.timer.as_timer0();
+        regs.intenset.write(|w| w.compare0().set());
+        regs.tasks_clear.write(|w| w.bits(1));
+        regs.tasks_start.write(|w| w.bits(1));
+    }
+}
@@ -0,0 +1,61 @@
+//! Low power idling.
+//!
+//! Busy polling keeps the CPU running at 64 MHz from flash, a few mA. While
+//! sleeping in System ON idle the CPU is stopped and the current drops to
+//! what the running peripherals and clocks draw, a few uA with only the RTC
+//! on LFCLK running. A TIMER keeps HFCLK running, which costs a few hundred
+//! uA more, prefer the RTC for periodic wake-ups. Figures are from the
+//! product specification, measure the actual board with a power profiler.
+//!
+//! Any enabled interrupt wakes the CPU, so state the application waits for,
+//! such as button presses, has to be delivered by an interrupt, for example
+//! GPIOTE, instead of being polled. Use the GPIOTE PORT event for pins, it
+//! runs from the pin SENSE mechanism without any clock. A GPIOTE IN channel
+//! keeps HFCLK running, which draws about as much as a TIMER does.
+//!
+//! TODO: Measure the idle current of the blinky example on the
+//! nRF52833-DK, busy polling against `sleep`, for example with a power
+//! profiler on the nRF current measurement header, and record the figures
+//! here. Until then the figures above are estimates from the product
+//! specification, not measurements.
+
+use crate::hal::pac::{Interrupt, NVIC};
+
+/// FPSCR cumulative exception flags, IOC, DZC, OFC, UFC, IXC and IDC
+const FPSCR_EXCEPTION_FLAGS: u32 = 0x0000_009f;
+
+/// Sleeps until an interrupt is pending
+///
+/// Intended for the `idle` loop, call it repeatedly and check for work
+/// between calls. Interrupts may be disabled, as in a critical section, the
+/// CPU still wakes for pending interrupts but the handler runs only once
+/// interrupts are enabled again.
+///
+/// Pending FPU exceptions wake the CPU immediately, so the FPU exception
+/// flags and the pending FPU interrupt are cleared before sleeping.
+pub fn sleep() {
+    clear_fpu_exceptions();
+    cortex_m::asm::dsb();
+    cortex_m::asm::wfi();
+}
+
+/// Sleeps until an event, or an interrupt when SEVONPEND is set
+///
+/// Returns immediately if an event was registered since the last call, such
+/// as an interrupt occurring while the CPU was awake, so a wake-up between
+/// checking for work and sleeping isn't lost.
+pub fn sleep_until_event() {
+    clear_fpu_exceptions();
+    cortex_m::asm::dsb();
+    cortex_m::asm::wfe();
+}
+
+/// Clears the FPU exception flags, which would otherwise keep the FPU
+/// interrupt pending
+fn clear_fpu_exceptions() {
+    use cortex_m::register::fpscr::{self, Fpscr};
+    let bits = fpscr::read().bits();
+    fpscr::write(Fpscr::from_bits(bits & !FPSCR_EXCEPTION_FLAGS));
+    let _ = fpscr::read();
+    NVIC::unpend(Interrupt::FPU);
+}
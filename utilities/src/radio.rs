@@ -0,0 +1,893 @@
+//! Helpers around the psila IEEE 802.15.4 radio driver.
+//!
+//! The radio owns the frame check sequence. `queue_transmission` takes a
+//! frame without FCS, sets the PHY length to the frame length plus
+//! `FCS_SIZE` and the radio appends the FCS while sending. Received packets
+//! end with the FCS, which the radio has already checked, see
+//! `Radio::crc_ok`.
+
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::hal::pac;
+use crate::rng::Rng;
+
+/// Size of the frame check sequence
+pub const FCS_SIZE: usize = 2;
+
+/// Errors found when validating received frames
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RadioError {
+    /// The frame is shorter than its MAC header and frame check sequence
+    TruncatedFrame,
+    /// The frame uses the reserved addressing mode
+    InvalidAddressing,
+    /// There is no room for the frame check sequence in the buffer
+    BufferTooShort,
+}
+
+/// Calculates the IEEE 802.15.4 frame check sequence
+///
+/// The FCS is the ITU-T CRC-16 with the polynomial x^16 + x^12 + x^5 + 1,
+/// computed least significant bit first with a zero initial value. It is
+/// sent least significant byte first, see `append_fcs`.
+pub fn fcs(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for byte in data {
+        crc ^= u16::from(*byte);
+        for _ in 0..8 {
+            if crc & 1 == 1 {
+                crc = (crc >> 1) ^ 0x8408;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    crc
+}
+
+/// Checks the frame check sequence at the end of `frame`
+pub fn check_fcs(frame: &[u8]) -> bool {
+    if frame.len() < FCS_SIZE {
+        return false;
+    }
+    let (data, sequence) = frame.split_at(frame.len() - FCS_SIZE);
+    fcs(data) == u16::from_le_bytes([sequence[0], sequence[1]])
+}
+
+/// Writes the frame check sequence of `frame[..length]` after it and
+/// returns the length including the FCS
+///
+/// For frames passed on to something other than the radio, such as a
+/// capture file. Frames queued for transmission must not carry an FCS.
+pub fn append_fcs(frame: &mut [u8], length: usize) -> Result<usize, RadioError> {
+    if length + FCS_SIZE > frame.len() {
+        return Err(RadioError::BufferTooShort);
+    }
+    let sequence = fcs(&frame[..length]).to_le_bytes();
+    frame[length..length + FCS_SIZE].copy_from_slice(&sequence);
+    Ok(length + FCS_SIZE)
+}
+
+/// Removes a valid frame check sequence from the end of `frame`
+///
+/// Use this on frames from the host which may or may not carry an FCS,
+/// before queueing them for transmission. A frame without FCS whose last
+/// two octets happen to form a valid one is cut short, the chance is 1 in
+/// 65536.
+pub fn strip_fcs(frame: &[u8]) -> &[u8] {
+    if check_fcs(frame) {
+        &frame[..frame.len() - FCS_SIZE]
+    } else {
+        frame
+    }
+}
+
+/// Size of an address for the frame control address mode
+fn address_size(mode: u16) -> Result<usize, RadioError> {
+    match mode {
+        0b00 => Ok(0),
+        0b10 => Ok(2),
+        0b11 => Ok(8),
+        _ => Err(RadioError::InvalidAddressing),
+    }
+}
+
+/// Calculates the size of the MAC header from the frame control field
+pub fn mac_header_size(frame: &[u8]) -> Result<usize, RadioError> {
+    if frame.len() < 2 {
+        return Err(RadioError::TruncatedFrame);
+    }
+    let frame_control = u16::from_le_bytes([frame[0], frame[1]]);
+    let pan_id_compression = frame_control & 0x0040 == 0x0040;
+    let sequence_suppression = frame_control & 0x0100 == 0x0100;
+    let destination_mode = (frame_control >> 10) & 0b11;
+    let version = (frame_control >> 12) & 0b11;
+    let source_mode = (frame_control >> 14) & 0b11;
+
+    let mut size = 2;
+    // Sequence number suppression is only available in 802.15.4-2015 frames
+    if !(version == 0b10 && sequence_suppression) {
+        size += 1;
+    }
+    let destination_size = address_size(destination_mode)?;
+    if destination_size > 0 {
+        size += 2 + destination_size;
+    }
+    let source_size = address_size(source_mode)?;
+    if source_size > 0 {
+        if !pan_id_compression {
+            size += 2;
+        }
+        size += source_size;
+    }
+    Ok(size)
+}
+
+/// Validates a received packet, a length octet followed by the frame
+///
+/// Checks that the length is within the packet and that the frame is long
+/// enough to hold the MAC header and the frame check sequence.
+pub fn validate_frame(packet: &[u8]) -> Result<(), RadioError> {
+    let length = match packet.first() {
+        Some(length) => *length as usize,
+        None => return Err(RadioError::TruncatedFrame),
+    };
+    if length == 0 || length > packet.len() {
+        return Err(RadioError::TruncatedFrame);
+    }
+    let frame = &packet[1..length];
+    if frame.len() < mac_header_size(frame)? + FCS_SIZE {
+        return Err(RadioError::TruncatedFrame);
+    }
+    Ok(())
+}
+
+/// Length of a beacon request frame, without the frame check sequence
+pub const BEACON_REQUEST_LENGTH: usize = 8;
+
+/// Broadcast PAN identifier and short address
+pub const BROADCAST: u16 = 0xffff;
+
+/// MAC command identifier of the beacon request
+const BEACON_REQUEST_COMMAND: u8 = 0x07;
+
+/// Base superframe duration, aBaseSuperframeDuration, in microseconds
+pub const BASE_SUPERFRAME_DURATION_MICROS: u32 = 960 * 16;
+
+/// Time to listen on each channel for beacons during a scan
+///
+/// The scan duration is aBaseSuperframeDuration * (2^exponent + 1), an
+/// exponent of 3 gives about 138 ms per channel. The exponent is at most 14.
+pub fn scan_duration_micros(exponent: u8) -> u32 {
+    BASE_SUPERFRAME_DURATION_MICROS * ((1 << exponent.min(14)) + 1)
+}
+
+/// Builds a beacon request MAC command frame in `frame`
+///
+/// The request is broadcast to all PANs, every coordinator and router
+/// hearing it answers with a beacon. Returns the frame length, without the
+/// frame check sequence. Pass the frame to `queue_transmission`.
+pub fn beacon_request(frame: &mut [u8], sequence: u8) -> Result<usize, RadioError> {
+    if frame.len() < BEACON_REQUEST_LENGTH {
+        return Err(RadioError::BufferTooShort);
+    }
+    // MAC command, short destination address, no source address
+    let frame_control: u16 = 0b011 | 0b10 << 10;
+    frame[..2].copy_from_slice(&frame_control.to_le_bytes());
+    frame[2] = sequence;
+    frame[3..5].copy_from_slice(&BROADCAST.to_le_bytes());
+    frame[5..7].copy_from_slice(&BROADCAST.to_le_bytes());
+    frame[7] = BEACON_REQUEST_COMMAND;
+    Ok(BEACON_REQUEST_LENGTH)
+}
+
+/// Address of the device sending a beacon
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum Address {
+    /// 16 bit short address
+    Short(u16),
+    /// 64 bit extended address
+    Extended(u64),
+}
+
+/// Zigbee network information carried in the beacon payload
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct ZigbeeBeacon {
+    /// Stack profile, 1 for Zigbee, 2 for Zigbee PRO
+    pub stack_profile: u8,
+    /// Network protocol version
+    pub protocol_version: u8,
+    /// The device accepts routers joining it
+    pub router_capacity: bool,
+    /// Depth of the device in the network, the coordinator is 0
+    pub depth: u8,
+    /// The device accepts end devices joining it
+    pub end_device_capacity: bool,
+    /// Extended PAN identifier of the network
+    pub extended_pan_id: u64,
+}
+
+/// Network found by a beacon
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct Beacon {
+    /// PAN identifier
+    pub pan_id: u16,
+    /// Address of the sender
+    pub address: Address,
+    /// The sender is the PAN coordinator
+    pub pan_coordinator: bool,
+    /// Association permit, the network accepts joining devices
+    pub permit_join: bool,
+    /// First octet of the beacon payload, 0 for Zigbee, `None` when the
+    /// payload is empty
+    pub protocol_id: Option<u8>,
+    /// Zigbee network information, when the protocol identifier is 0
+    pub zigbee: Option<ZigbeeBeacon>,
+}
+
+/// Length of the Zigbee beacon payload
+const ZIGBEE_BEACON_LENGTH: usize = 15;
+
+/// Parses a received packet, a length octet followed by the frame, as a
+/// beacon
+///
+/// Returns `None` for frames other than beacons, so this can be used to
+/// filter the frames received during a scan. Beacons that are cut short are
+/// reported as `TruncatedFrame`.
+pub fn parse_beacon(packet: &[u8]) -> Result<Option<Beacon>, RadioError> {
+    validate_frame(packet)?;
+    let frame = &packet[1..packet[0] as usize];
+    let frame_control = u16::from_le_bytes([frame[0], frame[1]]);
+    if frame_control & 0b111 != 0b000 {
+        return Ok(None);
+    }
+    let header_size = mac_header_size(frame)?;
+    let payload = &frame[header_size..frame.len() - FCS_SIZE];
+
+    // Beacons carry no destination, the source PAN identifier is followed
+    // by the source address
+    let destination_mode = (frame_control >> 10) & 0b11;
+    let source_mode = (frame_control >> 14) & 0b11;
+    if destination_mode != 0b00 || source_mode == 0b00 {
+        return Err(RadioError::InvalidAddressing);
+    }
+    let source = &frame[header_size - 2 - address_size(source_mode)?..header_size];
+    let pan_id = u16::from_le_bytes([source[0], source[1]]);
+    let address = if source_mode == 0b10 {
+        Address::Short(u16::from_le_bytes([source[2], source[3]]))
+    } else {
+        let mut octets = [0u8; 8];
+        octets.copy_from_slice(&source[2..10]);
+        Address::Extended(u64::from_le_bytes(octets))
+    };
+
+    // Superframe specification, GTS fields and pending addresses
+    if payload.len() < 4 {
+        return Err(RadioError::TruncatedFrame);
+    }
+    let superframe = u16::from_le_bytes([payload[0], payload[1]]);
+    let mut offset = 3;
+    let gts_count = usize::from(payload[2] & 0b111);
+    if gts_count > 0 {
+        offset += 1 + 3 * gts_count;
+    }
+    let pending = *payload.get(offset).ok_or(RadioError::TruncatedFrame)?;
+    offset += 1 + 2 * usize::from(pending & 0b111) + 8 * usize::from((pending >> 4) & 0b111);
+    let beacon_payload = payload.get(offset..).ok_or(RadioError::TruncatedFrame)?;
+
+    let protocol_id = beacon_payload.first().copied();
+    let zigbee = match protocol_id {
+        Some(0) if beacon_payload.len() >= ZIGBEE_BEACON_LENGTH => {
+            let mut extended_pan_id = [0u8; 8];
+            extended_pan_id.copy_from_slice(&beacon_payload[3..11]);
+            Some(ZigbeeBeacon {
+                stack_profile: beacon_payload[1] & 0x0f,
+                protocol_version: beacon_payload[1] >> 4,
+                router_capacity: beacon_payload[2] & 0x04 == 0x04,
+                depth: (beacon_payload[2] >> 3) & 0x0f,
+                end_device_capacity: beacon_payload[2] & 0x80 == 0x80,
+                extended_pan_id: u64::from_le_bytes(extended_pan_id),
+            })
+        }
+        _ => None,
+    };
+
+    Ok(Some(Beacon {
+        pan_id,
+        address,
+        pan_coordinator: superframe & 0x4000 == 0x4000,
+        permit_join: superframe & 0x8000 == 0x8000,
+        protocol_id,
+        zigbee,
+    }))
+}
+
+/// Time to wait for a response to a MAC command, macResponseWaitTime, 32
+/// base superframe durations or about 492 ms
+pub const RESPONSE_WAIT_MICROS: u32 = 32 * BASE_SUPERFRAME_DURATION_MICROS;
+
+/// Capability, the device is a full function device, such as a router
+pub const CAPABILITY_FFD: u8 = 0x02;
+/// Capability, the device is mains powered
+pub const CAPABILITY_MAINS_POWERED: u8 = 0x04;
+/// Capability, the receiver is on when the device is idle
+pub const CAPABILITY_RX_ON_WHEN_IDLE: u8 = 0x08;
+/// Capability, the coordinator shall allocate a short address
+pub const CAPABILITY_ALLOCATE_ADDRESS: u8 = 0x80;
+
+/// MAC command identifier of the association request
+const ASSOCIATION_REQUEST_COMMAND: u8 = 0x01;
+/// MAC command identifier of the association response
+const ASSOCIATION_RESPONSE_COMMAND: u8 = 0x02;
+/// MAC command identifier of the data request
+const DATA_REQUEST_COMMAND: u8 = 0x04;
+
+/// Writes a MAC command frame from `source`, requesting an acknowledgement
+///
+/// Without a source PAN identifier the PAN identifier is compressed, the
+/// source belongs to the destination PAN. Returns the frame length,
+/// without the frame check sequence.
+fn command_frame(
+    frame: &mut [u8],
+    sequence: u8,
+    pan_id: u16,
+    destination: Address,
+    source_pan_id: Option<u16>,
+    source: u64,
+    payload: &[u8],
+) -> Result<usize, RadioError> {
+    let (destination_mode, destination_size) = match destination {
+        Address::Short(_) => (0b10, 2),
+        Address::Extended(_) => (0b11, 8),
+    };
+    let source_pan_size = if source_pan_id.is_some() { 2 } else { 0 };
+    let length = 5 + destination_size + source_pan_size + 8 + payload.len();
+    if frame.len() < length {
+        return Err(RadioError::BufferTooShort);
+    }
+    // MAC command, acknowledge request, extended source address
+    let mut frame_control: u16 = 0b011 | 0x0020 | destination_mode << 10 | 0b11 << 14;
+    if source_pan_id.is_none() {
+        frame_control |= 0x0040;
+    }
+    frame[..2].copy_from_slice(&frame_control.to_le_bytes());
+    frame[2] = sequence;
+    frame[3..5].copy_from_slice(&pan_id.to_le_bytes());
+    let mut offset = 5;
+    match destination {
+        Address::Short(address) => {
+            frame[offset..offset + 2].copy_from_slice(&address.to_le_bytes())
+        }
+        Address::Extended(address) => {
+            frame[offset..offset + 8].copy_from_slice(&address.to_le_bytes())
+        }
+    }
+    offset += destination_size;
+    if let Some(source_pan_id) = source_pan_id {
+        frame[offset..offset + 2].copy_from_slice(&source_pan_id.to_le_bytes());
+        offset += 2;
+    }
+    frame[offset..offset + 8].copy_from_slice(&source.to_le_bytes());
+    offset += 8;
+    frame[offset..offset + payload.len()].copy_from_slice(payload);
+    Ok(length)
+}
+
+/// Builds an association request to `coordinator` in `pan_id`
+///
+/// `capability` is a combination of the `CAPABILITY_` flags. The device has
+/// no PAN yet, so the request is sent from the broadcast PAN with the
+/// extended address. The response isn't sent directly, send a data request
+/// after `RESPONSE_WAIT_MICROS` to fetch it. Returns the frame length,
+/// without the frame check sequence.
+pub fn association_request(
+    frame: &mut [u8],
+    sequence: u8,
+    pan_id: u16,
+    coordinator: Address,
+    extended_address: u64,
+    capability: u8,
+) -> Result<usize, RadioError> {
+    command_frame(
+        frame,
+        sequence,
+        pan_id,
+        coordinator,
+        Some(BROADCAST),
+        extended_address,
+        &[ASSOCIATION_REQUEST_COMMAND, capability],
+    )
+}
+
+/// Builds a data request, polling `coordinator` for pending frames
+///
+/// Returns the frame length, without the frame check sequence.
+pub fn data_request(
+    frame: &mut [u8],
+    sequence: u8,
+    pan_id: u16,
+    coordinator: Address,
+    extended_address: u64,
+) -> Result<usize, RadioError> {
+    command_frame(
+        frame,
+        sequence,
+        pan_id,
+        coordinator,
+        None,
+        extended_address,
+        &[DATA_REQUEST_COMMAND],
+    )
+}
+
+/// Destination PAN identifier and address of a received packet, a length
+/// octet followed by the frame
+fn destination(packet: &[u8]) -> Result<Option<(u16, Address)>, RadioError> {
+    validate_frame(packet)?;
+    let frame_control = u16::from_le_bytes([packet[1], packet[2]]);
+    let version = (frame_control >> 12) & 0b11;
+    let sequence_size = if version == 0b10 && frame_control & 0x0100 == 0x0100 {
+        0
+    } else {
+        1
+    };
+    // Skip the frame control and the sequence number
+    let offset = 1 + 2 + sequence_size;
+    let pan_id = u16::from_le_bytes([packet[offset], packet[offset + 1]]);
+    let offset = offset + 2;
+    let address = match (frame_control >> 10) & 0b11 {
+        0b10 => Address::Short(u16::from_le_bytes([packet[offset], packet[offset + 1]])),
+        0b11 => {
+            let mut octets = [0u8; 8];
+            octets.copy_from_slice(&packet[offset..offset + 8]);
+            Address::Extended(u64::from_le_bytes(octets))
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some((pan_id, address)))
+}
+
+/// Destination address of a received packet, a length octet followed by
+/// the frame
+pub fn destination_address(packet: &[u8]) -> Result<Option<Address>, RadioError> {
+    Ok(destination(packet)?.map(|(_, address)| address))
+}
+
+/// MAC payload of a received packet, a length octet followed by the frame
+///
+/// The payload follows the MAC header and ends before the frame check
+/// sequence.
+pub fn mac_payload(packet: &[u8]) -> Result<&[u8], RadioError> {
+    validate_frame(packet)?;
+    let frame = &packet[1..packet[0] as usize];
+    let header_size = mac_header_size(frame)?;
+    Ok(&frame[header_size..frame.len() - FCS_SIZE])
+}
+
+/// Outcome of an association request
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum AssociationStatus {
+    /// The device is associated
+    Success,
+    /// The coordinator has no room for more devices
+    PanAtCapacity,
+    /// The coordinator doesn't let the device join
+    AccessDenied,
+    /// Any other, reserved, status
+    Other(u8),
+}
+
+impl From<u8> for AssociationStatus {
+    fn from(value: u8) -> Self {
+        match value {
+            0x00 => AssociationStatus::Success,
+            0x01 => AssociationStatus::PanAtCapacity,
+            0x02 => AssociationStatus::AccessDenied,
+            _ => AssociationStatus::Other(value),
+        }
+    }
+}
+
+/// Association response from the coordinator
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct AssociationResponse {
+    /// Short address allocated to the device, 0xfffe when the device shall
+    /// use its extended address
+    pub short_address: u16,
+    /// Whether the association succeeded
+    pub status: AssociationStatus,
+}
+
+/// Parses a received packet, a length octet followed by the frame, as an
+/// association response to `extended_address`
+///
+/// Returns `None` for any other frame, including responses sent to other
+/// devices.
+pub fn parse_association_response(
+    packet: &[u8],
+    extended_address: u64,
+) -> Result<Option<AssociationResponse>, RadioError> {
+    validate_frame(packet)?;
+    if packet[1] & 0b111 != 0b011 {
+        return Ok(None);
+    }
+    if destination_address(packet)? != Some(Address::Extended(extended_address)) {
+        return Ok(None);
+    }
+    let payload = mac_payload(packet)?;
+    if payload.first() != Some(&ASSOCIATION_RESPONSE_COMMAND) {
+        return Ok(None);
+    }
+    if payload.len() < 4 {
+        return Err(RadioError::TruncatedFrame);
+    }
+    Ok(Some(AssociationResponse {
+        short_address: u16::from_le_bytes([payload[1], payload[2]]),
+        status: AssociationStatus::from(payload[3]),
+    }))
+}
+
+/// Minimum backoff exponent, macMinBE
+pub const MIN_BACKOFF_EXPONENT: u8 = 3;
+/// Maximum backoff exponent, macMaxBE
+pub const MAX_BACKOFF_EXPONENT: u8 = 5;
+/// Number of backoffs before giving up, macMaxCSMABackoffs
+pub const MAX_CSMA_BACKOFFS: u8 = 4;
+/// The unit backoff period, 20 symbols or 320 us, in 32.768 kHz RTC ticks
+/// rounded up
+pub const BACKOFF_PERIOD_TICKS: u32 = 11;
+
+/// CSMA-CA random exponential backoff
+///
+/// Call `next_delay` before each clear channel assessment, wait the given
+/// number of RTC ticks and then try to transmit. When the channel is busy,
+/// call `next_delay` again, the backoff window doubles for each attempt up to
+/// the maximum backoff exponent. `None` is returned when the maximum number
+/// of backoffs has been reached, the transmission has failed with a channel
+/// access failure. Call `reset` before starting a new transmission.
+pub struct Backoff {
+    /// Number of backoffs done, NB
+    backoffs: u8,
+    /// Backoff exponent, BE
+    exponent: u8,
+    /// Pseudo random state
+    random: u32,
+}
+
+impl Backoff {
+    /// Create a backoff with the given random seed
+    ///
+    /// Devices sharing a channel must use different seeds, use `from_rng`
+    /// or `from_ficr`.
+    pub fn new(seed: u32) -> Self {
+        Self {
+            backoffs: 0,
+            exponent: MIN_BACKOFF_EXPONENT,
+            // The xorshift state must never be zero
+            random: if seed == 0 { 0x2545_f491 } else { seed },
+        }
+    }
+
+    /// Create a backoff seeded from the hardware random number generator
+    pub fn from_rng(rng: &mut Rng) -> Self {
+        Self::new(rng.u32())
+    }
+
+    /// Create a backoff seeded from the device identifier in FICR
+    pub fn from_ficr(ficr: &pac::FICR) -> Self {
+        let seed = ficr.deviceid[0].read().bits() ^ ficr.deviceid[1].read().bits();
+        Self::new(seed)
+    }
+
+    /// Restart the backoff for a new transmission
+    pub fn reset(&mut self) {
+        self.backoffs = 0;
+        self.exponent = MIN_BACKOFF_EXPONENT;
+    }
+
+    /// Number of backoffs done since the last reset
+    pub fn backoffs(&self) -> u8 {
+        self.backoffs
+    }
+
+    /// Get the number of RTC ticks to wait before the next clear channel
+    /// assessment, `None` when the maximum number of backoffs is reached
+    ///
+    /// The delay is a random number of backoff periods in the range 0 to
+    /// 2^BE - 1.
+    pub fn next_delay(&mut self) -> Option<u32> {
+        if self.backoffs > MAX_CSMA_BACKOFFS {
+            return None;
+        }
+        let periods = self.next_random() & ((1 << self.exponent) - 1);
+        self.backoffs += 1;
+        self.exponent = (self.exponent + 1).min(MAX_BACKOFF_EXPONENT);
+        Some(periods * BACKOFF_PERIOD_TICKS)
+    }
+
+    /// Xorshift32 pseudo random number
+    fn next_random(&mut self) -> u32 {
+        let mut x = self.random;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.random = x;
+        x
+    }
+}
+
+/// Running packet counters
+///
+/// The counters are atomic so that a single `static` instance can be updated
+/// from the radio interrupt and read from a lower priority task without a
+/// lock. Counters wrap around on overflow.
+pub struct Stats {
+    received: AtomicU32,
+    transmitted: AtomicU32,
+    dropped: AtomicU32,
+    cca_busy: AtomicU32,
+    malformed: AtomicU32,
+}
+
+/// Counter values read from `Stats`
+#[derive(Clone, Copy, Debug, Default, PartialEq, defmt::Format)]
+pub struct StatsSnapshot {
+    /// Frames received
+    pub received: u32,
+    /// Frames the radio accepted for transmission
+    pub transmitted: u32,
+    /// Frames dropped as there was no room in the queue
+    pub dropped: u32,
+    /// Transmissions failed because the channel was busy
+    pub cca_busy: u32,
+    /// Frames dropped as they failed validation
+    pub malformed: u32,
+}
+
+impl Stats {
+    /// Create zeroed counters, usable in a `static`
+    pub const fn new() -> Self {
+        Self {
+            received: AtomicU32::new(0),
+            transmitted: AtomicU32::new(0),
+            dropped: AtomicU32::new(0),
+            cca_busy: AtomicU32::new(0),
+            malformed: AtomicU32::new(0),
+        }
+    }
+
+    /// Count a received frame
+    pub fn received(&self) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a frame the radio accepted for transmission
+    ///
+    /// Call it only when queueing the frame succeeded.
+    pub fn transmitted(&self) {
+        self.transmitted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a frame dropped for lack of buffer space
+    pub fn dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a busy channel
+    pub fn cca_busy(&self) {
+        self.cca_busy.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a malformed frame
+    pub fn malformed(&self) {
+        self.malformed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Read the counters
+    ///
+    /// Each counter is read on its own, a counter updated from an interrupt
+    /// while reading might not be consistent with the others.
+    pub fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            received: self.received.load(Ordering::Relaxed),
+            transmitted: self.transmitted.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            cca_busy: self.cca_busy.load(Ordering::Relaxed),
+            malformed: self.malformed.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Read and clear the counters
+    pub fn reset(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            received: self.received.swap(0, Ordering::Relaxed),
+            transmitted: self.transmitted.swap(0, Ordering::Relaxed),
+            dropped: self.dropped.swap(0, Ordering::Relaxed),
+            cca_busy: self.cca_busy.swap(0, Ordering::Relaxed),
+            malformed: self.malformed.swap(0, Ordering::Relaxed),
+        }
+    }
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// IEEE 802.15.4 radio with power management.
+///
+/// Wraps the psila radio driver and remembers the channel and transmission
+/// power so that they can be reapplied when the radio is powered up again.
+pub struct Radio {
+    radio: psila_nrf52::radio::Radio,
+    channel: u8,
+    power: i8,
+    enabled: bool,
+    /// PAN identifier and short address accepted by `accepts`
+    address: Option<(u16, u16)>,
+    /// Extended address accepted by `accepts`
+    extended_address: Option<u64>,
+}
+
+impl Radio {
+    /// Creates a new radio, the radio is enabled but not receiving.
+    pub fn new(radio: pac::RADIO) -> Self {
+        Self {
+            radio: psila_nrf52::radio::Radio::new(radio),
+            channel: 11,
+            power: 0,
+            enabled: true,
+            address: None,
+            extended_address: None,
+        }
+    }
+
+    /// Sets the channel, 11 to 26
+    pub fn set_channel(&mut self, channel: u8) {
+        self.channel = channel;
+        self.radio.set_channel(channel);
+    }
+
+    /// Get the configured channel
+    pub fn channel(&self) -> u8 {
+        self.channel
+    }
+
+    /// Sets the transmission power in dBm
+    pub fn set_transmission_power(&mut self, power: i8) {
+        self.power = power;
+        self.radio.set_transmission_power(power);
+    }
+
+    /// Get the configured transmission power in dBm
+    pub fn transmission_power(&self) -> i8 {
+        self.power
+    }
+
+    /// Sets the PAN identifier and short address of the device, as
+    /// assigned when associating
+    ///
+    /// The radio has no IEEE 802.15.4 address filter, received packets are
+    /// filtered in software with `accepts`.
+    pub fn set_address(&mut self, pan_id: u16, short_address: u16) {
+        self.address = Some((pan_id, short_address));
+    }
+
+    /// Forgets the PAN identifier and short address, for example when
+    /// leaving the network
+    pub fn clear_address(&mut self) {
+        self.address = None;
+    }
+
+    /// Sets the extended address of the device
+    pub fn set_extended_address(&mut self, extended_address: u64) {
+        self.extended_address = Some(extended_address);
+    }
+
+    /// Is a received packet, a length octet followed by the frame,
+    /// addressed to this device?
+    ///
+    /// Frames to the broadcast PAN and short address and frames without a
+    /// destination address, such as beacons, are accepted. Until an address
+    /// is set, frames to any PAN and short address or any extended address
+    /// are accepted.
+    pub fn accepts(&self, packet: &[u8]) -> bool {
+        let (pan_id, address) = match destination(packet) {
+            Ok(Some(destination)) => destination,
+            Ok(None) => return true,
+            Err(_) => return false,
+        };
+        match address {
+            Address::Short(short_address) => match self.address {
+                Some((own_pan_id, own_address)) => {
+                    (pan_id == own_pan_id || pan_id == BROADCAST)
+                        && (short_address == own_address || short_address == BROADCAST)
+                }
+                None => true,
+            },
+            Address::Extended(extended_address) => self
+                .extended_address
+                .map_or(true, |own_address| own_address == extended_address),
+        }
+    }
+
+    /// Did the hardware find the frame check sequence of the last received
+    /// packet valid?
+    ///
+    /// The radio checks the FCS of every received packet, when this is true
+    /// there is no need to check it again with `check_fcs`. On transmit the
+    /// radio appends the FCS, see the module documentation.
+    pub fn crc_ok(&self) -> bool {
+        // The register block is owned by the inner driver, this only reads
+        let radio = unsafe { &*pac::RADIO::ptr() };
+        radio.crcstatus.read().crcstatus().is_crcok()
+    }
+
+    /// Is the radio powered up?
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Stops any ongoing operation and powers down the radio peripheral.
+    ///
+    /// Powering down the peripheral resets all its registers, the
+    /// configuration is lost until `enable` is called.
+    pub fn disable(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        // The register block is owned by the inner driver which has no way
+        // to power down the peripheral
+        let radio = unsafe { &*pac::RADIO::ptr() };
+        // DISABLED isn't signalled again when the radio already is disabled,
+        // as it is after `new` and `enable`
+        if !radio.state.read().state().is_disabled() {
+            radio.events_disabled.reset();
+            radio.tasks_disable.write(|w| unsafe { w.bits(1) });
+            while radio.events_disabled.read().bits() == 0 {}
+            radio.events_disabled.reset();
+        }
+        radio.power.write(|w| w.power().disabled());
+        self.enabled = false;
+    }
+
+    /// Powers up the radio peripheral and reconfigures it.
+    ///
+    /// The channel and transmission power last set through this wrapper are
+    /// reapplied, if those were changed on the inner driver directly they
+    /// have to be set again after enabling. The radio is left idle, call
+    /// `receive_prepare` to start receiving.
+    ///
+    /// The high frequency crystal oscillator must be running. The radio
+    /// needs about 40 us (fast ramp-up) to 140 us to warm up before it can
+    /// receive or transmit.
+    pub fn enable(&mut self) {
+        if self.enabled {
+            return;
+        }
+        let radio = unsafe { &*pac::RADIO::ptr() };
+        radio.power.write(|w| w.power().enabled());
+        // The peripheral has been reset, configure it from scratch
+        let peripheral = unsafe { pac::Peripherals::steal() }.RADIO;
+        self.radio = psila_nrf52::radio::Radio::new(peripheral);
+        self.radio.set_channel(self.channel);
+        self.radio.set_transmission_power(self.power);
+        self.enabled = true;
+    }
+}
+
+impl Deref for Radio {
+    type Target = psila_nrf52::radio::Radio;
+
+    fn deref(&self) -> &Self::Target {
+        &self.radio
+    }
+}
+
+impl DerefMut for Radio {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.radio
+    }
+}
@@ -0,0 +1,179 @@
+//! Line editing and command dispatch for a debug console.
+
+use heapless::Vec;
+
+/// Command errors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplError {
+    /// The line is empty
+    Empty,
+    /// No command with the given name is registered
+    UnknownCommand,
+    /// A required argument is missing
+    MissingArgument,
+    /// An argument couldn't be parsed or is out of range
+    InvalidArgument,
+    /// There are arguments left after the command was handled
+    TooManyArguments,
+}
+
+/// Iterator over the whitespace separated tokens of a line
+#[derive(Clone)]
+pub struct Tokens<'a> {
+    rest: &'a [u8],
+}
+
+/// Splits a line into whitespace separated tokens
+pub fn tokens(line: &[u8]) -> Tokens<'_> {
+    Tokens { rest: line }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.rest.iter().position(|b| !b.is_ascii_whitespace())?;
+        let rest = &self.rest[start..];
+        let end = rest
+            .iter()
+            .position(|b| b.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        self.rest = &rest[end..];
+        Some(&rest[..end])
+    }
+}
+
+/// Command arguments
+pub struct Args<'a> {
+    tokens: Tokens<'a>,
+}
+
+impl<'a> Args<'a> {
+    /// Next argument as text
+    pub fn next_str(&mut self) -> Result<&'a str, ReplError> {
+        let token = self.tokens.next().ok_or(ReplError::MissingArgument)?;
+        core::str::from_utf8(token).map_err(|_| ReplError::InvalidArgument)
+    }
+
+    /// Next argument as a signed decimal number
+    pub fn next_i32(&mut self) -> Result<i32, ReplError> {
+        self.next_str()?
+            .parse()
+            .map_err(|_| ReplError::InvalidArgument)
+    }
+
+    /// Next argument as an unsigned decimal number
+    pub fn next_u32(&mut self) -> Result<u32, ReplError> {
+        self.next_str()?
+            .parse()
+            .map_err(|_| ReplError::InvalidArgument)
+    }
+
+    /// Fails if there are arguments left
+    pub fn finish(&mut self) -> Result<(), ReplError> {
+        match self.tokens.next() {
+            Some(_) => Err(ReplError::TooManyArguments),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Command handler, called with the console context and the arguments
+/// following the command name
+pub type Handler<C> = fn(&mut C, &mut Args) -> Result<(), ReplError>;
+
+/// Registered command
+pub struct Command<C> {
+    /// Name typed to run the command
+    pub name: &'static str,
+    /// One line description
+    pub help: &'static str,
+    /// Handler
+    pub handler: Handler<C>,
+}
+
+/// Result of feeding a byte to the line editor, tells the caller what to
+/// echo
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Input {
+    /// The byte was added to the line, echo it
+    Stored(u8),
+    /// The last byte was erased, echo backspace, space, backspace
+    Erased,
+    /// The line is complete, echo a line break and call `dispatch`
+    Line,
+    /// The byte was dropped, the line is full or the byte isn't printable
+    Ignored,
+}
+
+/// Line editor holding up to `N` bytes
+pub struct Repl<const N: usize> {
+    line: Vec<u8, N>,
+}
+
+impl<const N: usize> Repl<N> {
+    /// Creates an editor with an empty line
+    pub fn new() -> Self {
+        Self { line: Vec::new() }
+    }
+
+    /// Feeds a received byte to the editor
+    ///
+    /// Newline or carriage return completes the line, backspace and delete
+    /// erase the last byte. Bytes beyond `N` are dropped.
+    pub fn feed(&mut self, byte: u8) -> Input {
+        match byte {
+            b'\n' | b'\r' => Input::Line,
+            0x08 | 0x7f => match self.line.pop() {
+                Some(_) => Input::Erased,
+                None => Input::Ignored,
+            },
+            b' '..=b'~' => match self.line.push(byte) {
+                Ok(()) => Input::Stored(byte),
+                Err(_) => Input::Ignored,
+            },
+            _ => Input::Ignored,
+        }
+    }
+
+    /// The line entered so far
+    pub fn line(&self) -> &[u8] {
+        &self.line
+    }
+
+    /// Discards the line
+    pub fn clear(&mut self) {
+        self.line.clear();
+    }
+
+    /// Runs the command on the line and clears it
+    ///
+    /// The first token selects the command from `commands`, the handler is
+    /// called with the remaining tokens.
+    pub fn dispatch<C>(
+        &mut self,
+        context: &mut C,
+        commands: &[Command<C>],
+    ) -> Result<(), ReplError> {
+        let result = run(&self.line, context, commands);
+        self.line.clear();
+        result
+    }
+}
+
+impl<const N: usize> Default for Repl<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs the command on `line`
+pub fn run<C>(line: &[u8], context: &mut C, commands: &[Command<C>]) -> Result<(), ReplError> {
+    let mut tokens = tokens(line);
+    let name = tokens.next().ok_or(ReplError::Empty)?;
+    let command = commands
+        .iter()
+        .find(|c| c.name.as_bytes() == name)
+        .ok_or(ReplError::UnknownCommand)?;
+    (command.handler)(context, &mut Args { tokens })
+}
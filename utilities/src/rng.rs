@@ -0,0 +1,47 @@
+//! Hardware random number generator.
+
+use crate::hal::pac;
+
+/// Random number generator using the RNG peripheral
+///
+/// The RNG is a true random number generator, the numbers are generated from
+/// thermal noise. Bias correction is enabled, which makes the numbers
+/// uniformly distributed but generation slower, around 120 us per byte.
+///
+/// Use it to seed pseudo random generators, such as the CSMA-CA backoff, or
+/// to create nonces and keys.
+pub struct Rng {
+    rng: pac::RNG,
+}
+
+impl Rng {
+    /// Creates a new RNG with bias correction enabled
+    pub fn new(rng: pac::RNG) -> Self {
+        rng.config.write(|w| w.dercen().enabled());
+        Self { rng }
+    }
+
+    /// Fills `buffer` with random bytes
+    pub fn fill(&mut self, buffer: &mut [u8]) {
+        self.rng.events_valrdy.reset();
+        self.rng.tasks_start.write(|w| unsafe { w.bits(1) });
+        for byte in buffer.iter_mut() {
+            while self.rng.events_valrdy.read().bits() == 0 {}
+            self.rng.events_valrdy.reset();
+            *byte = self.rng.value.read().value().bits();
+        }
+        self.rng.tasks_stop.write(|w| unsafe { w.bits(1) });
+    }
+
+    /// Get a random `u32`
+    pub fn u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.fill(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    /// Return the RNG peripheral
+    pub fn free(self) -> pac::RNG {
+        self.rng
+    }
+}
@@ -0,0 +1,112 @@
+//! Rotation of drawing done in software.
+
+use embedded_graphics::{
+    drawable::Pixel,
+    geometry::{Point, Size},
+    pixelcolor::PixelColor,
+    DrawTarget,
+};
+
+/// Clockwise rotation
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rotation {
+    Rotate0,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl Rotation {
+    /// Does the rotation swap width and height?
+    pub fn is_transposed(&self) -> bool {
+        matches!(self, Rotation::Rotate90 | Rotation::Rotate270)
+    }
+}
+
+/// Draw target that rotates pixel coordinates before passing them on
+///
+/// Unlike `ST7735::set_orientation` this doesn't touch the display
+/// configuration, use it with panels that handle MADCTL poorly or with any
+/// other `DrawTarget`.
+pub struct RotatedDrawTarget<D> {
+    inner: D,
+    rotation: Rotation,
+}
+
+impl<D> RotatedDrawTarget<D> {
+    /// Wraps `inner`, drawing rotated by `rotation`
+    pub fn new(inner: D, rotation: Rotation) -> Self {
+        Self { inner, rotation }
+    }
+
+    /// Set the rotation used for following drawing
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.rotation = rotation;
+    }
+
+    /// Get the rotation
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    /// Get the inner draw target
+    pub fn inner(&mut self) -> &mut D {
+        &mut self.inner
+    }
+
+    /// Release the inner draw target
+    pub fn free(self) -> D {
+        self.inner
+    }
+}
+
+/// Maps a point to the coordinates of the unrotated target of size `size`
+fn transform(rotation: Rotation, size: Size, point: Point) -> Point {
+    let width = size.width as i32;
+    let height = size.height as i32;
+    match rotation {
+        Rotation::Rotate0 => point,
+        Rotation::Rotate90 => Point::new(width - 1 - point.y, point.x),
+        Rotation::Rotate180 => Point::new(width - 1 - point.x, height - 1 - point.y),
+        Rotation::Rotate270 => Point::new(point.y, height - 1 - point.x),
+    }
+}
+
+impl<D, C> DrawTarget<C> for RotatedDrawTarget<D>
+where
+    D: DrawTarget<C>,
+    C: PixelColor,
+{
+    type Error = D::Error;
+
+    fn draw_pixel(&mut self, pixel: Pixel<C>) -> Result<(), Self::Error> {
+        let Pixel(point, color) = pixel;
+        let point = transform(self.rotation, self.inner.size(), point);
+        self.inner.draw_pixel(Pixel(point, color))
+    }
+
+    fn draw_iter<T>(&mut self, item: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = Pixel<C>>,
+    {
+        let rotation = self.rotation;
+        let size = self.inner.size();
+        self.inner.draw_iter(
+            item.into_iter()
+                .map(move |Pixel(point, color)| Pixel(transform(rotation, size, point), color)),
+        )
+    }
+
+    fn size(&self) -> Size {
+        let size = self.inner.size();
+        if self.rotation.is_transposed() {
+            Size::new(size.height, size.width)
+        } else {
+            size
+        }
+    }
+
+    fn clear(&mut self, color: C) -> Result<(), Self::Error> {
+        self.inner.clear(color)
+    }
+}
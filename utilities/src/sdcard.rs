@@ -0,0 +1,354 @@
+//! SD card block access in SPI mode.
+
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::digital::v2::OutputPin;
+
+use crate::spi::{self, Spim};
+
+/// Size of a block in bytes
+pub const BLOCK_SIZE: usize = 512;
+
+/// GO_IDLE_STATE, software reset
+const CMD0: u8 = 0;
+/// SEND_IF_COND, check the supply voltage
+const CMD8: u8 = 8;
+/// SET_BLOCKLEN
+const CMD16: u8 = 16;
+/// READ_SINGLE_BLOCK
+const CMD17: u8 = 17;
+/// WRITE_BLOCK
+const CMD24: u8 = 24;
+/// APP_CMD, the next command is an application command
+const CMD55: u8 = 55;
+/// READ_OCR
+const CMD58: u8 = 58;
+/// SD_SEND_OP_COND, application command
+const ACMD41: u8 = 41;
+
+/// R1 idle state bit
+const R1_IDLE: u8 = 0x01;
+/// R1 illegal command bit
+const R1_ILLEGAL_COMMAND: u8 = 0x04;
+/// Token starting a data block
+const DATA_START_TOKEN: u8 = 0xfe;
+/// Data response for accepted data
+const DATA_ACCEPTED: u8 = 0x05;
+/// OCR card capacity status bit, set for SDHC and SDXC cards
+const OCR_CCS: u32 = 0x4000_0000;
+
+/// Number of bytes polled for a command response
+const RESPONSE_POLLS: usize = 8;
+/// Number of bytes polled for the start of a data block
+const DATA_POLLS: usize = 4096;
+/// Number of bytes polled while the card is busy programming
+const BUSY_POLLS: usize = 65535;
+/// Number of 1 ms attempts for the card to leave the idle state
+const INIT_ATTEMPTS: u16 = 1000;
+
+/// SD card errors
+#[derive(Debug, defmt::Format)]
+pub enum SdError {
+    /// SPI transfer error
+    Spi(spi::Error),
+    /// Failed to drive the chip select pin
+    ChipSelect,
+    /// The card didn't respond in time
+    Timeout,
+    /// The card doesn't support the supply voltage or isn't an SD card
+    UnsupportedCard,
+    /// The card answered a command with an error, the R1 response is given
+    Command(u8),
+    /// A read ended with an error token instead of data
+    ReadError(u8),
+    /// The card rejected written data, the data response is given
+    WriteRejected(u8),
+}
+
+impl From<spi::Error> for SdError {
+    fn from(error: spi::Error) -> Self {
+        SdError::Spi(error)
+    }
+}
+
+/// CRC-7 of a command, as used in the command frame
+pub fn crc7(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for byte in data {
+        let mut byte = *byte;
+        for _ in 0..8 {
+            crc <<= 1;
+            if (byte ^ crc) & 0x80 != 0 {
+                crc ^= 0x09;
+            }
+            byte <<= 1;
+        }
+    }
+    crc & 0x7f
+}
+
+/// Builds the six byte frame for a command, start bits, command index,
+/// argument and CRC-7 with the end bit
+pub fn command_frame(command: u8, argument: u32) -> [u8; 6] {
+    let argument = argument.to_be_bytes();
+    let mut frame = [
+        0x40 | (command & 0x3f),
+        argument[0],
+        argument[1],
+        argument[2],
+        argument[3],
+        0,
+    ];
+    frame[5] = (crc7(&frame[..5]) << 1) | 1;
+    frame
+}
+
+/// SD card connected to a SPIM instance with a GPIO chip select
+///
+/// Create the `Spim` without a hardware chip select, with `MODE_0` and an
+/// `orc` of 0xff, as the card expects the data line high while it responds.
+/// Cards must be initialised at 100 to 400 kHz, use `Frequency::K250`.
+///
+/// The chip select is deselected after each command, followed by eight
+/// clocks so that the card releases the data out line on a shared bus.
+pub struct SdCard<T, CS> {
+    spi: Spim<T>,
+    cs: CS,
+    /// Block addressed (SDHC / SDXC) or byte addressed (SDSC) card
+    high_capacity: bool,
+}
+
+impl<T, CS> SdCard<T, CS>
+where
+    T: spi::Instance,
+    CS: OutputPin,
+{
+    /// Creates the driver, call `init` before accessing blocks
+    pub fn new(spi: Spim<T>, cs: CS) -> Self {
+        Self {
+            spi,
+            cs,
+            high_capacity: false,
+        }
+    }
+
+    /// Initialises the card into SPI mode
+    ///
+    /// Sends the wake up clocks and the CMD0, CMD8 and ACMD41 sequence, then
+    /// finds out how the card is addressed. Version 1 cards and cards
+    /// answering CMD8 are supported, MMC cards are not.
+    pub fn init<DELAY>(&mut self, delay: &mut DELAY) -> Result<(), SdError>
+    where
+        DELAY: DelayMs<u16>,
+    {
+        // At least 74 clocks with chip select and data in high
+        self.cs.set_high().map_err(|_| SdError::ChipSelect)?;
+        // Buffers are kept on the stack, EasyDMA can't read constants in flash
+        let wake_up = [0xffu8; 10];
+        self.spi.write(&wake_up)?;
+
+        // The card may not answer the first resets
+        let mut idle = false;
+        for _ in 0..10 {
+            match self.command(CMD0, 0, &mut []) {
+                Ok(R1_IDLE) => {
+                    idle = true;
+                    break;
+                }
+                Ok(_) | Err(SdError::Timeout) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if !idle {
+            return Err(SdError::Timeout);
+        }
+
+        let mut r7 = [0u8; 4];
+        let r1 = self.command(CMD8, 0x1aa, &mut r7)?;
+        let version_2 = r1 & R1_ILLEGAL_COMMAND == 0;
+        if version_2 && (r7[2] & 0x0f != 0x01 || r7[3] != 0xaa) {
+            return Err(SdError::UnsupportedCard);
+        }
+
+        let argument = if version_2 { OCR_CCS } else { 0 };
+        let mut attempts = 0;
+        loop {
+            self.command(CMD55, 0, &mut [])?;
+            let r1 = self.command(ACMD41, argument, &mut [])?;
+            if r1 == 0 {
+                break;
+            }
+            if r1 != R1_IDLE {
+                return Err(SdError::Command(r1));
+            }
+            attempts += 1;
+            if attempts >= INIT_ATTEMPTS {
+                return Err(SdError::Timeout);
+            }
+            delay.delay_ms(1);
+        }
+
+        self.high_capacity = false;
+        if version_2 {
+            let mut ocr = [0u8; 4];
+            let r1 = self.command(CMD58, 0, &mut ocr)?;
+            if r1 != 0 {
+                return Err(SdError::Command(r1));
+            }
+            self.high_capacity = u32::from_be_bytes(ocr) & OCR_CCS == OCR_CCS;
+        }
+        if !self.high_capacity {
+            let r1 = self.command(CMD16, BLOCK_SIZE as u32, &mut [])?;
+            if r1 != 0 {
+                return Err(SdError::Command(r1));
+            }
+        }
+        Ok(())
+    }
+
+    /// Is the card block addressed, an SDHC or SDXC card?
+    pub fn is_high_capacity(&self) -> bool {
+        self.high_capacity
+    }
+
+    /// Reads a block
+    pub fn read_block(&mut self, block: u32, buffer: &mut [u8; BLOCK_SIZE]) -> Result<(), SdError> {
+        let address = self.block_address(block);
+        self.select()?;
+        let result = self.read_block_selected(address, buffer);
+        self.deselect()?;
+        result
+    }
+
+    /// Writes a block
+    ///
+    /// Returns when the card has finished programming the block.
+    pub fn write_block(&mut self, block: u32, data: &[u8; BLOCK_SIZE]) -> Result<(), SdError> {
+        let address = self.block_address(block);
+        self.select()?;
+        let result = self.write_block_selected(address, data);
+        self.deselect()?;
+        result
+    }
+
+    /// Returns the SPI interface and the chip select pin
+    pub fn free(self) -> (Spim<T>, CS) {
+        (self.spi, self.cs)
+    }
+
+    fn block_address(&self, block: u32) -> u32 {
+        if self.high_capacity {
+            block
+        } else {
+            block * BLOCK_SIZE as u32
+        }
+    }
+
+    fn select(&mut self) -> Result<(), SdError> {
+        self.cs.set_low().map_err(|_| SdError::ChipSelect)
+    }
+
+    /// Deselects the card and clocks out one byte so that it releases the
+    /// data out line
+    fn deselect(&mut self) -> Result<(), SdError> {
+        self.cs.set_high().map_err(|_| SdError::ChipSelect)?;
+        let mut byte = [0xff];
+        self.spi.transfer(&mut byte)?;
+        Ok(())
+    }
+
+    /// Clocks out 0xff and returns the received byte
+    fn read_byte(&mut self) -> Result<u8, SdError> {
+        let mut byte = [0xff];
+        self.spi.transfer(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    /// Sends a command with the card selected and returns the R1 response,
+    /// the bytes following R1 are read into `response`
+    fn send_command(
+        &mut self,
+        command: u8,
+        argument: u32,
+        response: &mut [u8],
+    ) -> Result<u8, SdError> {
+        let frame = command_frame(command, argument);
+        self.spi.write(&frame)?;
+        for _ in 0..RESPONSE_POLLS {
+            let r1 = self.read_byte()?;
+            if r1 & 0x80 == 0 {
+                for byte in response.iter_mut() {
+                    *byte = self.read_byte()?;
+                }
+                return Ok(r1);
+            }
+        }
+        Err(SdError::Timeout)
+    }
+
+    /// Sends a command in its own chip select assertion
+    fn command(&mut self, command: u8, argument: u32, response: &mut [u8]) -> Result<u8, SdError> {
+        self.select()?;
+        let result = self.send_command(command, argument, response);
+        self.deselect()?;
+        result
+    }
+
+    fn read_block_selected(
+        &mut self,
+        address: u32,
+        buffer: &mut [u8; BLOCK_SIZE],
+    ) -> Result<(), SdError> {
+        let r1 = self.send_command(CMD17, address, &mut [])?;
+        if r1 != 0 {
+            return Err(SdError::Command(r1));
+        }
+        let mut token = 0xff;
+        for _ in 0..DATA_POLLS {
+            token = self.read_byte()?;
+            if token != 0xff {
+                break;
+            }
+        }
+        match token {
+            DATA_START_TOKEN => {}
+            0xff => return Err(SdError::Timeout),
+            error => return Err(SdError::ReadError(error)),
+        }
+        for byte in buffer.iter_mut() {
+            *byte = 0xff;
+        }
+        self.spi.transfer(buffer)?;
+        // Discard the CRC-16
+        let mut crc = [0xff; 2];
+        self.spi.transfer(&mut crc)?;
+        Ok(())
+    }
+
+    fn write_block_selected(
+        &mut self,
+        address: u32,
+        data: &[u8; BLOCK_SIZE],
+    ) -> Result<(), SdError> {
+        let r1 = self.send_command(CMD24, address, &mut [])?;
+        if r1 != 0 {
+            return Err(SdError::Command(r1));
+        }
+        // One byte gap, then the start token, the data and a dummy CRC-16
+        let start = [0xff, DATA_START_TOKEN];
+        let crc = [0xff, 0xff];
+        self.spi.write(&start)?;
+        self.spi.write(data)?;
+        self.spi.write(&crc)?;
+        let response = self.read_byte()? & 0x1f;
+        if response != DATA_ACCEPTED {
+            return Err(SdError::WriteRejected(response));
+        }
+        // The card holds the data line low while programming
+        for _ in 0..BUSY_POLLS {
+            if self.read_byte()? != 0x00 {
+                return Ok(());
+            }
+        }
+        Err(SdError::Timeout)
+    }
+}
@@ -1,7 +1,84 @@
 /// Send command and data over SPI
 pub trait SpiSendCommandData {
+    /// Error returned when sending fails
+    type Error;
+
+    /// Send `data` where the first `command_bytes` are sent with the
+    /// data / command line low and the remaining bytes with it high
+    fn send_command_data(&mut self, data: &[u8], command_bytes: u8) -> Result<(), Self::Error>;
+
+    /// Send the `command` bytes followed by the `data` bytes without
+    /// copying them into one buffer
     ///
-    fn send_command_data(&mut self, data: &[u8], command_bytes: u8) -> Result<(), Error>;
+    /// The default implementation sends them as two transfers, releasing
+    /// the chip select in between. This suits controllers such as the
+    /// ST7735 which keep the command state when deselected. Implementations
+    /// able to hold the chip select across both transfers should do so.
+    fn send_command_then_data(&mut self, command: &[u8], data: &[u8]) -> Result<(), Self::Error> {
+        self.send_command_data(command, command.len() as u8)?;
+        self.send_command_data(data, 0)
+    }
+}
+
+/// SPI bus combined with a GPIO data / command pin
+///
+/// Makes it possible to use any embedded-hal SPI bus, such as a bit-banged
+/// or shared bus, to send commands and data to a display. The data /
+/// command pin is driven low while the command bytes are sent and high
+/// while the data bytes are sent.
+///
+/// Use this when the SPI peripheral lacks hardware data / command (DCX)
+/// support or the D/C signal is routed to a pin the peripheral can't use.
+/// The chip select has to be managed by the SPI bus, or be tied low.
+pub struct SpiDataCommand<SPI, DC> {
+    spi: SPI,
+    dc: DC,
+}
+
+/// Errors from the SPI bus or the data / command pin
+#[derive(Debug)]
+pub enum SpiDataCommandError<S, P> {
+    /// SPI bus error
+    Spi(S),
+    /// Data / command pin error
+    Pin(P),
+}
+
+impl<SPI, DC> SpiDataCommand<SPI, DC>
+where
+    SPI: embedded_hal::blocking::spi::Write<u8>,
+    DC: OutputPin,
+{
+    /// Combine a SPI bus with a data / command pin
+    pub fn new(spi: SPI, dc: DC) -> Self {
+        Self { spi, dc }
+    }
+
+    /// Return the SPI bus and data / command pin
+    pub fn free(self) -> (SPI, DC) {
+        (self.spi, self.dc)
+    }
+}
+
+impl<SPI, DC> SpiSendCommandData for SpiDataCommand<SPI, DC>
+where
+    SPI: embedded_hal::blocking::spi::Write<u8>,
+    DC: OutputPin,
+{
+    type Error = SpiDataCommandError<SPI::Error, DC::Error>;
+
+    fn send_command_data(&mut self, data: &[u8], command_bytes: u8) -> Result<(), Self::Error> {
+        let (command, data) = data.split_at(usize::from(command_bytes).min(data.len()));
+        if !command.is_empty() {
+            self.dc.set_low().map_err(SpiDataCommandError::Pin)?;
+            self.spi.write(command).map_err(SpiDataCommandError::Spi)?;
+        }
+        if !data.is_empty() {
+            self.dc.set_high().map_err(SpiDataCommandError::Pin)?;
+            self.spi.write(data).map_err(SpiDataCommandError::Spi)?;
+        }
+        Ok(())
+    }
 }
 
 // NRF52840 and NRF9160 16 bits 1..0xFFFF
@@ -29,6 +106,7 @@ pub(crate) fn slice_in_ram_or<T>(slice: &[u8], err: T) -> Result<(), T> {
 /// A handy structure for converting rust slices into ptr and len pairs
 /// for use with EasyDMA. Care must be taken to make sure mutability
 /// guarantees are respected
+#[derive(Clone, Copy)]
 pub(crate) struct DmaSlice {
     ptr: u32,
     len: u32,
@@ -51,10 +129,14 @@ impl DmaSlice {
 //
 // See product specification, chapter 31.
 use core::ops::Deref;
-use core::sync::atomic::{compiler_fence, Ordering::SeqCst};
+use core::sync::atomic::{
+    compiler_fence,
+    Ordering::{Acquire, Release},
+};
 
 use crate::hal::pac::{spim0, SPIM0, SPIM1, SPIM2, SPIM3};
 
+use embedded_hal::digital::v2::OutputPin;
 pub use embedded_hal::spi::{Mode, Phase, Polarity, MODE_0, MODE_1, MODE_2, MODE_3};
 pub use spim0::frequency::FREQUENCY_A as Frequency;
 
@@ -68,7 +150,18 @@ use crate::hal::gpio::{Floating, Input, Output, Pin, PushPull};
 /// - The SPIM instances share the same address space with instances of SPIS,
 ///   SPI, TWIM, TWIS, and TWI. You need to make sure that conflicting instances
 ///   are disabled before using `Spim`. See product specification, section 15.2.
-pub struct Spim<T>(T);
+pub struct Spim<T> {
+    spim: T,
+    /// Number of times a transfer with a short count is retried
+    retries: u8,
+    /// Chip select driven as a GPIO when in continuous mode
+    continuous_cs: Option<Pin<Output<PushPull>>>,
+    /// A transfer started with `start_transfer` hasn't been cleared yet
+    in_flight: bool,
+    /// Running CRC-32 state of the command and data stream
+    #[cfg(feature = "command-crc")]
+    command_crc: u32,
+}
 
 impl<T> embedded_hal::blocking::spi::Transfer<u8> for Spim<T>
 where
@@ -119,9 +212,126 @@ impl<T> SpiSendCommandData for Spim<T>
 where
     T: Instance,
 {
+    type Error = Error;
+
     fn send_command_data(&mut self, data: &[u8], command_bytes: u8) -> Result<(), Error> {
+        #[cfg(feature = "command-crc")]
+        {
+            self.command_crc = crc32_update(self.command_crc, data);
+        }
         self.write_dc(data, command_bytes)
     }
+
+    /// Sends the command and data as two DMA transfers with the chip select
+    /// held asserted, see `with_cs`
+    fn send_command_then_data(&mut self, command: &[u8], data: &[u8]) -> Result<(), Error> {
+        #[cfg(feature = "command-crc")]
+        {
+            self.command_crc = crc32_update(crc32_update(self.command_crc, command), data);
+        }
+        self.with_cs(|spim| {
+            spim.transfer_dcx(command, command.len() as u8)?;
+            spim.transfer_dcx(data, 0)
+        })
+    }
+}
+
+#[cfg(feature = "command-crc")]
+const CRC32_INIT: u32 = 0xffff_ffff;
+
+/// Update a CRC-32 (IEEE 802.3) state with `data`
+#[cfg(feature = "command-crc")]
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
+
+/// Runs `transfer`, running it again up to `retries` more times while it
+/// fails with a short count, `Error::Transmit` or `Error::Receive`
+///
+/// This is the retry policy of the blocking `Spim` transfers, see
+/// `Spim::set_retries`. Other errors are returned directly.
+pub fn retry_short_count<F>(retries: u8, mut transfer: F) -> Result<(), Error>
+where
+    F: FnMut() -> Result<(), Error>,
+{
+    let mut retries = retries;
+    loop {
+        match transfer() {
+            Err(Error::Transmit) | Err(Error::Receive) if retries > 0 => {
+                retries -= 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Does the SPI frequency require high drive outputs?
+pub fn requires_high_drive(frequency: Frequency) -> bool {
+    matches!(frequency, Frequency::M16 | Frequency::M32)
+}
+
+/// Configures the output pins, SCK, MOSI, CSN and DCX, for high drive
+///
+/// The standard drive strength is too weak for the fast edges needed at 16
+/// and 32 MHz, the signals degrade and transfers fail. Both the high and low
+/// levels are set to high drive (H0H1). Short traces are still recommended
+/// at these frequencies.
+pub fn configure_high_drive(pins: &Pins) {
+    set_high_drive(&pins.sck);
+    if let Some(mosi) = &pins.mosi {
+        set_high_drive(mosi);
+    }
+    if let Some(csn) = &pins.csn {
+        set_high_drive(csn);
+    }
+    if let Some(dcx) = &pins.dcx {
+        set_high_drive(dcx);
+    }
+}
+
+fn set_high_drive<MODE>(pin: &Pin<MODE>) {
+    use crate::hal::gpio::Port;
+    use crate::hal::pac::{P0, P1};
+    let port = match pin.port() {
+        Port::Port0 => P0::ptr(),
+        Port::Port1 => P1::ptr(),
+    };
+    // The pin is owned by the SPIM driver, only the drive strength is changed
+    unsafe { &*port }.pin_cnf[pin.pin() as usize].modify(|_, w| w.drive().h0h1());
+}
+
+/// Writes the CONFIG register, bit order and clock polarity and phase
+fn write_config(spim: &spim0::RegisterBlock, mode: Mode) {
+    spim.config.write(|w| {
+        w.order().msb_first();
+        match mode.polarity {
+            Polarity::IdleLow => {
+                w.cpol().active_high();
+            }
+            Polarity::IdleHigh => {
+                w.cpol().active_low();
+            }
+        }
+        match mode.phase {
+            Phase::CaptureOnFirstTransition => {
+                w.cpha().leading();
+            }
+            Phase::CaptureOnSecondTransition => {
+                w.cpha().trailing();
+            }
+        }
+        w
+    });
 }
 
 fn port_to_bool(port: crate::hal::gpio::Port) -> bool {
@@ -147,7 +357,41 @@ where
         self.do_spi_dma_transfer(DmaSlice::from_slice(&buf[..chunk.len()]), DmaSlice::null())
     }
 
-    pub fn new(spim: T, pins: Pins, frequency: Frequency, mode: Mode, orc: u8) -> Self {
+    /// Creates a new SPIM driver
+    ///
+    /// For frequencies above 8 MHz the output pins are switched to high
+    /// drive, see `configure_high_drive`.
+    ///
+    /// Fails with `Error::PinConflict` if a pin is assigned more than one role.
+    /// Fails with `Error::UnsupportedPin` if a chip select or DCX pin is
+    /// given for an instance without hardware support for them, only SPIM3
+    /// has it on the nRF52833. Use a GPIO chip select or `SpiDataCommand`
+    /// with the other instances.
+    ///
+    /// On failure nothing is configured and the SPIM peripheral is returned
+    /// with the error.
+    pub fn new(
+        spim: T,
+        pins: Pins,
+        frequency: Frequency,
+        mode: Mode,
+        orc: u8,
+    ) -> Result<Self, NewError<T>> {
+        let check = pins.validate().map_err(Error::PinConflict).and_then(|_| {
+            if !T::HAS_CSN_DCX && pins.csn.is_some() {
+                Err(Error::UnsupportedPin(PinRole::Csn))
+            } else if !T::HAS_CSN_DCX && pins.dcx.is_some() {
+                Err(Error::UnsupportedPin(PinRole::Dcx))
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(error) = check {
+            return Err(NewError { error, spim });
+        }
+        if requires_high_drive(frequency) {
+            configure_high_drive(&pins);
+        }
         // Select pins
         spim.psel.sck.write(|w| {
             let w = unsafe { w.pin().bits(pins.sck.pin()) };
@@ -201,26 +445,7 @@ where
         spim.enable.write(|w| w.enable().enabled());
 
         // Configure mode
-        spim.config.write(|w| {
-            w.order().msb_first();
-            match mode.polarity {
-                Polarity::IdleLow => {
-                    w.cpol().active_high();
-                }
-                Polarity::IdleHigh => {
-                    w.cpol().active_low();
-                }
-            }
-            match mode.phase {
-                Phase::CaptureOnFirstTransition => {
-                    w.cpha().leading();
-                }
-                Phase::CaptureOnSecondTransition => {
-                    w.cpha().trailing();
-                }
-            }
-            w
-        });
+        write_config(&spim, mode);
 
         // Configure frequency
         spim.frequency.write(|w| w.frequency().variant(frequency));
@@ -231,7 +456,170 @@ where
             // there.
             unsafe { w.orc().bits(orc) });
 
-        Spim(spim)
+        Ok(Spim {
+            spim,
+            retries: 0,
+            continuous_cs: None,
+            in_flight: false,
+            #[cfg(feature = "command-crc")]
+            command_crc: CRC32_INIT,
+        })
+    }
+
+    /// Disconnects the hardware chip select
+    ///
+    /// Use this when the chip select is driven by a GPIO, for example through
+    /// `transfer_with_cs`, on a bus shared by several slaves.
+    pub fn without_hw_cs(self) -> Self {
+        self.spim.psel.csn.write(|w| w.connect().disconnected());
+        self
+    }
+
+    /// Keeps the chip select asserted across transfers
+    ///
+    /// Normally the hardware asserts the chip select for each transfer, with
+    /// the polarity given by CSNPOL and the delay from asserting it to the
+    /// first clock edge given by CSNDUR. In continuous mode the chip select
+    /// pin is disconnected from the peripheral and driven low as a GPIO
+    /// instead, so CSNPOL and CSNDUR no longer apply.
+    ///
+    /// The chip select is asserted by each transfer and stays asserted until
+    /// `release_cs` is called, the slave is never deselected otherwise. Does
+    /// nothing if no chip select pin was configured.
+    pub fn continuous_cs(mut self) -> Self {
+        let psel = self.spim.psel.csn.read();
+        if psel.connect().is_disconnected() {
+            return self;
+        }
+        // The pin was handed over in `Pins` and is configured as an output
+        let mut cs = unsafe { Pin::<Output<PushPull>>::from_psel_bits(psel.bits() & 0x3f) };
+        self.spim.psel.csn.write(|w| w.connect().disconnected());
+        let _ = cs.set_low();
+        self.continuous_cs = Some(cs);
+        self
+    }
+
+    /// Deselects the slave when in continuous chip select mode
+    ///
+    /// The next transfer asserts the chip select again.
+    pub fn release_cs(&mut self) {
+        if let Some(cs) = self.continuous_cs.as_mut() {
+            let _ = cs.set_high();
+        }
+    }
+
+    /// Runs `f` with the chip select asserted for the whole call
+    ///
+    /// Several transfers can be made in the closure without deselecting the
+    /// slave in between, as needed by some device initialisation sequences.
+    /// The hardware chip select is disconnected from the peripheral and the
+    /// pin is driven low as a GPIO, so CSNPOL and CSNDUR don't apply. The pin
+    /// is driven high and handed back to the peripheral when `f` returns. In
+    /// continuous chip select mode the slave is deselected when `f` returns,
+    /// as with `release_cs`. Without a chip select pin `f` is just called.
+    ///
+    /// A hardware chip select only exists on SPIM3, `new` rejects one for
+    /// the other instances. On SPIM0 to SPIM2 the slave is only held
+    /// selected in continuous chip select mode, otherwise `f` is just called.
+    ///
+    /// Calls must not be nested, the inner call deselects the slave when it
+    /// returns.
+    pub fn with_cs<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        if self.continuous_cs.is_some() {
+            let result = f(self);
+            self.release_cs();
+            return result;
+        }
+        let psel = self.spim.psel.csn.read().bits();
+        if self.spim.psel.csn.read().connect().is_disconnected() {
+            return f(self);
+        }
+        // The pin was handed over in `Pins` and is configured as an output
+        let mut cs = unsafe { Pin::<Output<PushPull>>::from_psel_bits(psel & 0x3f) };
+        let _ = cs.set_high();
+        self.spim.psel.csn.write(|w| w.connect().disconnected());
+        let _ = cs.set_low();
+        self.continuous_cs = Some(cs);
+        let result = f(self);
+        if let Some(mut cs) = self.continuous_cs.take() {
+            let _ = cs.set_high();
+        }
+        self.spim.psel.csn.write(|w| unsafe { w.bits(psel) });
+        result
+    }
+
+    /// Read and write from a SPI slave selected by a GPIO chip select
+    ///
+    /// The chip select pin is driven low before the transfer and high after
+    /// it, also when the transfer fails.
+    pub fn transfer_with_cs<CS>(&mut self, cs: &mut CS, buffer: &mut [u8]) -> Result<(), Error>
+    where
+        CS: OutputPin,
+    {
+        cs.set_low().map_err(|_| Error::ChipSelect)?;
+        let result = self.transfer(buffer);
+        cs.set_high().map_err(|_| Error::ChipSelect)?;
+        result
+    }
+
+    /// Runs the phases back-to-back with the chip select asserted once
+    ///
+    /// Use this for commands made of several parts, such as a SPI flash
+    /// read with an opcode and address write phase followed by a read phase.
+    /// The hardware chip select is held as with `with_cs`, without one the
+    /// caller selects the slave, see `transaction_with_cs`. A failing phase
+    /// ends the transaction and the chip select is released.
+    ///
+    /// DCX is low only for the command bytes of `Phase::WriteDcx`, the other
+    /// phases are sent with DCX high. For a display, send the command byte
+    /// and its parameters in one `WriteDcx` phase with one command byte, and
+    /// pixel data in the following `Write` phases.
+    pub fn transaction(&mut self, phases: &mut [Phase<'_>]) -> Result<(), Error> {
+        self.with_cs(|spim| spim.run_phases(phases))
+    }
+
+    /// Runs the phases back-to-back with the slave selected by a GPIO chip
+    /// select
+    ///
+    /// The chip select pin is driven low before the first phase and high
+    /// after the last, also when a phase fails. See `transaction`.
+    pub fn transaction_with_cs<CS>(
+        &mut self,
+        cs: &mut CS,
+        phases: &mut [Phase<'_>],
+    ) -> Result<(), Error>
+    where
+        CS: OutputPin,
+    {
+        cs.set_low().map_err(|_| Error::ChipSelect)?;
+        let result = self.run_phases(phases);
+        cs.set_high().map_err(|_| Error::ChipSelect)?;
+        result
+    }
+
+    fn run_phases(&mut self, phases: &mut [Phase<'_>]) -> Result<(), Error> {
+        let result = phases.iter_mut().try_for_each(|phase| {
+            // DCXCNT is kept between transfers, keep DCX high for the
+            // phases without command bytes
+            self.clear_dcx_count();
+            match phase {
+                Phase::Write(data) => self.write(data),
+                Phase::WriteDcx(data, command_bytes) => self.transfer_dcx(data, *command_bytes),
+                Phase::Read(buffer) => buffer.chunks_mut(EASY_DMA_SIZE).try_for_each(|chunk| {
+                    self.do_spi_dma_transfer(DmaSlice::null(), DmaSlice::from_slice(chunk))
+                }),
+                Phase::Transfer(buffer) => self.transfer(buffer),
+            }
+        });
+        self.clear_dcx_count();
+        result
+    }
+
+    /// Clears DCXCNT, only SPIM3 has the register
+    fn clear_dcx_count(&self) {
+        if T::HAS_CSN_DCX {
+            self.spim.dcxcnt.write(|w| unsafe { w.bits(0) });
+        }
     }
 
     /// Internal helper function to setup and execute SPIM DMA transfer
@@ -241,24 +629,154 @@ where
         rx: DmaSlice,
         command_bytes: u8,
     ) -> Result<(), Error> {
-        // Configure DCX bytes
-        self.0
-            .dcxcnt
-            .write(|w| unsafe { w.bits(command_bytes as u32) });
+        // Configure DCX bytes, only SPIM3 has the register
+        if T::HAS_CSN_DCX {
+            self.spim
+                .dcxcnt
+                .write(|w| unsafe { w.bits(command_bytes as u32) });
+        }
         self.do_spi_dma_transfer(tx, rx)
     }
 
-    /// Internal helper function to setup and execute SPIM DMA transfer
+    /// Changes the SPI mode, clock polarity and phase
+    ///
+    /// Use this to share the bus between slaves using different modes. All
+    /// transfers are blocking and borrow the driver mutably, so the mode
+    /// can't change in the middle of a transfer. When the chip select is
+    /// kept asserted with `continuous_cs`, release it before switching to
+    /// a slave with another mode.
+    pub fn set_mode(&mut self, mode: Mode) {
+        write_config(&self.spim, mode);
+    }
+
+    /// Changes the SPI clock frequency
+    ///
+    /// Takes effect from the next transfer. The blocking transfers borrow
+    /// the driver mutably, so they can't be interrupted by a change. Changing
+    /// the frequency while a transfer started with `start_transfer` is in
+    /// flight is undefined, wait for `transfer_done` first.
+    ///
+    /// For frequencies above 8 MHz the output pins are switched to high
+    /// drive, as in `new`. They are left at high drive when switching back.
+    pub fn set_frequency(&mut self, frequency: Frequency) {
+        if requires_high_drive(frequency) {
+            self.set_high_drive();
+        }
+        self.spim
+            .frequency
+            .write(|w| w.frequency().variant(frequency));
+    }
+
+    /// Switches the connected output pins to high drive
+    fn set_high_drive(&self) {
+        let psels = [
+            self.spim.psel.sck.read().bits(),
+            self.spim.psel.mosi.read().bits(),
+            self.spim.psel.csn.read().bits(),
+            self.spim.pseldcx.read().bits(),
+        ];
+        for bits in psels.iter() {
+            // Bit 31 is set for disconnected pins
+            if bits & 0x8000_0000 == 0 {
+                let pin = unsafe { Pin::<Output<PushPull>>::from_psel_bits(bits & 0x3f) };
+                set_high_drive(&pin);
+            }
+        }
+    }
+
+    /// Changes the over-read character, the byte clocked out once the
+    /// transmit buffer is exhausted
+    ///
+    /// This is the fill sent by `transfer_split_uneven` and `command_read`
+    /// while receiving past the end of the transmit data. Like `set_mode`
+    /// it takes effect from the next transfer.
+    pub fn set_orc(&mut self, orc: u8) {
+        self.spim.orc.write(|w| unsafe { w.orc().bits(orc) });
+    }
+
+    /// Sets how many times a transfer is retried when fewer bytes than
+    /// expected were transmitted or received
+    ///
+    /// The default is zero, a short count fails the transfer with
+    /// `Error::Transmit` or `Error::Receive` directly.
+    pub fn set_retries(&mut self, retries: u8) {
+        self.retries = retries;
+    }
+
+    /// Waits until all DMA transfers are complete and their results are
+    /// visible
+    ///
+    /// A buffer handed to a non-blocking transfer must not be read, written
+    /// or dropped before `flush` returns, doing so is undefined behaviour as
+    /// the DMA may still access it. A transfer started with `start_transfer`
+    /// is waited for and its END event cleared, as with
+    /// `clear_transfer_done`. The blocking transfers wait for the END event
+    /// before returning, for those this is a no-op apart from the fence.
+    pub fn flush(&mut self) {
+        if self.in_flight {
+            while self.spim.events_end.read().bits() == 0 {}
+            self.spim.events_end.write(|w| w);
+            self.in_flight = false;
+        }
+        compiler_fence(Acquire);
+    }
+
+    /// Get the number of bytes transmitted and received, as `(TXD.AMOUNT,
+    /// RXD.AMOUNT)`
+    ///
+    /// The registers only reflect the most recent DMA chunk, transfers
+    /// larger than the EasyDMA size are split into several chunks.
+    pub fn last_amounts(&self) -> (u32, u32) {
+        (
+            self.spim.txd.amount.read().bits(),
+            self.spim.rxd.amount.read().bits(),
+        )
+    }
+
+    /// Internal helper function to setup and execute SPIM DMA transfer,
+    /// retrying on short counts
     fn do_spi_dma_transfer(&mut self, tx: DmaSlice, rx: DmaSlice) -> Result<(), Error> {
-        // Conservative compiler fence to prevent optimizations that do not
-        // take in to account actions by DMA. The fence has been placed here,
-        // before any DMA action has started
-        compiler_fence(SeqCst);
+        retry_short_count(self.retries, || self.do_spi_dma_transfer_once(tx, rx))
+    }
+
+    /// Internal helper function to setup and execute SPIM DMA transfer
+    fn do_spi_dma_transfer_once(&mut self, tx: DmaSlice, rx: DmaSlice) -> Result<(), Error> {
+        self.start_dma(tx, rx);
+
+        // Wait for END event
+        //
+        // This event is triggered once both transmitting and receiving are
+        // done.
+        while self.spim.events_end.read().bits() == 0 {}
+
+        // Reset the event, otherwise it will always read `1` from now on.
+        self.spim.events_end.write(|w| w);
+
+        // Acquire fence, reads of the RX buffer must not be moved before
+        // this point. The fence has been placed after the END event, when
+        // all DMA actions have completed, and before the amounts and the
+        // buffers are read.
+        compiler_fence(Acquire);
+
+        if self.spim.txd.amount.read().bits() != tx.len {
+            return Err(Error::Transmit);
+        }
+        if self.spim.rxd.amount.read().bits() != rx.len {
+            return Err(Error::Receive);
+        }
+        Ok(())
+    }
+
+    /// Internal helper function to setup and start SPIM DMA transfer
+    fn start_dma(&mut self, tx: DmaSlice, rx: DmaSlice) {
+        if let Some(cs) = self.continuous_cs.as_mut() {
+            let _ = cs.set_low();
+        }
 
         // Set up the DMA write
-        self.0.txd.ptr.write(|w| unsafe { w.ptr().bits(tx.ptr) });
+        self.spim.txd.ptr.write(|w| unsafe { w.ptr().bits(tx.ptr) });
 
-        self.0.txd.maxcnt.write(|w|
+        self.spim.txd.maxcnt.write(|w|
             // Note that that nrf52840 maxcnt is a wider
             // type than a u8, so we use a `_` cast rather than a `u8` cast.
             // The MAXCNT field is thus at least 8 bits wide and accepts the full
@@ -266,48 +784,77 @@ where
             unsafe { w.maxcnt().bits(tx.len as _ ) });
 
         // Set up the DMA read
-        self.0.rxd.ptr.write(|w|
+        self.spim.rxd.ptr.write(|w|
             // This is safe for the same reasons that writing to TXD.PTR is
             // safe. Please refer to the explanation there.
             unsafe { w.ptr().bits(rx.ptr) });
-        self.0.rxd.maxcnt.write(|w|
+        self.spim.rxd.maxcnt.write(|w|
             // This is safe for the same reasons that writing to TXD.MAXCNT is
             // safe. Please refer to the explanation there.
             unsafe { w.maxcnt().bits(rx.len as _) });
 
+        // Release fence, all writes to the buffers made before this point
+        // must be visible to the DMA. The fence has been placed after the
+        // pointers are set up and before the transaction is started, which
+        // is when the DMA begins reading the TX buffer.
+        compiler_fence(Release);
+
         // Start SPI transaction
-        self.0.tasks_start.write(|w|
+        self.spim.tasks_start.write(|w|
             // `1` is a valid value to write to task registers.
             unsafe { w.bits(1) });
+    }
 
-        // Conservative compiler fence to prevent optimizations that do not
-        // take in to account actions by DMA. The fence has been placed here,
-        // after all possible DMA actions have completed
-        compiler_fence(SeqCst);
-
-        // Wait for END event
-        //
-        // This event is triggered once both transmitting and receiving are
-        // done.
-        while self.0.events_end.read().bits() == 0 {}
-
-        // Reset the event, otherwise it will always read `1` from now on.
-        self.0.events_end.write(|w| w);
-
-        // Conservative compiler fence to prevent optimizations that do not
-        // take in to account actions by DMA. The fence has been placed here,
-        // after all possible DMA actions have completed
-        compiler_fence(SeqCst);
-
-        if self.0.txd.amount.read().bits() != tx.len {
-            return Err(Error::Transmit);
+    /// Starts a transfer without waiting for it to finish
+    ///
+    /// Transmits `tx_buffer` and receives into `rx_buffer`, either may be
+    /// empty. Poll `transfer_done` and call `clear_transfer_done` once it
+    /// returns true, before starting the next transfer. The transfer isn't
+    /// checked, compare `last_amounts` with the buffer lengths to find out
+    /// if all bytes were transferred. Each buffer is limited to
+    /// `EASY_DMA_SIZE` bytes.
+    ///
+    /// # Safety
+    ///
+    /// The DMA accesses the buffers until the transfer is done, the buffers
+    /// must not be used, moved or dropped until `flush` or
+    /// `clear_transfer_done` has returned. No other transfer may be made on
+    /// this instance in the meantime.
+    pub unsafe fn start_transfer(
+        &mut self,
+        tx_buffer: &[u8],
+        rx_buffer: &mut [u8],
+    ) -> Result<(), Error> {
+        slice_in_ram_or(tx_buffer, Error::DMABufferNotInDataMemory)?;
+        if tx_buffer.len() > EASY_DMA_SIZE {
+            return Err(Error::TxBufferTooLong);
         }
-        if self.0.rxd.amount.read().bits() != rx.len {
-            return Err(Error::Receive);
+        if rx_buffer.len() > EASY_DMA_SIZE {
+            return Err(Error::RxBufferTooLong);
         }
+        self.start_dma(
+            DmaSlice::from_slice(tx_buffer),
+            DmaSlice::from_slice(rx_buffer),
+        );
+        self.in_flight = true;
         Ok(())
     }
 
+    /// Has the transfer finished, is the END event set?
+    pub fn transfer_done(&self) -> bool {
+        self.spim.events_end.read().bits() != 0
+    }
+
+    /// Clears the END event after a transfer started with `start_transfer`
+    /// is done
+    ///
+    /// The receive buffer can be read after this call.
+    pub fn clear_transfer_done(&mut self) {
+        self.spim.events_end.write(|w| w);
+        self.in_flight = false;
+        compiler_fence(Acquire);
+    }
+
     /// Read from an SPI slave
     ///
     /// This method is deprecated. Consider using `transfer` or `transfer_split`
@@ -333,6 +880,53 @@ where
         })
     }
 
+    /// Send a command and read the response using a single buffer
+    ///
+    /// Transmits `buffer[..tx_len]` and then the `orc` value while receiving,
+    /// in a single transaction. The response following the command ends up
+    /// in `buffer[tx_len..]`.
+    ///
+    /// The bytes received while the command is sent are written to
+    /// `buffer[..tx_len]`, replacing the command. Each command byte has been
+    /// read by the DMA before the byte received in its place is written, so
+    /// the command is sent intact.
+    ///
+    /// The buffer may be at most `EASY_DMA_SIZE` bytes.
+    pub fn transfer_halves(&mut self, buffer: &mut [u8], tx_len: usize) -> Result<(), Error> {
+        slice_in_ram_or(buffer, Error::DMABufferNotInDataMemory)?;
+        if tx_len > buffer.len() {
+            return Err(Error::TxBufferTooLong);
+        }
+        if buffer.len() > EASY_DMA_SIZE {
+            return Err(Error::RxBufferTooLong);
+        }
+        self.do_spi_dma_transfer(
+            DmaSlice::from_slice(&buffer[..tx_len]),
+            DmaSlice::from_slice(buffer),
+        )
+    }
+
+    /// Read and write 16 bit words from a SPI slave, using a single buffer
+    ///
+    /// Each word is sent most significant byte first, and the received
+    /// bytes are assembled the same way. The hardware is still byte
+    /// oriented, the words are packed into a stack buffer and transferred
+    /// `FORCE_COPY_BUFFER_SIZE / 2` words at a time.
+    pub fn transfer_u16(&mut self, words: &mut [u16]) -> Result<(), Error> {
+        let mut buffer = [0u8; FORCE_COPY_BUFFER_SIZE];
+        for chunk in words.chunks_mut(FORCE_COPY_BUFFER_SIZE / 2) {
+            let bytes = &mut buffer[..chunk.len() * 2];
+            for (word, pair) in chunk.iter().zip(bytes.chunks_mut(2)) {
+                pair.copy_from_slice(&word.to_be_bytes());
+            }
+            self.do_spi_dma_transfer(DmaSlice::from_slice(bytes), DmaSlice::from_slice(bytes))?;
+            for (word, pair) in chunk.iter_mut().zip(bytes.chunks(2)) {
+                *word = u16::from_be_bytes([pair[0], pair[1]]);
+            }
+        }
+        Ok(())
+    }
+
     /// Read and write from a SPI slave, using separate read and write buffers
     ///
     /// This method implements a complete read transaction, which consists of
@@ -403,7 +997,7 @@ where
         //
         // Don't return early, as we must reset the CS pin
         txi.zip(rxi)
-            .take_while(|(t, r)| t.is_some() && r.is_some())
+            .take_while(|(t, r)| t.is_some() || r.is_some())
             // We also turn the slices into either a DmaSlice (if there was data), or a null
             // DmaSlice (if there is no data)
             .map(|(t, r)| {
@@ -417,6 +1011,73 @@ where
             .try_for_each(|(t, r)| self.do_spi_dma_transfer(t, r))
     }
 
+    /// Read and write from a SPI slave, using separate read and write
+    /// buffers, filling with a repeating pattern
+    ///
+    /// Works like `transfer_split_uneven`, but when `rx_buffer` is longer
+    /// than `tx_buffer` the extra outgoing bytes cycle through
+    /// `fill_pattern` instead of repeating the `orc` value. The pattern
+    /// continues across chunk boundaries. An empty pattern uses the `orc`
+    /// value.
+    ///
+    /// The outgoing bytes are copied through a stack buffer, up to
+    /// `FORCE_COPY_BUFFER_SIZE` bytes are transferred at a time.
+    pub fn transfer_split_uneven_fill(
+        &mut self,
+        tx_buffer: &[u8],
+        rx_buffer: &mut [u8],
+        fill_pattern: &[u8],
+    ) -> Result<(), Error> {
+        if fill_pattern.is_empty() || rx_buffer.len() <= tx_buffer.len() {
+            return self.transfer_split_uneven(tx_buffer, rx_buffer);
+        }
+        let mut buffer = [0u8; FORCE_COPY_BUFFER_SIZE];
+        let mut offset = 0;
+        for rx_chunk in rx_buffer.chunks_mut(FORCE_COPY_BUFFER_SIZE) {
+            let tx_chunk = &mut buffer[..rx_chunk.len()];
+            for (n, byte) in tx_chunk.iter_mut().enumerate() {
+                let position = offset + n;
+                *byte = match tx_buffer.get(position) {
+                    Some(byte) => *byte,
+                    None => fill_pattern[(position - tx_buffer.len()) % fill_pattern.len()],
+                };
+            }
+            offset += rx_chunk.len();
+            self.do_spi_dma_transfer(
+                DmaSlice::from_slice(tx_chunk),
+                DmaSlice::from_slice(rx_chunk),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Send a command to and read the response from a SPI slave
+    ///
+    /// Transmits `command` and then the `orc` value while receiving, all
+    /// within one chip select assertion. The bytes received while the
+    /// command is sent are discarded, the bytes following are written to
+    /// `rx_buffer`.
+    ///
+    /// The command and response together may not exceed
+    /// `FORCE_COPY_BUFFER_SIZE` bytes.
+    pub fn command_read(&mut self, command: &[u8], rx_buffer: &mut [u8]) -> Result<(), Error> {
+        slice_in_ram_or(command, Error::DMABufferNotInDataMemory)?;
+        let total = command.len() + rx_buffer.len();
+        if command.len() > FORCE_COPY_BUFFER_SIZE {
+            return Err(Error::TxBufferTooLong);
+        }
+        if total > FORCE_COPY_BUFFER_SIZE {
+            return Err(Error::RxBufferTooLong);
+        }
+        let mut buffer = [0u8; FORCE_COPY_BUFFER_SIZE];
+        self.do_spi_dma_transfer(
+            DmaSlice::from_slice(command),
+            DmaSlice::from_slice(&mut buffer[..total]),
+        )?;
+        rx_buffer.copy_from_slice(&buffer[command.len()..total]);
+        Ok(())
+    }
+
     /// Write to an SPI slave
     ///
     /// This method uses the provided chip select pin to initiate the
@@ -427,23 +1088,243 @@ where
         self.transfer_split_uneven(tx_buffer, &mut [0u8; 0])
     }
 
+    /// Receive from an SPI slave directly into a bbqueue
+    ///
+    /// Transmits `tx_buffer` and receives up to `length` bytes into a grant
+    /// from `producer`, without an intermediate buffer. The grant may be
+    /// smaller than `length` when the queue is nearly full or the free space
+    /// wraps around, then only as many bytes as fit are received. The
+    /// received bytes are committed and their count is returned. Fails with
+    /// `Error::QueueFull` if there is no room at all, nothing is transferred
+    /// then. On a transfer error nothing is committed.
+    #[cfg(feature = "queue")]
+    pub fn read_into_queue<const N: usize>(
+        &mut self,
+        producer: &mut bbqueue::Producer<'_, N>,
+        tx_buffer: &[u8],
+        length: usize,
+    ) -> Result<usize, Error> {
+        let mut grant = producer
+            .grant_max_remaining(length)
+            .map_err(|_| Error::QueueFull)?;
+        let count = grant.buf().len().min(length);
+        match self.transfer_split_uneven(tx_buffer, &mut grant.buf()[..count]) {
+            Ok(()) => {
+                grant.commit(count);
+                Ok(count)
+            }
+            Err(e) => {
+                grant.commit(0);
+                Err(e)
+            }
+        }
+    }
+
+    /// Write to an SPI slave, reporting the progress after each DMA transfer
+    ///
+    /// The data is written in chunks of `EASY_DMA_SIZE` bytes and
+    /// `on_chunk` is called with the total number of bytes written after
+    /// each chunk. Use it to feed a watchdog or update a progress indicator
+    /// during long writes.
+    pub fn write_with_progress(
+        &mut self,
+        data: &[u8],
+        mut on_chunk: impl FnMut(usize),
+    ) -> Result<(), Error> {
+        slice_in_ram_or(data, Error::DMABufferNotInDataMemory)?;
+        let mut done = 0;
+        for chunk in data.chunks(EASY_DMA_SIZE) {
+            self.spi_dma_no_copy(chunk)?;
+            done += chunk.len();
+            on_chunk(done);
+        }
+        Ok(())
+    }
+
+    /// Write to an SPI slave without checking that the buffer is in RAM
+    ///
+    /// Skips the RAM check done by `write`, for hot paths where the buffer
+    /// is known to be on the stack or in a static in RAM.
+    ///
+    /// # Safety
+    ///
+    /// `tx_buffer` must reside entirely in data RAM. EasyDMA can't read from
+    /// flash, a buffer outside RAM results in garbage being transmitted or a
+    /// bus fault.
+    pub unsafe fn write_unchecked(&mut self, tx_buffer: &[u8]) -> Result<(), Error> {
+        tx_buffer
+            .chunks(EASY_DMA_SIZE)
+            .try_for_each(|chunk| self.spi_dma_no_copy(chunk))
+    }
+
     /// Write to an SPI slave
     ///
     /// This method uses the provided chip select pin to initiate the
     /// transaction, then transmits all bytes in `tx_buffer`. All incoming
     /// bytes are discarded.
     pub fn write_dc(&mut self, tx_buffer: &[u8], command_bytes: u8) -> Result<(), Error> {
-        slice_in_ram_or(tx_buffer, Error::DMABufferNotInDataMemory)?;
-        self.do_spi_dma_transfer_dcx(
-            DmaSlice::from_slice(tx_buffer),
-            DmaSlice::null(),
-            command_bytes,
-        )
+        self.transfer_dcx(tx_buffer, command_bytes)
+    }
+
+    /// Write command and data bytes using the hardware DCX pin
+    ///
+    /// The DCX pin is held low while the first `command_bytes` bytes are
+    /// sent and high for the rest, use it for any device framing commands
+    /// and data with a D/C line, such as displays. All incoming bytes are
+    /// discarded.
+    ///
+    /// DCXCNT is a 4 bit field, 0 to 14 command bytes can be given. 15 is
+    /// special and sends the whole buffer as command bytes. Larger values
+    /// fail with `Error::CommandTooLong`.
+    ///
+    /// Only SPIM3 has a DCX pin. The other instances can send data only,
+    /// command bytes fail with `Error::UnsupportedPin(PinRole::Dcx)`.
+    pub fn transfer_dcx(&mut self, data: &[u8], command_bytes: u8) -> Result<(), Error> {
+        if command_bytes > 0x0f {
+            return Err(Error::CommandTooLong);
+        }
+        if !T::HAS_CSN_DCX && command_bytes > 0 {
+            return Err(Error::UnsupportedPin(PinRole::Dcx));
+        }
+        slice_in_ram_or(data, Error::DMABufferNotInDataMemory)?;
+        // Only the first chunk starts with command bytes, unless all of the
+        // data is commands
+        let mut command_bytes = command_bytes;
+        data.chunks(EASY_DMA_SIZE).try_for_each(|chunk| {
+            let result = self.do_spi_dma_transfer_dcx(
+                DmaSlice::from_slice(chunk),
+                DmaSlice::null(),
+                command_bytes,
+            );
+            if command_bytes != 0x0f {
+                command_bytes = 0;
+            }
+            result
+        })
+    }
+
+    /// CRC-32 of all bytes sent through `send_command_data` since creation
+    /// or the last `reset_command_crc`
+    ///
+    /// Comparing the CRC between runs tells if the same command stream was
+    /// sent to the display.
+    #[cfg(feature = "command-crc")]
+    pub fn command_crc(&self) -> u32 {
+        !self.command_crc
+    }
+
+    /// Restart the command stream CRC
+    #[cfg(feature = "command-crc")]
+    pub fn reset_command_crc(&mut self) {
+        self.command_crc = CRC32_INIT;
     }
 
     /// Return the raw interface to the underlying SPIM peripheral
     pub fn free(self) -> T {
-        self.0
+        self.spim
+    }
+}
+
+/// Any of the SPIM instances
+///
+/// Lets a struct field or board support code hold a SPI bus without being
+/// generic over the instance, for example when the instance is picked at
+/// runtime. Each call dispatches on the instance, use `Spim` directly where
+/// performance matters.
+pub enum AnySpim {
+    Spim0(Spim<SPIM0>),
+    Spim1(Spim<SPIM1>),
+    Spim2(Spim<SPIM2>),
+    Spim3(Spim<SPIM3>),
+}
+
+macro_rules! any_spim {
+    ($any:expr, $spim:ident => $call:expr) => {
+        match $any {
+            AnySpim::Spim0($spim) => $call,
+            AnySpim::Spim1($spim) => $call,
+            AnySpim::Spim2($spim) => $call,
+            AnySpim::Spim3($spim) => $call,
+        }
+    };
+}
+
+impl AnySpim {
+    /// Read and write from a SPI slave, using a single buffer
+    pub fn transfer(&mut self, buffer: &mut [u8]) -> Result<(), Error> {
+        any_spim!(self, spim => spim.transfer(buffer))
+    }
+
+    /// Read and write from a SPI slave, using separate read and write
+    /// buffers
+    pub fn transfer_split_uneven(
+        &mut self,
+        tx_buffer: &[u8],
+        rx_buffer: &mut [u8],
+    ) -> Result<(), Error> {
+        any_spim!(self, spim => spim.transfer_split_uneven(tx_buffer, rx_buffer))
+    }
+
+    /// Write to an SPI slave
+    pub fn write(&mut self, tx_buffer: &[u8]) -> Result<(), Error> {
+        any_spim!(self, spim => spim.write(tx_buffer))
+    }
+
+    /// Write command and data bytes using the hardware DCX pin
+    pub fn transfer_dcx(&mut self, data: &[u8], command_bytes: u8) -> Result<(), Error> {
+        any_spim!(self, spim => spim.transfer_dcx(data, command_bytes))
+    }
+}
+
+impl From<Spim<SPIM0>> for AnySpim {
+    fn from(spim: Spim<SPIM0>) -> Self {
+        AnySpim::Spim0(spim)
+    }
+}
+
+impl From<Spim<SPIM1>> for AnySpim {
+    fn from(spim: Spim<SPIM1>) -> Self {
+        AnySpim::Spim1(spim)
+    }
+}
+
+impl From<Spim<SPIM2>> for AnySpim {
+    fn from(spim: Spim<SPIM2>) -> Self {
+        AnySpim::Spim2(spim)
+    }
+}
+
+impl From<Spim<SPIM3>> for AnySpim {
+    fn from(spim: Spim<SPIM3>) -> Self {
+        AnySpim::Spim3(spim)
+    }
+}
+
+impl SpiSendCommandData for AnySpim {
+    type Error = Error;
+
+    fn send_command_data(&mut self, data: &[u8], command_bytes: u8) -> Result<(), Error> {
+        any_spim!(self, spim => spim.send_command_data(data, command_bytes))
+    }
+
+    fn send_command_then_data(&mut self, command: &[u8], data: &[u8]) -> Result<(), Error> {
+        any_spim!(self, spim => spim.send_command_then_data(command, data))
+    }
+}
+
+impl embedded_hal::blocking::spi::Transfer<u8> for AnySpim {
+    type Error = Error;
+
+    fn transfer<'w>(&mut self, words: &'w mut [u8]) -> Result<&'w [u8], Error> {
+        any_spim!(self, spim => embedded_hal::blocking::spi::Transfer::transfer(spim, words))
+    }
+}
+
+impl embedded_hal::blocking::spi::Write<u8> for AnySpim {
+    type Error = Error;
+
+    fn write<'w>(&mut self, words: &'w [u8]) -> Result<(), Error> {
+        any_spim!(self, spim => embedded_hal::blocking::spi::Write::write(spim, words))
     }
 }
 
@@ -469,7 +1350,69 @@ pub struct Pins {
     pub dcx: Option<Pin<Output<PushPull>>>,
 }
 
-#[derive(Debug)]
+/// Role of a pin in the SPIM interface
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum PinRole {
+    Sck,
+    Mosi,
+    Miso,
+    Csn,
+    Dcx,
+}
+
+/// Two roles assigned the same pin
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub struct PinConflict {
+    pub first: PinRole,
+    pub second: PinRole,
+}
+
+impl Pins {
+    /// Checks that all assigned pins are distinct
+    pub fn validate(&self) -> Result<(), PinConflict> {
+        let pins = [
+            (PinRole::Sck, Some((self.sck.port(), self.sck.pin()))),
+            (
+                PinRole::Mosi,
+                self.mosi.as_ref().map(|p| (p.port(), p.pin())),
+            ),
+            (
+                PinRole::Miso,
+                self.miso.as_ref().map(|p| (p.port(), p.pin())),
+            ),
+            (PinRole::Csn, self.csn.as_ref().map(|p| (p.port(), p.pin()))),
+            (PinRole::Dcx, self.dcx.as_ref().map(|p| (p.port(), p.pin()))),
+        ];
+        for (n, (first, a)) in pins.iter().enumerate() {
+            for (second, b) in pins[n + 1..].iter() {
+                if let (Some(a), Some(b)) = (a, b) {
+                    if port_to_bool(a.0) == port_to_bool(b.0) && a.1 == b.1 {
+                        return Err(PinConflict {
+                            first: *first,
+                            second: *second,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Part of a `Spim::transaction`
+pub enum Phase<'a> {
+    /// Transmits the bytes, the received bytes are discarded
+    Write(&'a [u8]),
+    /// Transmits the bytes with DCX low for the given number of command
+    /// bytes, as with `Spim::transfer_dcx`
+    WriteDcx(&'a [u8], u8),
+    /// Receives into the buffer while transmitting the `orc` value
+    Read(&'a mut [u8]),
+    /// Transmits the buffer and receives into it in place
+    Transfer(&'a mut [u8]),
+}
+
+#[derive(Debug, defmt::Format)]
 pub enum Error {
     TxBufferTooLong,
     RxBufferTooLong,
@@ -477,12 +1420,44 @@ pub enum Error {
     DMABufferNotInDataMemory,
     Transmit,
     Receive,
+    /// Failed to drive the chip select pin
+    ChipSelect,
+    /// The same pin is assigned to two roles
+    PinConflict(PinConflict),
+    /// More command bytes than the DCX counter can hold
+    CommandTooLong,
+    /// The pin role isn't supported by the SPIM instance
+    UnsupportedPin(PinRole),
+    /// There is no room in the queue
+    #[cfg(feature = "queue")]
+    QueueFull,
+}
+
+/// Error from `Spim::new`, holding the SPIM peripheral so that it can be
+/// used again
+pub struct NewError<T> {
+    pub error: Error,
+    pub spim: T,
+}
+
+impl<T> core::fmt::Debug for NewError<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(&self.error, f)
+    }
 }
 
 /// Implemented by all SPIM instances
-pub trait Instance: Deref<Target = spim0::RegisterBlock> {}
+pub trait Instance: Deref<Target = spim0::RegisterBlock> {
+    /// Does the instance support the hardware chip select and the DCX pin?
+    ///
+    /// On the nRF52833 only SPIM3 has these, on the other instances the
+    /// registers exist in the register block but are ignored.
+    const HAS_CSN_DCX: bool = false;
+}
 
 impl Instance for SPIM0 {}
 impl Instance for SPIM1 {}
 impl Instance for SPIM2 {}
-impl Instance for SPIM3 {}
+impl Instance for SPIM3 {
+    const HAS_CSN_DCX: bool = true;
+}
@@ -0,0 +1,211 @@
+//! SSD1306 driver for monochrome OLED displays connected over SPI.
+
+use crate::extended_enum;
+use crate::spi::SpiSendCommandData;
+pub use crate::st7735s::DisplayError;
+
+extended_enum!(
+/// SSD1306 instructions.
+Instruction, u8,
+NOP => 0xE3,
+SETCONTRAST => 0x81,
+DISPLAYRESUME => 0xA4,
+NORMALDISPLAY => 0xA6,
+INVERTDISPLAY => 0xA7,
+DISPLAYOFF => 0xAE,
+DISPLAYON => 0xAF,
+MEMORYMODE => 0x20,
+COLUMNADDR => 0x21,
+PAGEADDR => 0x22,
+SETSTARTLINE => 0x40,
+SEGREMAP => 0xA1,
+SETMULTIPLEX => 0xA8,
+COMSCANDEC => 0xC8,
+SETDISPLAYOFFSET => 0xD3,
+SETCLOCKDIV => 0xD5,
+SETPRECHARGE => 0xD9,
+SETCOMPINS => 0xDA,
+SETVCOMDETECT => 0xDB,
+CHARGEPUMP => 0x8D,
+);
+
+/// Largest supported display width in pixels
+pub const SSD1306_COLS: u32 = 128;
+/// Largest supported display height in pixels
+pub const SSD1306_ROWS: u32 = 64;
+
+/// Size of the page buffer, each byte holds eight vertical pixels
+const BUFFER_SIZE: usize = (SSD1306_COLS * SSD1306_ROWS / 8) as usize;
+
+/// Position of a pixel in the page buffer, as byte index and bit mask
+///
+/// The display memory is organised in pages of eight rows, each byte holds
+/// one column of a page with the top row in the least significant bit.
+fn buffer_position(x: u32, y: u32, width: u32) -> (usize, u8) {
+    let index = (x + (y / 8) * width) as usize;
+    (index, 1 << (y % 8))
+}
+
+/// SSD1306 driver with a page buffer
+///
+/// Drawing only updates the buffer in RAM, call `flush` to send it to the
+/// display. Commands are sent with the data / command line low and the
+/// buffer with it high, using the same `SpiSendCommandData` interface as
+/// the ST7735 driver.
+pub struct Ssd1306<SPI>
+where
+    SPI: SpiSendCommandData,
+{
+    /// SPI
+    spi: SPI,
+
+    /// Page buffer
+    buffer: [u8; BUFFER_SIZE],
+
+    width: u32,
+    height: u32,
+}
+
+impl<SPI> Ssd1306<SPI>
+where
+    SPI: SpiSendCommandData,
+{
+    /// Creates a new driver instance, `width` and `height` are clamped to
+    /// 1 x 8 up to 128 x 64 and the height is rounded down to whole pages.
+    pub fn new(spi: SPI, width: u32, height: u32) -> Self {
+        Ssd1306 {
+            spi,
+            buffer: [0u8; BUFFER_SIZE],
+            width: width.max(1).min(SSD1306_COLS),
+            height: (height.max(8).min(SSD1306_ROWS) / 8) * 8,
+        }
+    }
+
+    /// Runs commands to initialize the display and clears it.
+    ///
+    /// The charge pump is enabled, so the panel is powered from the logic
+    /// supply.
+    pub fn init(&mut self) -> Result<(), DisplayError> {
+        // COM pins are sequential on 32 row panels and alternate otherwise
+        let com_pins = if self.height <= 32 { 0x02 } else { 0x12 };
+        self.write_command(Instruction::DISPLAYOFF, &[])?;
+        self.write_command(Instruction::SETCLOCKDIV, &[0x80])?;
+        self.write_command(Instruction::SETMULTIPLEX, &[self.height as u8 - 1])?;
+        self.write_command(Instruction::SETDISPLAYOFFSET, &[0x00])?;
+        self.write_command(Instruction::SETSTARTLINE, &[])?;
+        self.write_command(Instruction::CHARGEPUMP, &[0x14])?;
+        // Horizontal addressing, the buffer is written in one go
+        self.write_command(Instruction::MEMORYMODE, &[0x00])?;
+        self.write_command(Instruction::SEGREMAP, &[])?;
+        self.write_command(Instruction::COMSCANDEC, &[])?;
+        self.write_command(Instruction::SETCOMPINS, &[com_pins])?;
+        self.write_command(Instruction::SETCONTRAST, &[0xCF])?;
+        self.write_command(Instruction::SETPRECHARGE, &[0xF1])?;
+        self.write_command(Instruction::SETVCOMDETECT, &[0x40])?;
+        self.write_command(Instruction::DISPLAYRESUME, &[])?;
+        self.write_command(Instruction::NORMALDISPLAY, &[])?;
+        self.clear_buffer();
+        self.flush()?;
+        self.write_command(Instruction::DISPLAYON, &[])
+    }
+
+    /// Sends a command, the command and its parameters are all sent with the
+    /// data / command line low.
+    fn write_command(&mut self, command: Instruction, params: &[u8]) -> Result<(), DisplayError> {
+        let mut spi_data = [0u8; 8];
+        spi_data[0] = u8::from(command);
+        let octets = params.len() + 1;
+        if octets > spi_data.len() {
+            return Err(DisplayError::OutOfBounds);
+        }
+        spi_data[1..octets].copy_from_slice(params);
+        self.spi
+            .send_command_data(&spi_data[..octets], octets as u8)
+            .map_err(|_| DisplayError::Interface)
+    }
+
+    /// Sets the contrast, 0 to 255
+    pub fn set_contrast(&mut self, contrast: u8) -> Result<(), DisplayError> {
+        self.write_command(Instruction::SETCONTRAST, &[contrast])
+    }
+
+    /// Inverts the display, lit pixels are shown dark
+    pub fn set_inverted(&mut self, inverted: bool) -> Result<(), DisplayError> {
+        if inverted {
+            self.write_command(Instruction::INVERTDISPLAY, &[])
+        } else {
+            self.write_command(Instruction::NORMALDISPLAY, &[])
+        }
+    }
+
+    /// Turns the display on or off, the display memory is kept when off
+    pub fn set_display_on(&mut self, on: bool) -> Result<(), DisplayError> {
+        if on {
+            self.write_command(Instruction::DISPLAYON, &[])
+        } else {
+            self.write_command(Instruction::DISPLAYOFF, &[])
+        }
+    }
+
+    /// Clears the page buffer, the display is updated on the next `flush`
+    pub fn clear_buffer(&mut self) {
+        self.buffer = [0u8; BUFFER_SIZE];
+    }
+
+    /// Sets a pixel in the page buffer, pixels outside the display are
+    /// ignored
+    pub fn set_pixel(&mut self, x: u32, y: u32, on: bool) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let (index, mask) = buffer_position(x, y, self.width);
+        if on {
+            self.buffer[index] |= mask;
+        } else {
+            self.buffer[index] &= !mask;
+        }
+    }
+
+    /// Sends the page buffer to the display
+    pub fn flush(&mut self) -> Result<(), DisplayError> {
+        let pages = (self.height / 8) as u8;
+        self.write_command(Instruction::COLUMNADDR, &[0, self.width as u8 - 1])?;
+        self.write_command(Instruction::PAGEADDR, &[0, pages - 1])?;
+        let size = (self.width * self.height / 8) as usize;
+        self.spi
+            .send_command_data(&self.buffer[..size], 0)
+            .map_err(|_| DisplayError::Interface)
+    }
+
+    /// Returns the SPI interface
+    pub fn free(self) -> SPI {
+        self.spi
+    }
+}
+
+use embedded_graphics::{drawable::Pixel, pixelcolor::BinaryColor, prelude::*, DrawTarget};
+
+impl<SPI> DrawTarget<BinaryColor> for Ssd1306<SPI>
+where
+    SPI: SpiSendCommandData,
+{
+    type Error = DisplayError;
+
+    fn draw_pixel(&mut self, pixel: Pixel<BinaryColor>) -> Result<(), Self::Error> {
+        let Pixel(Point { x, y }, color) = pixel;
+        if x >= 0 && y >= 0 {
+            self.set_pixel(x as u32, y as u32, color.is_on());
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+
+    fn clear(&mut self, color: BinaryColor) -> Result<(), Self::Error> {
+        let fill = if color.is_on() { 0xff } else { 0x00 };
+        self.buffer = [fill; BUFFER_SIZE];
+        Ok(())
+    }
+}
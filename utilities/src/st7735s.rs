@@ -22,6 +22,8 @@ RASET => 0x2B,
 RAMWR => 0x2C,
 RAMRD => 0x2E,
 PTLAR => 0x30,
+TEOFF => 0x34,
+TEON => 0x35,
 COLMOD => 0x3A,
 MADCTL => 0x36,
 FRMCTR1 => 0xB1,
@@ -44,15 +46,345 @@ GMCTRP1 => 0xE0,
 GMCTRN1 => 0xE1,
 );
 
+/// Delay after commands without a known delay requirement, in milliseconds
+pub const DEFAULT_COMMAND_DELAY_MS: u16 = 200;
+
+/// Instructions which need no delay before the next command
+const NO_DELAY_INSTRUCTIONS: [Instruction; 27] = [
+    Instruction::NOP,
+    Instruction::PTLON,
+    Instruction::INVOFF,
+    Instruction::INVON,
+    Instruction::DISPOFF,
+    Instruction::CASET,
+    Instruction::RASET,
+    Instruction::RAMWR,
+    Instruction::PTLAR,
+    Instruction::TEOFF,
+    Instruction::TEON,
+    Instruction::COLMOD,
+    Instruction::MADCTL,
+    Instruction::FRMCTR1,
+    Instruction::FRMCTR2,
+    Instruction::FRMCTR3,
+    Instruction::INVCTR,
+    Instruction::DISSET5,
+    Instruction::PWCTR1,
+    Instruction::PWCTR2,
+    Instruction::PWCTR3,
+    Instruction::PWCTR4,
+    Instruction::PWCTR5,
+    Instruction::VMCTR1,
+    Instruction::PWCTR6,
+    Instruction::GMCTRP1,
+    Instruction::GMCTRN1,
+];
+
+/// Delay required after a command before the next command is sent, in
+/// milliseconds
+///
+/// The delays are the datasheet minimums, a software reset and sleep in or
+/// out need 120 ms. Commands without a known requirement get the
+/// conservative `DEFAULT_COMMAND_DELAY_MS`.
+pub fn command_delay_ms(command: u8) -> u16 {
+    if command == Instruction::SWRESET
+        || command == Instruction::SLPOUT
+        || command == Instruction::SLPIN
+    {
+        120
+    } else if command == Instruction::DISPON {
+        100
+    } else if command == Instruction::NORON {
+        10
+    } else if NO_DELAY_INSTRUCTIONS.iter().any(|i| command == *i) {
+        0
+    } else {
+        DEFAULT_COMMAND_DELAY_MS
+    }
+}
+
+/// MADCTL row address order bit
+const MADCTL_MY: u8 = 0x80;
+/// MADCTL column address order bit
+const MADCTL_MX: u8 = 0x40;
+/// MADCTL row / column exchange bit
+const MADCTL_MV: u8 = 0x20;
+/// MADCTL vertical refresh order bit
+const MADCTL_ML: u8 = 0x10;
+/// MADCTL BGR colour order bit
+const MADCTL_BGR: u8 = 0x08;
+/// MADCTL horizontal refresh order bit
+const MADCTL_MH: u8 = 0x04;
+
+/// Memory data access control (MADCTL) flags
+///
+/// Each flag is one bit of the MADCTL register, combine them with `|`.
+///
+/// Text that reads mirrored is fixed by toggling `MX` in the portrait
+/// orientations and `MY` in the landscape orientations, as `MV` exchanges
+/// the axes. Red and blue swapped is fixed by toggling `BGR`. `ML` and `MH`
+/// only change the refresh direction, which shows as tearing when drawing
+/// while the panel refreshes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MadctlFlags(u8);
+
+impl MadctlFlags {
+    /// Row address order, mirrors the rows
+    pub const MY: Self = Self(MADCTL_MY);
+    /// Column address order, mirrors the columns
+    pub const MX: Self = Self(MADCTL_MX);
+    /// Row / column exchange, rotates into landscape
+    pub const MV: Self = Self(MADCTL_MV);
+    /// Vertical refresh order, bottom to top
+    pub const ML: Self = Self(MADCTL_ML);
+    /// BGR colour order
+    pub const BGR: Self = Self(MADCTL_BGR);
+    /// Horizontal refresh order, right to left
+    pub const MH: Self = Self(MADCTL_MH);
+
+    /// No flags set
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Flags from the MADCTL byte, the unused low bits are dropped
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits & 0xfc)
+    }
+
+    /// The MADCTL byte
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Are all flags in `other` set?
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Sets the flags in `other`
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    /// Clears the flags in `other`
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+
+    /// Toggles the flags in `other`
+    pub fn toggle(&mut self, other: Self) {
+        self.0 ^= other.0;
+    }
+
+    /// Sets or clears the flags in `other`
+    pub fn set(&mut self, other: Self, value: bool) {
+        if value {
+            self.insert(other);
+        } else {
+            self.remove(other);
+        }
+    }
+}
+
+impl core::ops::BitOr for MadctlFlags {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl core::ops::BitOrAssign for MadctlFlags {
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+/// Columns in the ST7735 frame memory
 pub const ST7735_COLS: u16 = 132;
+/// Rows in the ST7735 frame memory
 pub const ST7735_ROWS: u16 = 162;
 
-use embedded_hal::blocking::delay::DelayMs;
+use core::convert::Infallible;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+
+use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+use embedded_hal::digital::v2::InputPin;
+
+/// Default number of pixels written per SPI transfer
+pub const DEFAULT_FLUSH_CHUNK: usize = 128;
+/// Largest number of pixels written per SPI transfer
+///
+/// Pixels are packed into a stack buffer of this many pixels, 1 KB, before
+/// being sent.
+pub const MAX_FLUSH_CHUNK: usize = 512;
+
+/// Positive gamma correction used by Adafruit for its 1.8" 128 x 160 panel
+pub const ADAFRUIT_GAMMA_POSITIVE: [u8; 16] = [
+    0x02, 0x1C, 0x07, 0x12, 0x37, 0x32, 0x29, 0x2D, 0x29, 0x25, 0x2B, 0x39, 0x00, 0x01, 0x03, 0x10,
+];
+/// Negative gamma correction used by Adafruit for its 1.8" 128 x 160 panel
+pub const ADAFRUIT_GAMMA_NEGATIVE: [u8; 16] = [
+    0x03, 0x1D, 0x07, 0x06, 0x2E, 0x2C, 0x29, 0x2D, 0x2E, 0x2E, 0x37, 0x3F, 0x00, 0x00, 0x02, 0x10,
+];
+
+/// Longest wait for the TE line in `wait_for_te`, about three refresh
+/// periods at the default frame rate
+pub const TE_TIMEOUT_US: u32 = 50_000;
+/// Interval between reads of the TE line in `wait_for_te`
+const TE_POLL_US: u16 = 10;
+
+/// Number of pixels in the buffer sent repeatedly by `fill_solid`
+pub const FILL_BUFFER_PIXELS: usize = 256;
+
+/// Largest number of regions flushed by one `flush_regions` call
+pub const MAX_FLUSH_REGIONS: usize = 16;
+
+/// ST7735 driver errors
+#[derive(Clone, Copy, Debug, PartialEq, defmt::Format)]
+pub enum DisplayError {
+    /// Failed to send over the SPI interface
+    Interface,
+    /// The window or data doesn't fit the display or the command buffer
+    OutOfBounds,
+    /// The operation isn't supported by the display
+    Unsupported,
+}
+
+/// Converts to the unit error used by earlier versions of the driver
+impl From<DisplayError> for () {
+    fn from(_: DisplayError) -> Self {}
+}
+
+/// Command sent while initializing a panel
+pub struct PanelCommand {
+    /// Command byte
+    pub command: u8,
+    /// Command parameters
+    pub params: &'static [u8],
+    /// Delay after the command, in milliseconds
+    pub delay_ms: u16,
+}
+
+/// Panel specific configuration of a controller in the ST7735 family
+///
+/// The controllers share the MIPI DCS command set for windowing, memory
+/// access control and pixel writes, they differ in the frame memory size,
+/// the pixel format value and the power and frame rate setup.
+pub trait Panel {
+    /// Columns in the frame memory, in portrait orientation
+    const MEMORY_COLS: u16;
+    /// Rows in the frame memory, in portrait orientation
+    const MEMORY_ROWS: u16;
+    /// COLMOD parameter selecting 16 bits per pixel
+    const COLMOD: u8;
+    /// Positive and negative gamma correction set by `init`, if any
+    const GAMMA: Option<(&'static [u8; 16], &'static [u8; 16])>;
+    /// Commands sent by `init` after leaving sleep, before the inversion,
+    /// memory access control and pixel format are set
+    const INIT: &'static [PanelCommand];
+}
+
+/// ST7735S panel configuration
+pub struct St7735Panel;
+
+impl Panel for St7735Panel {
+    const MEMORY_COLS: u16 = ST7735_COLS;
+    const MEMORY_ROWS: u16 = ST7735_ROWS;
+    const COLMOD: u8 = 0x05;
+    const GAMMA: Option<(&'static [u8; 16], &'static [u8; 16])> =
+        Some((&ADAFRUIT_GAMMA_POSITIVE, &ADAFRUIT_GAMMA_NEGATIVE));
+    const INIT: &'static [PanelCommand] = &[
+        PanelCommand {
+            // FRMCTR1
+            command: 0xB1,
+            params: &[0x01, 0x2C, 0x2D],
+            delay_ms: 0,
+        },
+        PanelCommand {
+            // FRMCTR2
+            command: 0xB2,
+            params: &[0x01, 0x2C, 0x2D],
+            delay_ms: 0,
+        },
+        PanelCommand {
+            // FRMCTR3
+            command: 0xB3,
+            params: &[0x01, 0x2C, 0x2D, 0x01, 0x2C, 0x2D],
+            delay_ms: 0,
+        },
+        PanelCommand {
+            // INVCTR
+            command: 0xB4,
+            params: &[0x07],
+            delay_ms: 0,
+        },
+        PanelCommand {
+            // PWCTR1
+            command: 0xC0,
+            params: &[0xA2, 0x02, 0x84],
+            delay_ms: 0,
+        },
+        PanelCommand {
+            // PWCTR2
+            command: 0xC1,
+            params: &[0xC5],
+            delay_ms: 0,
+        },
+        PanelCommand {
+            // PWCTR3
+            command: 0xC2,
+            params: &[0x0A, 0x00],
+            delay_ms: 0,
+        },
+        PanelCommand {
+            // PWCTR4
+            command: 0xC3,
+            params: &[0x8A, 0x2A],
+            delay_ms: 0,
+        },
+        PanelCommand {
+            // PWCTR5
+            command: 0xC4,
+            params: &[0x8A, 0xEE],
+            delay_ms: 0,
+        },
+        PanelCommand {
+            // VMCTR1
+            command: 0xC5,
+            params: &[0x0E],
+            delay_ms: 0,
+        },
+    ];
+}
 
 /// ST7735 driver to connect to TFT displays.
-pub struct ST7735<SPI>
+pub type ST7735<SPI> = TftDisplay<SPI, St7735Panel>;
+
+/// Placeholder for the TE pin of a display without the TE line wired
+pub struct NoTePin;
+
+impl InputPin for NoTePin {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Infallible> {
+        Ok(false)
+    }
+
+    fn is_low(&self) -> Result<bool, Infallible> {
+        Ok(true)
+    }
+}
+
+/// Driver for TFT displays with a controller in the ST7735 family, the
+/// panel specifics are given by `P` and the TE input pin by `TE`
+pub struct TftDisplay<SPI, P, TE = NoTePin>
 where
     SPI: crate::spi::SpiSendCommandData,
+    P: Panel,
+    TE: InputPin,
 {
     /// SPI
     spi: SPI,
@@ -63,77 +395,435 @@ where
     /// Whether the colours are inverted (true) or not (false)
     inverted: bool,
 
+    /// Current display orientation
+    orientation: Orientation,
+
+    /// Mirror the image horizontally (true) or not (false)
+    flip_horizontal: bool,
+
+    /// Mirror the image vertically (true) or not (false)
+    flip_vertical: bool,
+
+    /// Memory access control written to the display
+    madctl: MadctlFlags,
+
+    /// Drawing is clipped to this rectangle when set
+    clip: Option<Rectangle>,
+
+    /// Number of pixels written per SPI transfer
+    flush_chunk: usize,
+
+    /// Handling of windows outside the panel
+    clip_mode: ClipMode,
+
+    /// Set the panel default gamma correction in `init`
+    default_gamma: bool,
+
+    /// Whether the controller is in sleep mode, as it is after reset
+    sleeping: bool,
+
+    /// Whether the display output is on
+    display_on: bool,
+
+    /// Tearing effect input, when wired
+    te_pin: Option<TE>,
+
+    /// Tearing effect output mode of the display, off when `None`
+    tearing: Option<TearingMode>,
+
+    /// Size of the controller frame memory, in portrait orientation
+    memory_cols: u16,
+    memory_rows: u16,
+
     /// Global image offset
     dx: u16,
     dy: u16,
     width: u32,
     height: u32,
+
+    panel: PhantomData<P>,
 }
 
 extended_enum!(
     /// Display orientation.
+    ///
+    /// The values are the MADCTL row / column exchange (MV), column order
+    /// (MX) and row order (MY) bits for each rotation, as given in the
+    /// ST7735S datasheet. Panels are mounted differently, if the image comes
+    /// out upside-down use the swapped orientation or `flip` both axes.
     Orientation, u8,
+    /// No rotation
     Portrait => 0x00,
+    /// Rotated 90 degrees clockwise, MV | MX
     Landscape => 0x60,
+    /// Rotated 180 degrees, MY | MX
     PortraitSwapped => 0xC0,
+    /// Rotated 270 degrees clockwise, MV | MY
     LandscapeSwapped => 0xA0,
 );
 
-impl<SPI> ST7735<SPI>
+/// Tearing effect output mode
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TearingMode {
+    /// TE pulses high during vertical blanking
+    VBlank,
+    /// TE pulses high during both vertical and horizontal blanking
+    VHBlank,
+}
+
+/// Handling of drawing outside the panel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClipMode {
+    /// Windows are clamped to the panel, pixels outside are dropped
+    Clamp,
+    /// Windows outside the panel fail with `DisplayError::OutOfBounds`
+    Error,
+}
+
+impl Orientation {
+    /// Are rows and columns exchanged?
+    pub fn is_landscape(self) -> bool {
+        u8::from(self) & MADCTL_MV == MADCTL_MV
+    }
+
+    /// The MADCTL byte for this orientation, with mirroring and colour
+    /// order applied
+    pub fn madctl(self, flip_horizontal: bool, flip_vertical: bool, rgb: bool) -> u8 {
+        let mut madctl = u8::from(self);
+        if flip_horizontal {
+            madctl ^= MADCTL_MX;
+        }
+        if flip_vertical {
+            madctl ^= MADCTL_MY;
+        }
+        if !rgb {
+            madctl |= MADCTL_BGR;
+        }
+        madctl
+    }
+}
+
+impl<SPI, P> TftDisplay<SPI, P>
 where
     SPI: crate::spi::SpiSendCommandData,
+    P: Panel,
 {
     /// Creates a new driver instance that uses hardware SPI.
     pub fn new(spi: SPI, rgb: bool, inverted: bool, width: u32, height: u32) -> Self {
-        ST7735 {
+        TftDisplay {
             spi,
             rgb,
             inverted,
+            orientation: Orientation::Portrait,
+            flip_horizontal: false,
+            flip_vertical: false,
+            madctl: MadctlFlags::from_bits(Orientation::Portrait.madctl(false, false, rgb)),
+            clip: None,
+            flush_chunk: DEFAULT_FLUSH_CHUNK,
+            clip_mode: ClipMode::Clamp,
+            default_gamma: true,
+            sleeping: true,
+            display_on: false,
+            te_pin: None,
+            tearing: None,
+            memory_cols: P::MEMORY_COLS,
+            memory_rows: P::MEMORY_ROWS,
             dx: 0,
             dy: 0,
             width,
             height,
+            panel: PhantomData,
+        }
+    }
+
+    /// Creates a new driver instance that uses hardware SPI, the
+    /// orientation is applied when the display is initialized.
+    ///
+    /// `width` and `height` are given in portrait orientation and are
+    /// swapped for the landscape orientations.
+    pub fn new_with_orientation(
+        spi: SPI,
+        rgb: bool,
+        inverted: bool,
+        width: u32,
+        height: u32,
+        orientation: Orientation,
+    ) -> Self {
+        let (width, height) = if orientation.is_landscape() {
+            (height, width)
+        } else {
+            (width, height)
+        };
+        TftDisplay {
+            orientation,
+            madctl: MadctlFlags::from_bits(orientation.madctl(false, false, rgb)),
+            ..Self::new(spi, rgb, inverted, width, height)
         }
     }
 
+    /// Sets the pin wired to the TE output of the display, see
+    /// `wait_for_te`
+    pub fn with_te_pin<TE: InputPin>(self, pin: TE) -> TftDisplay<SPI, P, TE> {
+        TftDisplay {
+            spi: self.spi,
+            rgb: self.rgb,
+            inverted: self.inverted,
+            orientation: self.orientation,
+            flip_horizontal: self.flip_horizontal,
+            flip_vertical: self.flip_vertical,
+            madctl: self.madctl,
+            clip: self.clip,
+            flush_chunk: self.flush_chunk,
+            clip_mode: self.clip_mode,
+            default_gamma: self.default_gamma,
+            sleeping: self.sleeping,
+            display_on: self.display_on,
+            te_pin: Some(pin),
+            tearing: self.tearing,
+            memory_cols: self.memory_cols,
+            memory_rows: self.memory_rows,
+            dx: self.dx,
+            dy: self.dy,
+            width: self.width,
+            height: self.height,
+            panel: PhantomData,
+        }
+    }
+}
+
+impl<SPI, P, TE> TftDisplay<SPI, P, TE>
+where
+    SPI: crate::spi::SpiSendCommandData,
+    P: Panel,
+    TE: InputPin,
+{
     /// Runs commands to initialize the display.
-    pub fn init<DELAY>(&mut self, delay: &mut DELAY) -> Result<(), ()>
+    ///
+    /// The delay is given in milliseconds as `u16` so that delays longer than
+    /// 255 ms can be expressed.
+    pub fn init<DELAY>(&mut self, delay: &mut DELAY) -> Result<(), DisplayError>
     where
-        DELAY: DelayMs<u8>,
+        DELAY: DelayMs<u16>,
     {
-        self.write_command(Instruction::SWRESET, &[])?;
-        delay.delay_ms(200);
-        self.write_command(Instruction::SLPOUT, &[])?;
-        delay.delay_ms(200);
-        self.write_command(Instruction::FRMCTR1, &[0x01, 0x2C, 0x2D])?;
-        self.write_command(Instruction::FRMCTR2, &[0x01, 0x2C, 0x2D])?;
-        self.write_command(Instruction::FRMCTR3, &[0x01, 0x2C, 0x2D, 0x01, 0x2C, 0x2D])?;
-        self.write_command(Instruction::INVCTR, &[0x07])?;
-        self.write_command(Instruction::PWCTR1, &[0xA2, 0x02, 0x84])?;
-        self.write_command(Instruction::PWCTR2, &[0xC5])?;
-        self.write_command(Instruction::PWCTR3, &[0x0A, 0x00])?;
-        self.write_command(Instruction::PWCTR4, &[0x8A, 0x2A])?;
-        self.write_command(Instruction::PWCTR5, &[0x8A, 0xEE])?;
-        self.write_command(Instruction::VMCTR1, &[0x0E])?;
+        self.write_command_timed(Instruction::SWRESET, &[], delay)?;
+        self.write_command_timed(Instruction::SLPOUT, &[], delay)?;
+        self.sleeping = false;
+        for command in P::INIT {
+            self.send_raw(command.command, command.params)?;
+            if command.delay_ms > 0 {
+                delay.delay_ms(command.delay_ms);
+            }
+        }
         if self.inverted {
             self.write_command(Instruction::INVON, &[])?;
         } else {
             self.write_command(Instruction::INVOFF, &[])?;
         }
-        if self.rgb {
-            self.write_command(Instruction::MADCTL, &[0x00])?;
-        } else {
-            self.write_command(Instruction::MADCTL, &[0x08])?;
+        if self.default_gamma {
+            if let Some((positive, negative)) = P::GAMMA {
+                self.set_gamma(positive, negative)?;
+            }
+        }
+        self.write_madctl()?;
+        self.write_command(Instruction::COLMOD, &[P::COLMOD])?;
+        self.write_command_timed(Instruction::DISPON, &[], delay)?;
+        self.display_on = true;
+        Ok(())
+    }
+
+    /// Turns the display off and puts the controller in sleep mode
+    ///
+    /// The frame memory is kept. The controller needs 120 ms after entering
+    /// sleep before it accepts another sleep command, so this waits before
+    /// returning. Does nothing if the controller already sleeps.
+    pub fn sleep<DELAY>(&mut self, delay: &mut DELAY) -> Result<(), DisplayError>
+    where
+        DELAY: DelayMs<u16>,
+    {
+        if self.sleeping {
+            return Ok(());
+        }
+        self.display_off()?;
+        self.write_command_timed(Instruction::SLPIN, &[], delay)?;
+        self.sleeping = true;
+        Ok(())
+    }
+
+    /// Wakes the controller from sleep mode and turns the display on
+    ///
+    /// Waits the 120 ms the controller needs to leave sleep before the
+    /// display is turned on. Does nothing if the controller is awake.
+    pub fn wake<DELAY>(&mut self, delay: &mut DELAY) -> Result<(), DisplayError>
+    where
+        DELAY: DelayMs<u16>,
+    {
+        if !self.sleeping {
+            return Ok(());
+        }
+        self.write_command_timed(Instruction::SLPOUT, &[], delay)?;
+        self.sleeping = false;
+        self.display_on()
+    }
+
+    /// Is the controller in sleep mode?
+    pub fn is_sleeping(&self) -> bool {
+        self.sleeping
+    }
+
+    /// Turns the display output on, showing the frame memory
+    pub fn display_on(&mut self) -> Result<(), DisplayError> {
+        if self.display_on {
+            return Ok(());
         }
-        self.write_command(Instruction::COLMOD, &[0x05])?;
         self.write_command(Instruction::DISPON, &[])?;
-        delay.delay_ms(200);
+        self.display_on = true;
+        Ok(())
+    }
+
+    /// Turns the display output off, the panel is blank but the frame
+    /// memory can still be written
+    pub fn display_off(&mut self) -> Result<(), DisplayError> {
+        if !self.display_on {
+            return Ok(());
+        }
+        self.write_command(Instruction::DISPOFF, &[])?;
+        self.display_on = false;
+        Ok(())
+    }
+
+    /// Is the display output on?
+    pub fn is_display_on(&self) -> bool {
+        self.display_on
+    }
+
+    /// Turns the tearing effect output of the display on or off
+    ///
+    /// The display pulses its TE line high while blanking, so frames can be
+    /// written while the panel isn't being refreshed, see `wait_for_te`.
+    pub fn set_tearing_effect(&mut self, mode: Option<TearingMode>) -> Result<(), DisplayError> {
+        match mode {
+            None => self.write_command(Instruction::TEOFF, &[])?,
+            Some(TearingMode::VBlank) => self.write_command(Instruction::TEON, &[0x00])?,
+            Some(TearingMode::VHBlank) => self.write_command(Instruction::TEON, &[0x01])?,
+        }
+        self.tearing = mode;
+        Ok(())
+    }
+
+    /// Returns the TE pin, `wait_for_te` no longer waits
+    pub fn take_te_pin(&mut self) -> Option<TE> {
+        self.te_pin.take()
+    }
+
+    /// Waits for the start of the next blanking period
+    ///
+    /// Call this before writing a full frame, so that the frame is written
+    /// behind the refresh and doesn't tear. Blocks until the rising edge of
+    /// the TE line, normally at most one refresh period, and gives up after
+    /// `TE_TIMEOUT_US` in case the line doesn't toggle. Returns at once when
+    /// no TE pin is set, the tearing effect output is off or the display is
+    /// sleeping or off. Returns true if the edge was seen.
+    pub fn wait_for_te<DELAY>(&self, delay: &mut DELAY) -> bool
+    where
+        DELAY: DelayUs<u16>,
+    {
+        let pin = match &self.te_pin {
+            Some(pin) if self.tearing.is_some() && !self.sleeping && self.display_on => pin,
+            _ => return false,
+        };
+        let mut polls = TE_TIMEOUT_US / u32::from(TE_POLL_US);
+        // Waits while the line is at `high`, false on timeout
+        let mut wait_while = |high: bool| {
+            while pin.is_high().ok() == Some(high) {
+                if polls == 0 {
+                    return false;
+                }
+                polls -= 1;
+                delay.delay_us(TE_POLL_US);
+            }
+            true
+        };
+        wait_while(true) && wait_while(false)
+    }
+
+    /// Sets whether `init` applies the default gamma correction of the
+    /// panel, enabled by default
+    ///
+    /// Disable it to keep the gamma the controller comes out of reset with.
+    pub fn set_default_gamma(&mut self, enabled: bool) {
+        self.default_gamma = enabled;
+    }
+
+    /// Sets the positive and negative gamma correction curves
+    ///
+    /// Both take 16 parameters, as described for `GMCTRP1` and `GMCTRN1` in
+    /// the ST7735S datasheet. `ADAFRUIT_GAMMA_POSITIVE` and
+    /// `ADAFRUIT_GAMMA_NEGATIVE` work well on the common 1.8" panels and are
+    /// the defaults applied by `init`.
+    pub fn set_gamma(
+        &mut self,
+        positive: &[u8; 16],
+        negative: &[u8; 16],
+    ) -> Result<(), DisplayError> {
+        self.write_command(Instruction::GMCTRP1, positive)?;
+        self.write_command(Instruction::GMCTRN1, negative)
+    }
+
+    fn write_command(&mut self, command: Instruction, params: &[u8]) -> Result<(), DisplayError> {
+        self.send_raw(u8::from(command), params)
+    }
+
+    /// Sends a command and waits the delay it requires, see
+    /// `command_delay_ms`
+    fn write_command_timed<DELAY>(
+        &mut self,
+        command: Instruction,
+        params: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), DisplayError>
+    where
+        DELAY: DelayMs<u16>,
+    {
+        self.send_raw_timed(u8::from(command), params, delay)
+    }
+
+    /// Sends any command byte with parameters to the display and waits the
+    /// delay it requires, see `command_delay_ms`
+    ///
+    /// Unknown commands are followed by the conservative
+    /// `DEFAULT_COMMAND_DELAY_MS`.
+    pub fn send_raw_timed<DELAY>(
+        &mut self,
+        command: u8,
+        params: &[u8],
+        delay: &mut DELAY,
+    ) -> Result<(), DisplayError>
+    where
+        DELAY: DelayMs<u16>,
+    {
+        self.send_raw(command, params)?;
+        let delay_ms = command_delay_ms(command);
+        if delay_ms > 0 {
+            delay.delay_ms(delay_ms);
+        }
         Ok(())
     }
 
-    fn write_command(&mut self, command: Instruction, params: &[u8]) -> Result<(), ()> {
+    /// Sends any command byte with parameters to the display.
+    ///
+    /// This is an escape hatch for vendor specific or undocumented commands
+    /// found in ST7735 clones. Nothing is validated and the driver state is
+    /// not updated, a command changing for example the memory access control
+    /// can leave the driver out of sync with the display.
+    ///
+    /// Fails with `DisplayError::OutOfBounds` for more than 127 parameters.
+    pub fn send_raw(&mut self, command: u8, params: &[u8]) -> Result<(), DisplayError> {
         let mut spi_data = [0u8; 128];
-        spi_data[0] = u8::from(command);
+        // The command byte followed by the parameters
+        if 1 + params.len() > spi_data.len() {
+            return Err(DisplayError::OutOfBounds);
+        }
+        spi_data[0] = command;
         let octets = if params.len() > 0 {
             let octets = params.len() + 1;
             spi_data[1..octets].copy_from_slice(params);
@@ -143,13 +833,24 @@ where
         };
         self.spi
             .send_command_data(&spi_data[..octets], 1)
-            .map_err(|_| ())?;
+            .map_err(|_| DisplayError::Interface)?;
         Ok(())
     }
 
-    fn write_command_words(&mut self, command: Instruction, params: &[u16]) -> Result<(), ()> {
+    /// Sends any command byte with 16 bit parameters to the display, each
+    /// sent big endian
+    ///
+    /// As `send_raw`, nothing is validated and the driver state is not
+    /// updated.
+    ///
+    /// Fails with `DisplayError::OutOfBounds` for more than 63 parameters.
+    pub fn send_raw_words(&mut self, command: u8, params: &[u16]) -> Result<(), DisplayError> {
         let mut spi_data = [0u8; 128];
-        spi_data[0] = u8::from(command);
+        // The command byte followed by two bytes per word, at most 63 words
+        if 1 + params.len() * 2 > spi_data.len() {
+            return Err(DisplayError::OutOfBounds);
+        }
+        spi_data[0] = command;
         let octets = if params.len() > 0 {
             let mut offset = 1;
             for word in params {
@@ -164,67 +865,340 @@ where
         };
         self.spi
             .send_command_data(&spi_data[..octets], 1)
-            .map_err(|_| ())?;
+            .map_err(|_| DisplayError::Interface)?;
         Ok(())
     }
 
-    fn write_command_words_iter<P: IntoIterator<Item = u16>>(
+    fn write_command_words(
         &mut self,
         command: Instruction,
-        params: P,
-    ) -> Result<(), ()> {
-        let mut spi_data = [0u8; 32768];
-        spi_data[0] = u8::from(command);
-        let mut offset = 1;
-        for word in params {
-            let bytes = word.to_be_bytes();
-            spi_data[offset] = bytes[0];
-            spi_data[offset + 1] = bytes[1];
-            offset += 2;
-        }
-        self.spi
-            .send_command_data(&spi_data[..offset], 1)
-            .map_err(|_| ())?;
-        Ok(())
+        params: &[u16],
+    ) -> Result<(), DisplayError> {
+        self.send_raw_words(u8::from(command), params)
     }
 
-    pub fn set_orientation(&mut self, orientation: Orientation) -> Result<(), ()> {
-        if self.rgb {
-            self.write_command(Instruction::MADCTL, &[u8::from(orientation)])?;
-        } else {
-            self.write_command(Instruction::MADCTL, &[u8::from(orientation) | 0x08])?;
+    /// Writes the memory access control register
+    fn write_madctl(&mut self) -> Result<(), DisplayError> {
+        self.write_command(Instruction::MADCTL, &[self.madctl.bits()])
+    }
+
+    /// Writes the memory access control from the orientation, mirroring and
+    /// colour order
+    fn update_madctl(&mut self) -> Result<(), DisplayError> {
+        let madctl = self
+            .orientation
+            .madctl(self.flip_horizontal, self.flip_vertical, self.rgb);
+        self.set_madctl(MadctlFlags::from_bits(madctl))
+    }
+
+    /// Sets the memory access control flags directly
+    ///
+    /// For panels which need bit combinations not covered by the
+    /// orientations and `flip`. Setting or clearing `MV` swaps the width and
+    /// height, so that `size` and the clipping follow the rotated panel, and
+    /// swaps the offsets with them. The offsets are exact for panels centered
+    /// in the frame memory, others may need `set_offset` for the mirrored
+    /// orientations.
+    ///
+    /// The flags are replaced by the next `set_orientation`, `flip` or
+    /// `set_color_order`.
+    pub fn set_madctl(&mut self, flags: MadctlFlags) -> Result<(), DisplayError> {
+        if flags.contains(MadctlFlags::MV) != self.madctl.contains(MadctlFlags::MV) {
+            core::mem::swap(&mut self.width, &mut self.height);
+            core::mem::swap(&mut self.dx, &mut self.dy);
         }
-        Ok(())
+        self.madctl = flags;
+        self.write_madctl()
+    }
+
+    /// The memory access control flags written to the display
+    pub fn madctl(&self) -> MadctlFlags {
+        self.madctl
+    }
+
+    /// Mutable access to the SPI interface, for example to change the SPI
+    /// frequency between initialization and drawing
+    pub fn spi_mut(&mut self) -> &mut SPI {
+        &mut self.spi
+    }
+
+    /// Sets the display orientation
+    ///
+    /// Changing between a portrait and a landscape orientation swaps the
+    /// width and height, see `set_madctl`.
+    pub fn set_orientation(&mut self, orientation: Orientation) -> Result<(), DisplayError> {
+        self.orientation = orientation;
+        self.update_madctl()
+    }
+
+    /// Mirrors the image horizontally and/or vertically on top of the
+    /// current orientation. Flipping both rotates the image 180 degrees,
+    /// which is handy for a panel mounted upside-down.
+    ///
+    /// The flip is kept when the orientation is changed later on.
+    pub fn flip(&mut self, horizontal: bool, vertical: bool) -> Result<(), DisplayError> {
+        self.flip_horizontal = horizontal;
+        self.flip_vertical = vertical;
+        self.update_madctl()
+    }
+
+    /// Sets the colour order, RGB (true) or BGR (false)
+    ///
+    /// Use this when red and blue come out swapped. The order is kept when
+    /// the orientation or mirroring is changed later on.
+    pub fn set_color_order(&mut self, rgb: bool) -> Result<(), DisplayError> {
+        self.rgb = rgb;
+        self.update_madctl()
     }
 
     /// Sets the global offset of the displayed image
-    pub fn set_offset(&mut self, dx: u16, dy: u16) {
+    ///
+    /// The offset is where the panel starts in the controller frame memory.
+    /// Fails with `DisplayError::OutOfBounds`, keeping the current offset,
+    /// if the panel moved by the offset doesn't fit the frame memory in the
+    /// current orientation. Set the orientation and memory size first.
+    pub fn set_offset(&mut self, dx: u16, dy: u16) -> Result<(), DisplayError> {
+        let (cols, rows) = self.memory_size();
+        if u32::from(dx) + self.width > u32::from(cols)
+            || u32::from(dy) + self.height > u32::from(rows)
+        {
+            return Err(DisplayError::OutOfBounds);
+        }
         self.dx = dx;
         self.dy = dy;
+        Ok(())
+    }
+
+    /// Sets the size of the controller frame memory, in portrait orientation
+    ///
+    /// The default is the ST7735 memory of `ST7735_COLS` × `ST7735_ROWS`.
+    /// Controllers with larger memory, such as the ST7789 with 240 × 320,
+    /// need this set for the offset and window checks to be correct.
+    pub fn set_memory_size(&mut self, cols: u16, rows: u16) {
+        self.memory_cols = cols;
+        self.memory_rows = rows;
+    }
+
+    /// Size of the controller frame memory in the current orientation
+    fn memory_size(&self) -> (u16, u16) {
+        if self.madctl.contains(MadctlFlags::MV) {
+            (self.memory_rows, self.memory_cols)
+        } else {
+            (self.memory_cols, self.memory_rows)
+        }
+    }
+
+    /// Sets the offset so that the panel is centered in the controller
+    /// frame memory, as is common for panels smaller than the memory
+    pub fn set_offset_centered(&mut self) {
+        let (cols, rows) = self.memory_size();
+        self.dx = cols.saturating_sub(self.width as u16) / 2;
+        self.dy = rows.saturating_sub(self.height as u16) / 2;
+    }
+
+    /// Sets how windows reaching outside the panel are handled
+    ///
+    /// The default is `ClipMode::Clamp`, which lets drawing partly outside
+    /// the panel work. Use `ClipMode::Error` to catch layout bugs.
+    pub fn set_clip_mode(&mut self, mode: ClipMode) {
+        self.clip_mode = mode;
+    }
+
+    /// Checks a window against the panel
+    ///
+    /// Inverted windows are always rejected, windows reaching outside the
+    /// panel only in `ClipMode::Error`.
+    fn check_window(&self, sx: u16, sy: u16, ex: u16, ey: u16) -> Result<(), DisplayError> {
+        if sx > ex || sy > ey {
+            return Err(DisplayError::OutOfBounds);
+        }
+        if self.clip_mode == ClipMode::Error
+            && (u32::from(ex) >= self.width || u32::from(ey) >= self.height)
+        {
+            return Err(DisplayError::OutOfBounds);
+        }
+        Ok(())
     }
 
     /// Sets the address window for the display.
-    fn set_address_window(&mut self, sx: u16, sy: u16, ex: u16, ey: u16) -> Result<(), ()> {
+    ///
+    /// Fails with `DisplayError::OutOfBounds` if the window is inverted. The
+    /// window is clamped to the panel or rejected depending on the clip
+    /// mode. A window starting outside the panel has nothing to clamp to and
+    /// fails in both modes, the drawing functions skip such windows before
+    /// setting them.
+    pub(crate) fn set_address_window(
+        &mut self,
+        sx: u16,
+        sy: u16,
+        ex: u16,
+        ey: u16,
+    ) -> Result<(), DisplayError> {
+        self.check_window(sx, sy, ex, ey)?;
+        if u32::from(sx) >= self.width || u32::from(sy) >= self.height {
+            return Err(DisplayError::OutOfBounds);
+        }
+        let max_x = (self.width as u16).saturating_sub(1);
+        let max_y = (self.height as u16).saturating_sub(1);
+        let (sx, ex) = (sx.min(max_x), ex.min(max_x));
+        let (sy, ey) = (sy.min(max_y), ey.min(max_y));
+        let (cols, rows) = self.memory_size();
+        if u32::from(ex) + u32::from(self.dx) >= u32::from(cols)
+            || u32::from(ey) + u32::from(self.dy) >= u32::from(rows)
+        {
+            return Err(DisplayError::OutOfBounds);
+        }
         self.write_command_words(Instruction::CASET, &[sx + self.dx, ex + self.dx])?;
         self.write_command_words(Instruction::RASET, &[sy + self.dy, ey + self.dy])
     }
 
+    /// Sets the address window from an embedded-graphics rectangle
+    ///
+    /// The rectangle is clamped to the panel. Fails if the rectangle is
+    /// inverted or lies entirely outside the panel.
+    pub fn set_window(&mut self, rect: Rectangle) -> Result<(), DisplayError> {
+        let max_x = self.width as i32 - 1;
+        let max_y = self.height as i32 - 1;
+        let sx = rect.top_left.x.max(0);
+        let sy = rect.top_left.y.max(0);
+        let ex = rect.bottom_right.x.min(max_x);
+        let ey = rect.bottom_right.y.min(max_y);
+        if sx > ex || sy > ey {
+            return Err(DisplayError::OutOfBounds);
+        }
+        self.set_address_window(sx as u16, sy as u16, ex as u16, ey as u16)
+    }
+
     /// Sets a pixel color at the given coords.
-    pub fn set_pixel(&mut self, x: u16, y: u16, color: u16) -> Result<(), ()> {
+    ///
+    /// Pixels outside the panel are skipped in `ClipMode::Clamp` and fail
+    /// with `DisplayError::OutOfBounds` in `ClipMode::Error`. Pixels outside
+    /// the clip rectangle are skipped.
+    pub fn set_pixel(&mut self, x: u16, y: u16, color: u16) -> Result<(), DisplayError> {
+        self.check_window(x, y, x, y)?;
+        if self.clip_window(x, y, x, y).is_none() {
+            return Ok(());
+        }
         self.set_address_window(x, y, x, y)?;
         self.write_command_words(Instruction::RAMWR, &[color])
     }
 
+    /// Sets the number of pixels written per SPI transfer by `set_pixels`
+    /// and the drawing functions
+    ///
+    /// Larger chunks spend less time on transfer setup, smaller chunks hold
+    /// a shared SPI bus for a shorter time and lower the interrupt latency.
+    /// `RAMWR` is sent once and the chunks follow as data only transfers,
+    /// so the bytes sent are the same for any chunk size. The pixels are
+    /// packed into a stack buffer of `MAX_FLUSH_CHUNK` pixels whatever the
+    /// chunk size, a smaller chunk doesn't lower the stack usage.
+    /// The size is limited to 1 to `MAX_FLUSH_CHUNK` pixels.
+    pub fn set_flush_chunk(&mut self, pixels: usize) {
+        self.flush_chunk = pixels.max(1).min(MAX_FLUSH_CHUNK);
+    }
+
     /// Writes pixel colors sequentially into the current drawing window
-    pub fn write_pixels<P: IntoIterator<Item = u16>>(&mut self, colors: P) -> Result<(), ()> {
-        self.write_command_words_iter(Instruction::RAMWR, colors)
+    ///
+    /// The write starts at the beginning of the drawing window, the pixels
+    /// are written in chunks as set by `set_flush_chunk`.
+    pub fn write_pixels<P: IntoIterator<Item = u16>>(
+        &mut self,
+        colors: P,
+    ) -> Result<(), DisplayError> {
+        let chunk_pixels = self.flush_chunk;
+        self.write_pixels_chunked(colors, chunk_pixels, || {})
     }
 
+    /// Writes pixel colors sequentially into the current drawing window,
+    /// `chunk_pixels` at a time
+    ///
+    /// `yield_fn` is called between the chunks, letting the caller do other
+    /// work or release a shared SPI bus. `RAMWR` is sent once, the chunks
+    /// follow as data only transfers. The controller keeps writing where
+    /// the previous chunk ended until it receives another command, so chip
+    /// select may be released between chunks, but no command may be sent to
+    /// the display from `yield_fn`.
+    pub fn write_pixels_chunked<P, F>(
+        &mut self,
+        colors: P,
+        chunk_pixels: usize,
+        mut yield_fn: F,
+    ) -> Result<(), DisplayError>
+    where
+        P: IntoIterator<Item = u16>,
+        F: FnMut(),
+    {
+        let chunk_pixels = chunk_pixels.max(1).min(MAX_FLUSH_CHUNK);
+        let mut colors = colors.into_iter().peekable();
+        if colors.peek().is_none() {
+            return Ok(());
+        }
+        self.write_command(Instruction::RAMWR, &[])?;
+        loop {
+            self.write_pixel_data(colors.by_ref().take(chunk_pixels))?;
+            if colors.peek().is_none() {
+                return Ok(());
+            }
+            yield_fn();
+        }
+    }
+
+    /// Sends pixel colors as data, continuing the memory write started by
+    /// the last `RAMWR`
+    ///
+    /// The colors are packed into a stack buffer of `MAX_FLUSH_CHUNK` pixels
+    /// and sent each time it is full, so any number of colors can be sent.
+    pub(crate) fn write_pixel_data<P: IntoIterator<Item = u16>>(
+        &mut self,
+        colors: P,
+    ) -> Result<(), DisplayError> {
+        let mut spi_data = [0u8; MAX_FLUSH_CHUNK * 2];
+        let mut offset = 0;
+        for word in colors {
+            if offset == spi_data.len() {
+                self.spi
+                    .send_command_data(&spi_data, 0)
+                    .map_err(|_| DisplayError::Interface)?;
+                offset = 0;
+            }
+            spi_data[offset..offset + 2].copy_from_slice(&word.to_be_bytes());
+            offset += 2;
+        }
+        if offset > 0 {
+            self.spi
+                .send_command_data(&spi_data[..offset], 0)
+                .map_err(|_| DisplayError::Interface)?;
+        }
+        Ok(())
+    }
+
+    /// Writes pre-encoded pixels into the current drawing window
+    ///
+    /// `data` holds the pixel colors as big endian 16 bit words, the format
+    /// sent to the display. The command byte and the data are sent without
+    /// copying them into a stack buffer, so any amount of data can be
+    /// written without the stack usage of `write_pixels`. With `Spim` the
+    /// data must be in RAM and the chip select is held across the command
+    /// and the data.
+    pub fn write_pixel_bytes(&mut self, data: &[u8]) -> Result<(), DisplayError> {
+        if data.len() % 2 != 0 {
+            return Err(DisplayError::OutOfBounds);
+        }
+        self.spi
+            .send_command_then_data(&[u8::from(Instruction::RAMWR)], data)
+            .map_err(|_| DisplayError::Interface)
+    }
+
+    /// Writes pixel colors sequentially into the current drawing window, in
+    /// transfers of `MAX_FLUSH_CHUNK` pixels
+    ///
+    /// Unlike `write_pixels` the chunk size set by `set_flush_chunk` isn't
+    /// used, the fewest transfers are made.
     pub fn write_pixels_buffered<P: IntoIterator<Item = u16>>(
         &mut self,
         colors: P,
-    ) -> Result<(), ()> {
-        self.write_command_words_iter(Instruction::RAMWR, colors)
+    ) -> Result<(), DisplayError> {
+        self.write_pixels_chunked(colors, MAX_FLUSH_CHUNK, || {})
     }
 
     /// Sets pixel colors at the given drawing window
@@ -235,9 +1209,84 @@ where
         ex: u16,
         ey: u16,
         colors: P,
-    ) -> Result<(), ()> {
-        self.set_address_window(sx, sy, ex, ey)?;
-        self.write_pixels(colors)
+    ) -> Result<(), DisplayError> {
+        self.check_window(sx, sy, ex, ey)?;
+        match self.clip_window(sx, sy, ex, ey) {
+            None => Ok(()),
+            Some(window) if window == (sx, sy, ex, ey) => {
+                self.set_address_window(sx, sy, ex, ey)?;
+                self.write_pixels(colors)
+            }
+            Some((csx, csy, cex, cey)) => {
+                let width = usize::from(ex - sx) + 1;
+                self.set_address_window(csx, csy, cex, cey)?;
+                self.write_pixels(
+                    colors
+                        .into_iter()
+                        .enumerate()
+                        .filter(move |(n, _)| {
+                            let x = sx + (n % width) as u16;
+                            let y = sy + (n / width) as u16;
+                            x >= csx && x <= cex && y >= csy && y <= cey
+                        })
+                        .map(|(_, color)| color),
+                )
+            }
+        }
+    }
+
+    /// Sets pixel colors at the given drawing window, converting from
+    /// `Rgb565` while streaming
+    pub fn set_pixels_color<P: IntoIterator<Item = Rgb565>>(
+        &mut self,
+        sx: u16,
+        sy: u16,
+        ex: u16,
+        ey: u16,
+        colors: P,
+    ) -> Result<(), DisplayError> {
+        self.set_pixels(
+            sx,
+            sy,
+            ex,
+            ey,
+            colors.into_iter().map(|c| RawU16::from(c).into_inner()),
+        )
+    }
+
+    /// Sets pixel colors in a rectangle with signed corners, as used by
+    /// embedded-graphics
+    ///
+    /// The colors cover the whole rectangle in row order. Pixels outside the
+    /// panel are dropped in both clip modes, so the rectangle may start left
+    /// of or above the panel.
+    pub fn set_pixels_rect<P: IntoIterator<Item = u16>>(
+        &mut self,
+        rect: Rectangle,
+        colors: P,
+    ) -> Result<(), DisplayError> {
+        let Rectangle {
+            top_left,
+            bottom_right,
+        } = rect;
+        let sx = top_left.x.max(0);
+        let sy = top_left.y.max(0);
+        let ex = bottom_right.x.min(self.width as i32 - 1);
+        let ey = bottom_right.y.min(self.height as i32 - 1);
+        if sx > ex || sy > ey {
+            return Ok(());
+        }
+        let width = (bottom_right.x - top_left.x + 1) as usize;
+        let visible = colors
+            .into_iter()
+            .enumerate()
+            .filter(move |(n, _)| {
+                let x = top_left.x + (n % width) as i32;
+                let y = top_left.y + (n / width) as i32;
+                x >= sx && x <= ex && y >= sy && y <= ey
+            })
+            .map(|(_, color)| color);
+        self.set_pixels(sx as u16, sy as u16, ex as u16, ey as u16, visible)
     }
 
     pub fn set_pixels_buffered<P: IntoIterator<Item = u16>>(
@@ -247,9 +1296,293 @@ where
         ex: u16,
         ey: u16,
         colors: P,
-    ) -> Result<(), ()> {
+    ) -> Result<(), DisplayError> {
+        self.set_pixels(sx, sy, ex, ey, colors)
+    }
+
+    /// Width and height of a region, which must lie within the panel
+    fn region_size(&self, rect: &Rectangle) -> Result<(usize, usize), DisplayError> {
+        let Rectangle {
+            top_left,
+            bottom_right,
+        } = *rect;
+        if top_left.x < 0
+            || top_left.y < 0
+            || top_left.x > bottom_right.x
+            || top_left.y > bottom_right.y
+            || bottom_right.x as u32 >= self.width
+            || bottom_right.y as u32 >= self.height
+        {
+            return Err(DisplayError::OutOfBounds);
+        }
+        Ok((
+            (bottom_right.x - top_left.x + 1) as usize,
+            (bottom_right.y - top_left.y + 1) as usize,
+        ))
+    }
+
+    /// Writes several regions, merging adjacent regions into one window
+    ///
+    /// Each region is a rectangle within the panel and its pixel colors in
+    /// row order. The regions are sorted from top to bottom and left to
+    /// right. Regions spanning the same rows and following each other
+    /// horizontally are written as one window, as are regions spanning the
+    /// same columns and following each other vertically. This saves the
+    /// `CASET`, `RASET` and `RAMWR` commands for each merged region.
+    ///
+    /// Returns the number of windows written. Fails without writing anything
+    /// if there are more than `MAX_FLUSH_REGIONS` regions, a region is
+    /// outside the panel or its data doesn't match its size. The clip
+    /// rectangle set by `with_clip` isn't applied.
+    pub fn flush_regions(
+        &mut self,
+        regions: &[(Rectangle, &[u16])],
+    ) -> Result<usize, DisplayError> {
+        if regions.len() > MAX_FLUSH_REGIONS {
+            return Err(DisplayError::OutOfBounds);
+        }
+        let mut order = [0usize; MAX_FLUSH_REGIONS];
+        for (index, (rect, data)) in regions.iter().enumerate() {
+            let (width, height) = self.region_size(rect)?;
+            if data.len() != width * height {
+                return Err(DisplayError::OutOfBounds);
+            }
+            order[index] = index;
+        }
+        let order = &mut order[..regions.len()];
+        order.sort_unstable_by_key(|&i| (regions[i].0.top_left.y, regions[i].0.top_left.x));
+
+        let mut windows = 0;
+        let mut start = 0;
+        while start < order.len() {
+            let first = regions[order[start]].0;
+            let mut end = start + 1;
+            let mut last = first;
+            // Regions on the same rows, each starting where the previous ended
+            while end < order.len() {
+                let next = regions[order[end]].0;
+                if next.top_left.y != first.top_left.y
+                    || next.bottom_right.y != first.bottom_right.y
+                    || next.top_left.x != last.bottom_right.x + 1
+                {
+                    break;
+                }
+                last = next;
+                end += 1;
+            }
+            if end == start + 1 {
+                // Regions on the same columns, each starting below the previous
+                while end < order.len() {
+                    let next = regions[order[end]].0;
+                    if next.top_left.x != first.top_left.x
+                        || next.bottom_right.x != first.bottom_right.x
+                        || next.top_left.y != last.bottom_right.y + 1
+                    {
+                        break;
+                    }
+                    last = next;
+                    end += 1;
+                }
+            }
+            let group = &order[start..end];
+            self.set_address_window(
+                first.top_left.x as u16,
+                first.top_left.y as u16,
+                last.bottom_right.x as u16,
+                last.bottom_right.y as u16,
+            )?;
+            if first.bottom_right.y == last.bottom_right.y {
+                // Interleave the rows of horizontally merged regions
+                let rows = 0..(first.bottom_right.y - first.top_left.y + 1) as usize;
+                let pixels = rows.flat_map(move |row| {
+                    group.iter().flat_map(move |&i| {
+                        let (rect, data) = regions[i];
+                        let width = (rect.bottom_right.x - rect.top_left.x + 1) as usize;
+                        data[row * width..(row + 1) * width].iter().copied()
+                    })
+                });
+                self.write_pixels(pixels)?;
+            } else {
+                let pixels = group.iter().flat_map(|&i| regions[i].1.iter().copied());
+                self.write_pixels(pixels)?;
+            }
+            windows += 1;
+            start = end;
+        }
+        Ok(windows)
+    }
+
+    /// Clips all following drawing to `clip` until the returned guard is
+    /// dropped
+    ///
+    /// Drawing is done through the guard. Pixels outside the clip rectangle,
+    /// and any clip rectangle already active, are dropped.
+    pub fn with_clip(&mut self, clip: Rectangle) -> ClipGuard<'_, SPI, P, TE> {
+        let previous = self.clip;
+        let clip = match previous {
+            Some(previous) => Rectangle::new(
+                Point::new(
+                    clip.top_left.x.max(previous.top_left.x),
+                    clip.top_left.y.max(previous.top_left.y),
+                ),
+                Point::new(
+                    clip.bottom_right.x.min(previous.bottom_right.x),
+                    clip.bottom_right.y.min(previous.bottom_right.y),
+                ),
+            ),
+            None => clip,
+        };
+        self.clip = Some(clip);
+        ClipGuard {
+            display: self,
+            previous,
+        }
+    }
+
+    /// Fills a rectangle `w` by `h` pixels with its top left corner at `x`,
+    /// `y` with a single color
+    ///
+    /// A rectangle reaching outside the panel is clamped or rejected
+    /// depending on the clip mode. Only the part inside the clip rectangle
+    /// is drawn. Nothing is drawn for an empty rectangle.
+    pub fn fill_rect(
+        &mut self,
+        x: u16,
+        y: u16,
+        w: u16,
+        h: u16,
+        color: u16,
+    ) -> Result<(), DisplayError> {
+        if w == 0 || h == 0 {
+            return Ok(());
+        }
+        let ex = x.saturating_add(w - 1);
+        let ey = y.saturating_add(h - 1);
+        self.fill_solid(x, y, ex, ey, color)
+    }
+
+    /// Fills the window from `sx`, `sy` to `ex`, `ey`, inclusive, with a
+    /// single color
+    ///
+    /// The color is packed into a buffer of `FILL_BUFFER_PIXELS` pixels once
+    /// and the buffer is sent repeatedly. The pixel loop of `write_pixels`
+    /// is skipped and each transfer moves a full buffer, so large fills are
+    /// limited by the SPI clock rather than by the CPU. The window is
+    /// clipped like with `set_pixels`.
+    ///
+    /// A full 160 x 80 fill is 25600 bytes, at least 25.6 ms at 8 MHz. The
+    /// display example times a clear with `fill_solid` and with
+    /// `set_pixels`, which packs every pixel, and logs both.
+    pub fn fill_solid(
+        &mut self,
+        sx: u16,
+        sy: u16,
+        ex: u16,
+        ey: u16,
+        color: u16,
+    ) -> Result<(), DisplayError> {
+        self.check_window(sx, sy, ex, ey)?;
+        let (sx, sy, ex, ey) = match self.clip_window(sx, sy, ex, ey) {
+            Some(window) => window,
+            None => return Ok(()),
+        };
+        let mut count = (usize::from(ex - sx) + 1) * (usize::from(ey - sy) + 1);
         self.set_address_window(sx, sy, ex, ey)?;
-        self.write_pixels_buffered(colors)
+        let mut buffer = [0u8; FILL_BUFFER_PIXELS * 2];
+        for pixel in buffer.chunks_exact_mut(2) {
+            pixel.copy_from_slice(&color.to_be_bytes());
+        }
+        // The controller keeps writing until the next command, the buffer
+        // is sent as data only transfers after a single RAMWR
+        self.write_command(Instruction::RAMWR, &[])?;
+        while count > 0 {
+            let pixels = count.min(FILL_BUFFER_PIXELS);
+            self.spi
+                .send_command_data(&buffer[..pixels * 2], 0)
+                .map_err(|_| DisplayError::Interface)?;
+            count -= pixels;
+        }
+        Ok(())
+    }
+
+    /// Fills a horizontal run of pixels with a single color
+    fn fill_run(&mut self, sx: u16, ex: u16, y: u16, color: u16) -> Result<(), DisplayError> {
+        if sx == ex {
+            return self.set_pixel(sx, y, color);
+        }
+        let count = usize::from(ex - sx) + 1;
+        self.set_pixels(sx, y, ex, y, core::iter::repeat(color).take(count))
+    }
+
+    /// Is the point on the panel?
+    fn contains(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && (x as u32) < self.width && (y as u32) < self.height
+    }
+
+    /// Intersects a window with the panel and the clip rectangle, returns
+    /// `None` if nothing of the window is visible
+    fn clip_window(&self, sx: u16, sy: u16, ex: u16, ey: u16) -> Option<(u16, u16, u16, u16)> {
+        let mut csx = i32::from(sx);
+        let mut csy = i32::from(sy);
+        let mut cex = i32::from(ex).min(self.width as i32 - 1);
+        let mut cey = i32::from(ey).min(self.height as i32 - 1);
+        if let Some(clip) = self.clip {
+            csx = csx.max(clip.top_left.x);
+            csy = csy.max(clip.top_left.y);
+            cex = cex.min(clip.bottom_right.x);
+            cey = cey.min(clip.bottom_right.y);
+        }
+        if csx > cex || csy > cey {
+            None
+        } else {
+            Some((csx as u16, csy as u16, cex as u16, cey as u16))
+        }
+    }
+}
+
+/// Clips the drawing on a display, the previous clip is restored on drop
+pub struct ClipGuard<'a, SPI, P = St7735Panel, TE = NoTePin>
+where
+    SPI: crate::spi::SpiSendCommandData,
+    P: Panel,
+    TE: InputPin,
+{
+    display: &'a mut TftDisplay<SPI, P, TE>,
+    previous: Option<Rectangle>,
+}
+
+impl<'a, SPI, P, TE> Deref for ClipGuard<'a, SPI, P, TE>
+where
+    SPI: crate::spi::SpiSendCommandData,
+    P: Panel,
+    TE: InputPin,
+{
+    type Target = TftDisplay<SPI, P, TE>;
+
+    fn deref(&self) -> &Self::Target {
+        self.display
+    }
+}
+
+impl<'a, SPI, P, TE> DerefMut for ClipGuard<'a, SPI, P, TE>
+where
+    SPI: crate::spi::SpiSendCommandData,
+    P: Panel,
+    TE: InputPin,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.display
+    }
+}
+
+impl<'a, SPI, P, TE> Drop for ClipGuard<'a, SPI, P, TE>
+where
+    SPI: crate::spi::SpiSendCommandData,
+    P: Panel,
+    TE: InputPin,
+{
+    fn drop(&mut self) {
+        self.display.clip = self.previous;
     }
 }
 
@@ -266,17 +1599,56 @@ use embedded_graphics::{
     DrawTarget,
 };
 
-impl<SPI> DrawTarget<Rgb565> for ST7735<SPI>
+impl<SPI, P, TE> DrawTarget<Rgb565> for TftDisplay<SPI, P, TE>
 where
     SPI: crate::spi::SpiSendCommandData,
+    P: Panel,
+    TE: InputPin,
 {
-    type Error = ();
+    type Error = DisplayError;
 
+    /// Draws a pixel, pixels outside the panel are clipped
     fn draw_pixel(&mut self, pixel: Pixel<Rgb565>) -> Result<(), Self::Error> {
         let Pixel(Point { x, y }, color) = pixel;
+        if !self.contains(x, y) {
+            return Ok(());
+        }
         self.set_pixel(x as u16, y as u16, RawU16::from(color).into_inner())
     }
 
+    /// Draws pixels, coalescing horizontal runs of the same color
+    ///
+    /// Adjacent pixels on the same row with the same color are written as a
+    /// single window, which speeds up lines and outlines considerably.
+    fn draw_iter<T>(&mut self, item: T) -> Result<(), Self::Error>
+    where
+        T: IntoIterator<Item = Pixel<Rgb565>>,
+    {
+        // Current run as start x, end x, y and color
+        let mut run: Option<(u16, u16, u16, u16)> = None;
+        for Pixel(Point { x, y }, color) in item {
+            if !self.contains(x, y) {
+                continue;
+            }
+            let (x, y) = (x as u16, y as u16);
+            let color = RawU16::from(color).into_inner();
+            run = match run {
+                Some((sx, ex, ry, rc)) if ry == y && rc == color && ex.wrapping_add(1) == x => {
+                    Some((sx, x, ry, rc))
+                }
+                Some((sx, ex, ry, rc)) => {
+                    self.fill_run(sx, ex, ry, rc)?;
+                    Some((x, x, y, color))
+                }
+                None => Some((x, x, y, color)),
+            };
+        }
+        match run {
+            Some((sx, ex, y, color)) => self.fill_run(sx, ex, y, color),
+            None => Ok(()),
+        }
+    }
+
     fn draw_rectangle(
         &mut self,
         item: &Styled<Rectangle, PrimitiveStyle<Rgb565>>,
@@ -288,14 +1660,21 @@ where
 
         match (item.style.fill_color, item.style.stroke_color) {
             (Some(fill), None) => {
-                let color = RawU16::from(fill).into_inner();
-                let iter = (0..rect_size).map(move |_| color);
-                self.set_pixels_buffered(
-                    shape.top_left.x as u16,
-                    shape.top_left.y as u16,
-                    shape.bottom_right.x as u16,
-                    shape.bottom_right.y as u16,
-                    iter,
+                // A draw target clips, so the rectangle is cut to the panel
+                // before `fill_solid` checks the window
+                let sx = shape.top_left.x.max(0);
+                let sy = shape.top_left.y.max(0);
+                let ex = shape.bottom_right.x.min(self.width as i32 - 1);
+                let ey = shape.bottom_right.y.min(self.height as i32 - 1);
+                if sx > ex || sy > ey {
+                    return Ok(());
+                }
+                self.fill_solid(
+                    sx as u16,
+                    sy as u16,
+                    ex as u16,
+                    ey as u16,
+                    RawU16::from(fill).into_inner(),
                 )
             }
             (Some(fill), Some(stroke)) => {
@@ -312,13 +1691,7 @@ where
                         fill_color
                     }
                 });
-                self.set_pixels_buffered(
-                    shape.top_left.x as u16,
-                    shape.top_left.y as u16,
-                    shape.bottom_right.x as u16,
-                    shape.bottom_right.y as u16,
-                    iter,
-                )
+                self.set_pixels_rect(shape, iter)
             }
             // TODO: Draw edges as subrectangles
             (None, Some(_)) => self.draw_iter(item),
@@ -331,17 +1704,11 @@ where
         &'b I: IntoPixelIter<Rgb565>,
         I: ImageDimensions,
     {
-        let sx = item.top_left().x as u16;
-        let sy = item.top_left().y as u16;
-        let ex = item.bottom_right().x as u16;
-        let ey = item.bottom_right().y as u16;
         // -1 is required because image gets skewed if it is not present
         // NOTE: Is this also required for draw_rect?
-        self.set_pixels_buffered(
-            sx,
-            sy,
-            ex - 1,
-            ey - 1,
+        let bottom_right = item.bottom_right() - Point::new(1, 1);
+        self.set_pixels_rect(
+            Rectangle::new(item.top_left(), bottom_right),
             item.into_iter().map(|p| RawU16::from(p.1).into_inner()),
         )
     }
@@ -349,4 +1716,9 @@ where
     fn size(&self) -> Size {
         Size::new(self.width, self.height)
     }
+
+    fn clear(&mut self, color: Rgb565) -> Result<(), Self::Error> {
+        let color = RawU16::from(color).into_inner();
+        self.fill_rect(0, 0, self.width as u16, self.height as u16, color)
+    }
 }
@@ -0,0 +1,86 @@
+//! ST7789 panels, driven by the ST7735 driver.
+//!
+//! The ST7789 uses the same commands for windowing, memory access control
+//! and pixel writes as the ST7735, only the frame memory size and the power
+//! setup differ.
+
+use crate::st7735s::{Panel, PanelCommand, TftDisplay};
+
+/// Columns in the ST7789 frame memory
+pub const ST7789_COLS: u16 = 240;
+/// Rows in the ST7789 frame memory
+pub const ST7789_ROWS: u16 = 320;
+
+/// ST7789 panel configuration
+///
+/// Most ST7789 panels need the colours inverted, create the driver with
+/// `inverted` set if black shows as white.
+pub struct St7789Panel;
+
+impl Panel for St7789Panel {
+    const MEMORY_COLS: u16 = ST7789_COLS;
+    const MEMORY_ROWS: u16 = ST7789_ROWS;
+    const COLMOD: u8 = 0x55;
+    // The ST7789 gamma commands take 14 parameters, the reset values are
+    // kept
+    const GAMMA: Option<(&'static [u8; 16], &'static [u8; 16])> = None;
+    const INIT: &'static [PanelCommand] = &[
+        PanelCommand {
+            // PORCTRL, porch setting
+            command: 0xB2,
+            params: &[0x0C, 0x0C, 0x00, 0x33, 0x33],
+            delay_ms: 0,
+        },
+        PanelCommand {
+            // GCTRL, gate control
+            command: 0xB7,
+            params: &[0x35],
+            delay_ms: 0,
+        },
+        PanelCommand {
+            // VCOMS
+            command: 0xBB,
+            params: &[0x19],
+            delay_ms: 0,
+        },
+        PanelCommand {
+            // LCMCTRL
+            command: 0xC0,
+            params: &[0x2C],
+            delay_ms: 0,
+        },
+        PanelCommand {
+            // VDVVRHEN
+            command: 0xC2,
+            params: &[0x01],
+            delay_ms: 0,
+        },
+        PanelCommand {
+            // VRHS
+            command: 0xC3,
+            params: &[0x12],
+            delay_ms: 0,
+        },
+        PanelCommand {
+            // VDVS
+            command: 0xC4,
+            params: &[0x20],
+            delay_ms: 0,
+        },
+        PanelCommand {
+            // FRCTRL2, 60 Hz frame rate
+            command: 0xC6,
+            params: &[0x0F],
+            delay_ms: 0,
+        },
+        PanelCommand {
+            // PWCTRL1
+            command: 0xD0,
+            params: &[0xA4, 0xA1],
+            delay_ms: 0,
+        },
+    ];
+}
+
+/// ST7789 driver to connect to TFT displays.
+pub type ST7789<SPI> = TftDisplay<SPI, St7789Panel>;
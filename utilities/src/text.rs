@@ -0,0 +1,132 @@
+//! Layout and rendering helpers for text in monospaced fonts.
+//!
+//! Widths are in pixels, use the character width of the font, such as
+//! `ProFont12Point::CHARACTER_SIZE.width`.
+
+use embedded_graphics::{
+    drawable::{Drawable, Pixel},
+    fonts::{Font, Text},
+    geometry::{Point, Size},
+    pixelcolor::{raw::RawU16, Rgb565},
+    primitives::Rectangle,
+    style::TextStyleBuilder,
+    DrawTarget,
+};
+use embedded_hal::digital::v2::InputPin;
+
+use crate::spi::SpiSendCommandData;
+use crate::st7735s::{DisplayError, Panel, TftDisplay};
+
+/// Width of the longest line in `text`
+pub fn text_width(text: &str, font_width: u32) -> u32 {
+    text.lines()
+        .map(|line| line.chars().count() as u32 * font_width)
+        .max()
+        .unwrap_or(0)
+}
+
+/// X coordinate that centers `text` on a screen `screen_width` pixels wide
+///
+/// Text wider than the screen starts at zero.
+pub fn center_x(text: &str, font_width: u32, screen_width: u32) -> i32 {
+    (screen_width.saturating_sub(text_width(text, font_width)) / 2) as i32
+}
+
+/// X coordinate that aligns `text` to the right edge of a screen
+/// `screen_width` pixels wide
+///
+/// Text wider than the screen starts at zero.
+pub fn right_align(text: &str, font_width: u32, screen_width: u32) -> i32 {
+    screen_width.saturating_sub(text_width(text, font_width)) as i32
+}
+
+/// Text rendered into a RAM bitmap, for drawing in a single transfer
+///
+/// Drawing text directly on the display sends a window and the pixels for
+/// every run of glyph pixels. Rendering into a bitmap first and sending it
+/// as one region is much faster for short strings such as status lines.
+///
+/// The bitmap holds up to `N` pixels, at two bytes each.
+pub struct BufferedText<const N: usize> {
+    pixels: [u16; N],
+    width: u32,
+    height: u32,
+}
+
+impl<const N: usize> BufferedText<N> {
+    /// Renders `text` with `font`
+    ///
+    /// Fails with `DisplayError::OutOfBounds` if the text doesn't fit the
+    /// bitmap.
+    pub fn new<F>(
+        text: &str,
+        font: F,
+        foreground: Rgb565,
+        background: Rgb565,
+    ) -> Result<Self, DisplayError>
+    where
+        F: Font + Copy,
+    {
+        let character = F::CHARACTER_SIZE;
+        let width = text_width(text, character.width);
+        let height = text.lines().count() as u32 * character.height;
+        if (width * height) as usize > N {
+            return Err(DisplayError::OutOfBounds);
+        }
+        let mut buffer = Self {
+            pixels: [RawU16::from(background).into_inner(); N],
+            width,
+            height,
+        };
+        let style = TextStyleBuilder::new(font)
+            .text_color(foreground)
+            .background_color(background)
+            .build();
+        Text::new(text, Point::zero())
+            .into_styled(style)
+            .draw(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Draws the text with its top left corner at `top_left`
+    ///
+    /// The text may reach outside the panel, only the visible part is
+    /// drawn.
+    pub fn draw<SPI, P, TE>(
+        &self,
+        display: &mut TftDisplay<SPI, P, TE>,
+        top_left: Point,
+    ) -> Result<(), DisplayError>
+    where
+        SPI: SpiSendCommandData,
+        P: Panel,
+        TE: InputPin,
+    {
+        if self.width == 0 || self.height == 0 {
+            return Ok(());
+        }
+        let size = Point::new(self.width as i32 - 1, self.height as i32 - 1);
+        let count = (self.width * self.height) as usize;
+        display.set_pixels_rect(
+            Rectangle::new(top_left, top_left + size),
+            self.pixels[..count].iter().cloned(),
+        )
+    }
+}
+
+impl<const N: usize> DrawTarget<Rgb565> for BufferedText<N> {
+    type Error = DisplayError;
+
+    fn draw_pixel(&mut self, pixel: Pixel<Rgb565>) -> Result<(), Self::Error> {
+        let Pixel(Point { x, y }, color) = pixel;
+        if x >= 0 && y >= 0 && (x as u32) < self.width && (y as u32) < self.height {
+            let index = y as usize * self.width as usize + x as usize;
+            self.pixels[index] = RawU16::from(color).into_inner();
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+}
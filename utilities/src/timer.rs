@@ -0,0 +1,66 @@
+//! TIMER counter conversions.
+
+use crate::hal::timer::Instance;
+
+/// Frequency of the TIMER base clock
+const BASE_FREQUENCY: u32 = 16_000_000;
+
+/// Counter frequency with the reset prescaler, 4, also used by the HAL
+pub const DEFAULT_FREQUENCY: u32 = 1_000_000;
+
+/// Counter frequency from the prescaler configured in the timer
+pub fn frequency<T: Instance>(timer: &T) -> u32 {
+    let prescaler = timer.as_timer0().prescaler.read().prescaler().bits();
+    BASE_FREQUENCY >> prescaler
+}
+
+/// Configures the timer as a free running 32 bit counter at the default
+/// 1 MHz and starts it
+///
+/// The counter counts microseconds and wraps around after 2^32 us, about
+/// 71.6 minutes. Read it with `Instance::read_counter`, which uses
+/// capture/compare register 1.
+pub fn start_free_running<T: Instance>(timer: &T) {
+    let regs = timer.as_timer0();
+    regs.tasks_stop.write(|w| unsafe { w.bits(1) });
+    regs.intenclr.write(|w| unsafe { w.bits(0xffff_ffff) });
+    regs.shorts.reset();
+    regs.mode.write(|w| w.mode().timer());
+    regs.bitmode.write(|w| w.bitmode()._32bit());
+    // 16 MHz / 2^4 = 1 MHz
+    regs.prescaler.write(|w| unsafe { w.prescaler().bits(4) });
+    regs.tasks_clear.write(|w| unsafe { w.bits(1) });
+    regs.tasks_start.write(|w| unsafe { w.bits(1) });
+}
+
+/// Mask of the counter bits for the bit mode configured in the timer
+fn counter_mask<T: Instance>(timer: &T) -> u32 {
+    match timer.as_timer0().bitmode.read().bits() & 0b11 {
+        0 => 0xffff,
+        1 => 0xff,
+        2 => 0x00ff_ffff,
+        _ => 0xffff_ffff,
+    }
+}
+
+/// Converts counter ticks at `frequency` to milliseconds
+pub fn ticks_to_ms_at(ticks: u32, frequency: u32) -> u32 {
+    (u64::from(ticks) * 1000 / u64::from(frequency)) as u32
+}
+
+/// Converts counter ticks at the default 1 MHz to milliseconds
+pub fn ticks_to_ms(ticks: u32) -> u32 {
+    ticks_to_ms_at(ticks, DEFAULT_FREQUENCY)
+}
+
+/// Captures the counter and returns it with the milliseconds elapsed since
+/// the counter value `since`
+///
+/// The prescaler and bit mode are read from the timer, so this is right
+/// whatever the timer was configured for. The counter is assumed to have
+/// wrapped at most once.
+pub fn elapsed_ms<T: Instance>(timer: &T, since: u32) -> (u32, u32) {
+    let now = timer.read_counter();
+    let ticks = now.wrapping_sub(since) & counter_mask(timer);
+    (now, ticks_to_ms_at(ticks, frequency(timer)))
+}
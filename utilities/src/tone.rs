@@ -0,0 +1,138 @@
+//! Simple tones on a PWM peripheral, for beeps and UI feedback.
+
+use core::ops::Deref;
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use embedded_hal::blocking::delay::DelayMs;
+
+use crate::hal::gpio::{Output, Pin, Port, PushPull};
+use crate::hal::pac::{pwm0, PWM0, PWM1, PWM2, PWM3};
+
+/// PWM base clock in Hz
+const PWM_CLOCK: u32 = 16_000_000;
+/// Largest counter top value, COUNTERTOP is 15 bits wide
+const MAX_COUNTER_TOP: u32 = 0x7fff;
+/// Smallest counter top value
+const MIN_COUNTER_TOP: u32 = 3;
+/// Largest prescaler, the clock is divided by 2^7
+const MAX_PRESCALER: u8 = 7;
+
+/// Calculates the prescaler and counter top for a tone frequency
+///
+/// The smallest prescaler that fits the period in the counter is used, to
+/// get the best frequency resolution. Returns `None` if the frequency is
+/// outside the range of the PWM, about 4 Hz to 5.3 MHz.
+pub fn tone_period(frequency: u32) -> Option<(u8, u16)> {
+    if frequency == 0 {
+        return None;
+    }
+    for prescaler in 0..=MAX_PRESCALER {
+        let top = (PWM_CLOCK >> prescaler) / frequency;
+        if top < MIN_COUNTER_TOP {
+            return None;
+        }
+        if top <= MAX_COUNTER_TOP {
+            return Some((prescaler, top as u16));
+        }
+    }
+    None
+}
+
+/// Square wave tone generator
+pub struct Tone<T> {
+    pwm: T,
+}
+
+impl<T> Tone<T>
+where
+    T: Instance,
+{
+    /// Creates a tone generator driving `pin`, the pin is kept low when
+    /// silent
+    pub fn new(pwm: T, pin: Pin<Output<PushPull>>) -> Self {
+        let port = match pin.port() {
+            Port::Port0 => false,
+            Port::Port1 => true,
+        };
+        pwm.psel.out[0].write(|w| {
+            let w = unsafe { w.pin().bits(pin.pin()) };
+            w.port().bit(port).connect().connected()
+        });
+        pwm.enable.write(|w| w.enable().enabled());
+        pwm.mode.write(|w| w.updown().up());
+        pwm.decoder
+            .write(|w| w.load().common().mode().refresh_count());
+        pwm.loop_.write(|w| w.cnt().disabled());
+        pwm.seq0.refresh.write(|w| unsafe { w.bits(0) });
+        pwm.seq0.enddelay.write(|w| unsafe { w.bits(0) });
+        Self { pwm }
+    }
+
+    /// Starts a tone, it plays until `stop` is called
+    ///
+    /// Fails if the frequency is outside the range of the PWM, see
+    /// `tone_period`.
+    pub fn start(&mut self, frequency: u32) -> Result<(), ()> {
+        let (prescaler, top) = tone_period(frequency).ok_or(())?;
+        // 50 % duty cycle
+        let duty = [top / 2];
+        self.pwm
+            .prescaler
+            .write(|w| unsafe { w.bits(u32::from(prescaler)) });
+        self.pwm
+            .countertop
+            .write(|w| unsafe { w.bits(u32::from(top)) });
+        self.pwm
+            .seq0
+            .ptr
+            .write(|w| unsafe { w.bits(duty.as_ptr() as u32) });
+        self.pwm.seq0.cnt.write(|w| unsafe { w.bits(1) });
+        self.pwm.events_seqend[0].reset();
+        compiler_fence(Ordering::Release);
+        self.pwm.tasks_seqstart[0].write(|w| unsafe { w.bits(1) });
+        // The duty cycle is on the stack, wait until it has been loaded. The
+        // last value keeps repeating after the sequence has ended.
+        while self.pwm.events_seqend[0].read().bits() == 0 {}
+        self.pwm.events_seqend[0].reset();
+        compiler_fence(Ordering::Acquire);
+        Ok(())
+    }
+
+    /// Stops the tone
+    pub fn stop(&mut self) {
+        self.pwm.events_stopped.reset();
+        self.pwm.tasks_stop.write(|w| unsafe { w.bits(1) });
+        while self.pwm.events_stopped.read().bits() == 0 {}
+        self.pwm.events_stopped.reset();
+    }
+
+    /// Plays a tone for `duration` milliseconds, blocking
+    pub fn play<DELAY>(
+        &mut self,
+        frequency: u32,
+        duration: u32,
+        delay: &mut DELAY,
+    ) -> Result<(), ()>
+    where
+        DELAY: DelayMs<u32>,
+    {
+        self.start(frequency)?;
+        delay.delay_ms(duration);
+        self.stop();
+        Ok(())
+    }
+
+    /// Return the PWM peripheral
+    pub fn free(self) -> T {
+        self.pwm.enable.write(|w| w.enable().disabled());
+        self.pwm
+    }
+}
+
+/// Implemented by all PWM instances
+pub trait Instance: Deref<Target = pwm0::RegisterBlock> {}
+
+impl Instance for PWM0 {}
+impl Instance for PWM1 {}
+impl Instance for PWM2 {}
+impl Instance for PWM3 {}
@@ -0,0 +1,313 @@
+//! Frame decoder for debugging received frames.
+//!
+//! `log_frame` walks the IEEE 802.15.4 MAC, Zigbee NWK, APS and ZCL layers
+//! of a frame and logs the header fields of each layer over defmt. Decoding
+//! stops at the first layer that can't be parsed, or that is encrypted, and
+//! the rest of the frame is logged as a hexdump. Use it on frames the stack
+//! rejects to see what arrived.
+//!
+//! Enabled with the `trace` feature, so that release builds don't carry
+//! the decoder.
+
+use psila_data::{
+    application_service::ApplicationServiceHeader, pack::Pack, security::SecurityHeader,
+};
+
+use crate::radio::{mac_header_size, Address};
+
+/// Bytes per hexdump line
+const HEXDUMP_LINE: usize = 16;
+
+/// Logs `data` as hex, 16 bytes per line prefixed with the offset
+pub fn hexdump(data: &[u8]) {
+    for (n, line) in data.chunks(HEXDUMP_LINE).enumerate() {
+        defmt::info!("{=usize:04x}: {=[u8]:02x}", n * HEXDUMP_LINE, line);
+    }
+}
+
+/// Logs the headers of a received frame and a hexdump of what remains
+///
+/// The frame starts with the MAC frame control field, without the PHY
+/// length octet. A trailing frame check sequence ends up in the hexdump.
+pub fn log_frame(frame: &[u8]) {
+    defmt::info!("Frame, {=usize} bytes", frame.len());
+    let offset = decode(frame);
+    if offset < frame.len() {
+        defmt::info!("Remaining {=usize} bytes", frame.len() - offset);
+        hexdump(&frame[offset..]);
+    }
+}
+
+/// Logs the headers it can parse, returns the offset where it stopped
+fn decode(frame: &[u8]) -> usize {
+    let mut offset = match log_mac(frame) {
+        Some(size) => size,
+        None => return 0,
+    };
+    // Only MAC data frames without MAC security carry NWK frames
+    if frame[0] & 0b111 != 0b001 || frame[0] & 0b1000 != 0 {
+        return offset;
+    }
+    let nwk_control = frame.get(offset).copied().unwrap_or_default();
+    let (size, nwk_secured) = match log_nwk(&frame[offset..]) {
+        Some(nwk) => nwk,
+        None => return offset,
+    };
+    offset += size;
+    if nwk_secured {
+        return offset + log_security(&frame[offset..], "NWK");
+    }
+    // NWK commands have no APS header
+    if nwk_control & 0b11 != 0b00 {
+        return offset;
+    }
+    let aps = &frame[offset..];
+    let aps_control = match aps.first() {
+        Some(control) => *control,
+        None => return offset,
+    };
+    let size = match log_aps(aps) {
+        Some(size) => size,
+        None => return offset,
+    };
+    offset += size;
+    if aps_control & 0x20 == 0x20 {
+        return offset + log_security(&frame[offset..], "APS");
+    }
+    // APS data frames carry ZCL frames, except for the ZDO profile. The
+    // profile follows the cluster, after the destination endpoint or the
+    // group address.
+    if aps_control & 0b11 == 0b00 {
+        let profile_offset = if (aps_control >> 2) & 0b11 == 0b11 {
+            5
+        } else {
+            4
+        };
+        let profile = aps
+            .get(profile_offset..profile_offset + 2)
+            .map(|profile| u16::from_le_bytes([profile[0], profile[1]]));
+        if profile != Some(0x0000) {
+            offset += log_zcl(&frame[offset..]);
+        }
+    }
+    offset
+}
+
+/// Name of a MAC frame type
+fn mac_frame_type(frame_type: u8) -> &'static str {
+    match frame_type {
+        0b000 => "beacon",
+        0b001 => "data",
+        0b010 => "acknowledge",
+        0b011 => "command",
+        _ => "reserved",
+    }
+}
+
+/// Reads an address of the given addressing mode at `offset`
+fn read_address(frame: &[u8], offset: usize, mode: u16) -> (Address, usize) {
+    if mode == 0b10 {
+        let address = u16::from_le_bytes([frame[offset], frame[offset + 1]]);
+        (Address::Short(address), 2)
+    } else {
+        let mut octets = [0u8; 8];
+        octets.copy_from_slice(&frame[offset..offset + 8]);
+        (Address::Extended(u64::from_le_bytes(octets)), 8)
+    }
+}
+
+/// Logs the MAC header, returns its size
+fn log_mac(frame: &[u8]) -> Option<usize> {
+    if frame.len() < 2 {
+        defmt::warn!("MAC, frame control truncated");
+        return None;
+    }
+    let frame_control = u16::from_le_bytes([frame[0], frame[1]]);
+    let version = (frame_control >> 12) & 0b11;
+    defmt::info!(
+        "MAC {=str}, security {=bool}, pending {=bool}, ack request {=bool}, PAN ID compression {=bool}, version {=u16}",
+        mac_frame_type(frame[0] & 0b111),
+        frame_control & 0x0008 == 0x0008,
+        frame_control & 0x0010 == 0x0010,
+        frame_control & 0x0020 == 0x0020,
+        frame_control & 0x0040 == 0x0040,
+        version
+    );
+    let size = match mac_header_size(frame) {
+        Ok(size) if size <= frame.len() => size,
+        Ok(size) => {
+            defmt::warn!("MAC header truncated, {=usize} bytes expected", size);
+            return None;
+        }
+        Err(_) => {
+            defmt::warn!("MAC header, reserved addressing mode");
+            return None;
+        }
+    };
+
+    let mut offset = 2;
+    if !(version == 0b10 && frame_control & 0x0100 == 0x0100) {
+        defmt::info!("MAC sequence {=u8}", frame[offset]);
+        offset += 1;
+    }
+    let destination_mode = (frame_control >> 10) & 0b11;
+    let mut pan_id = None;
+    if destination_mode != 0b00 {
+        let destination_pan_id = u16::from_le_bytes([frame[offset], frame[offset + 1]]);
+        let (address, address_size) = read_address(frame, offset + 2, destination_mode);
+        defmt::info!(
+            "MAC destination PAN {=u16:04x}, {}",
+            destination_pan_id,
+            address
+        );
+        pan_id = Some(destination_pan_id);
+        offset += 2 + address_size;
+    }
+    let source_mode = (frame_control >> 14) & 0b11;
+    if source_mode != 0b00 {
+        if frame_control & 0x0040 == 0 || pan_id.is_none() {
+            pan_id = Some(u16::from_le_bytes([frame[offset], frame[offset + 1]]));
+            offset += 2;
+        }
+        let (address, _) = read_address(frame, offset, source_mode);
+        defmt::info!("MAC source PAN {}, {}", pan_id, address);
+    }
+    Some(size)
+}
+
+/// Logs the NWK header, returns its size and whether the frame is secured
+/// at the network level
+fn log_nwk(frame: &[u8]) -> Option<(usize, bool)> {
+    if frame.len() < 8 {
+        defmt::warn!("NWK header truncated, {=usize} bytes", frame.len());
+        return None;
+    }
+    let frame_control = u16::from_le_bytes([frame[0], frame[1]]);
+    let frame_type = match frame_control & 0b11 {
+        0b00 => "data",
+        0b01 => "command",
+        0b11 => "inter-PAN",
+        _ => "reserved",
+    };
+    let secured = frame_control & 0x0200 == 0x0200;
+    defmt::info!(
+        "NWK {=str}, version {=u16}, destination {=u16:04x}, source {=u16:04x}, radius {=u8}, sequence {=u8}, security {=bool}",
+        frame_type,
+        (frame_control >> 2) & 0x0f,
+        u16::from_le_bytes([frame[2], frame[3]]),
+        u16::from_le_bytes([frame[4], frame[5]]),
+        frame[6],
+        frame[7],
+        secured
+    );
+    let mut size = 8;
+    for &(flag, name) in [(0x0800, "destination"), (0x1000, "source")].iter() {
+        if frame_control & flag == flag {
+            if frame.len() < size + 8 {
+                defmt::warn!("NWK {=str} IEEE address truncated", name);
+                return None;
+            }
+            let (address, _) = read_address(frame, size, 0b11);
+            defmt::info!("NWK {=str} {}", name, address);
+            size += 8;
+        }
+    }
+    if frame_control & 0x0100 == 0x0100 {
+        match frame.get(size) {
+            Some(control) => defmt::info!("NWK multicast control {=u8:02x}", control),
+            None => {
+                defmt::warn!("NWK multicast control truncated");
+                return None;
+            }
+        }
+        size += 1;
+    }
+    if frame_control & 0x0400 == 0x0400 {
+        let relays = match frame.get(size..size + 2) {
+            Some(relays) => relays,
+            None => {
+                defmt::warn!("NWK source route truncated");
+                return None;
+            }
+        };
+        defmt::info!(
+            "NWK source route, {=u8} relays, index {=u8}",
+            relays[0],
+            relays[1]
+        );
+        size += 2 + 2 * usize::from(relays[0]);
+        if size > frame.len() {
+            defmt::warn!("NWK relay list truncated");
+            return None;
+        }
+    }
+    Some((size, secured))
+}
+
+/// Logs an auxiliary security header, returns its size
+///
+/// The payload following the header is encrypted and can't be decoded.
+fn log_security(frame: &[u8], layer: &str) -> usize {
+    match SecurityHeader::unpack(frame) {
+        Ok((header, used)) => {
+            defmt::info!("{=str} security {}", layer, defmt::Debug2Format(&header));
+            defmt::info!("{=str} payload encrypted", layer);
+            used
+        }
+        Err(error) => {
+            defmt::warn!(
+                "{=str} security header, {}",
+                layer,
+                defmt::Debug2Format(&error)
+            );
+            0
+        }
+    }
+}
+
+/// Logs the APS header, returns its size
+fn log_aps(frame: &[u8]) -> Option<usize> {
+    match ApplicationServiceHeader::unpack(frame) {
+        Ok((header, used)) => {
+            defmt::info!("APS {}", defmt::Debug2Format(&header));
+            Some(used)
+        }
+        Err(error) => {
+            defmt::warn!("APS header, {}", defmt::Debug2Format(&error));
+            None
+        }
+    }
+}
+
+/// Logs the ZCL header, returns its size
+fn log_zcl(frame: &[u8]) -> usize {
+    let control = match frame.first() {
+        Some(control) => *control,
+        None => return 0,
+    };
+    let manufacturer_specific = control & 0x04 == 0x04;
+    let size = if manufacturer_specific { 5 } else { 3 };
+    if frame.len() < size {
+        defmt::warn!("ZCL header truncated, {=usize} bytes", frame.len());
+        return 0;
+    }
+    if manufacturer_specific {
+        defmt::info!(
+            "ZCL manufacturer {=u16:04x}",
+            u16::from_le_bytes([frame[1], frame[2]])
+        );
+    }
+    defmt::info!(
+        "ZCL {=str}, to client {=bool}, default response {=bool}, sequence {=u8}, command {=u8:02x}",
+        if control & 0b11 == 0b01 {
+            "cluster specific"
+        } else {
+            "global"
+        },
+        control & 0x08 == 0x08,
+        control & 0x10 == 0,
+        frame[size - 2],
+        frame[size - 1]
+    );
+    size
+}
@@ -0,0 +1,207 @@
+//! Line oriented UARTE receive helpers.
+
+use core::marker::PhantomData;
+
+use crate::hal::{
+    timer::{self, OneShot},
+    uarte::{self, Uarte},
+    Timer,
+};
+
+/// UART receive errors
+#[derive(Debug)]
+pub enum Error {
+    /// The buffer filled up before a line terminator was received
+    BufferFull,
+    /// No byte was received within the timeout, the number of bytes received
+    /// so far is given
+    Timeout(usize),
+    /// UARTE peripheral error
+    Uarte(uarte::Error),
+}
+
+fn is_terminator(byte: u8) -> bool {
+    byte == b'\n' || byte == b'\r'
+}
+
+/// Reads a line terminated by newline or carriage return into `buffer`
+///
+/// Blocks until a terminator is received. The terminator is not stored,
+/// returns the length of the line.
+///
+/// Each byte is received by DMA into a stack buffer and then copied to
+/// `buffer`, so `buffer` may be anywhere in memory.
+pub fn read_line<T>(uarte: &mut Uarte<T>, buffer: &mut [u8]) -> Result<usize, Error>
+where
+    T: uarte::Instance,
+{
+    let mut length = 0;
+    loop {
+        let mut byte = [0u8; 1];
+        uarte.read(&mut byte).map_err(Error::Uarte)?;
+        if is_terminator(byte[0]) {
+            return Ok(length);
+        }
+        if length == buffer.len() {
+            return Err(Error::BufferFull);
+        }
+        buffer[length] = byte[0];
+        length += 1;
+    }
+}
+
+/// Reads a line terminated by newline or carriage return into `buffer`,
+/// ending when the line goes idle
+///
+/// Returns the length of the line when a terminator is received. If no
+/// byte is received within `cycles` timer cycles, `Error::Timeout` is
+/// returned with the number of bytes stored so far. Continue the line by
+/// calling again with the rest of the buffer.
+pub fn read_line_timeout<T, I>(
+    uarte: &mut Uarte<T>,
+    buffer: &mut [u8],
+    timer: &mut Timer<I, OneShot>,
+    cycles: u32,
+) -> Result<usize, Error>
+where
+    T: uarte::Instance,
+    I: timer::Instance,
+{
+    let mut length = 0;
+    loop {
+        let mut byte = [0u8; 1];
+        match uarte.read_timeout(&mut byte, timer, cycles) {
+            Ok(()) => {}
+            Err(uarte::Error::Timeout(_)) => return Err(Error::Timeout(length)),
+            Err(e) => return Err(Error::Uarte(e)),
+        }
+        if is_terminator(byte[0]) {
+            return Ok(length);
+        }
+        if length == buffer.len() {
+            return Err(Error::BufferFull);
+        }
+        buffer[length] = byte[0];
+        length += 1;
+    }
+}
+
+/// Collects received bytes into lines terminated by newline or carriage
+/// return
+pub struct LineBuffer<const N: usize> {
+    buffer: [u8; N],
+    length: usize,
+    overflow: bool,
+}
+
+impl<const N: usize> LineBuffer<N> {
+    pub const fn new() -> Self {
+        Self {
+            buffer: [0; N],
+            length: 0,
+            overflow: false,
+        }
+    }
+
+    /// Adds a received byte, returns the line when `byte` terminates it
+    ///
+    /// The terminator is not stored. Empty lines, such as the one between a
+    /// carriage return and a newline, are skipped. A line longer than the
+    /// buffer is dropped and reported as `Error::BufferFull` when it ends.
+    pub fn push(&mut self, byte: u8) -> Option<Result<&[u8], Error>> {
+        if is_terminator(byte) {
+            let length = self.length;
+            let overflow = self.overflow;
+            self.length = 0;
+            self.overflow = false;
+            if overflow {
+                Some(Err(Error::BufferFull))
+            } else if length == 0 {
+                None
+            } else {
+                Some(Ok(&self.buffer[..length]))
+            }
+        } else {
+            if self.length == N {
+                self.overflow = true;
+            } else {
+                self.buffer[self.length] = byte;
+                self.length += 1;
+            }
+            None
+        }
+    }
+}
+
+impl<const N: usize> Default for LineBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Continuous UARTE receiver
+///
+/// Reception is started once and never stopped. EasyDMA fills two one byte
+/// buffers in turn, the ENDRX to STARTRX short restarts reception into the
+/// other buffer as soon as a byte has arrived, so nothing is lost while the
+/// application is busy as long as the UARTE interrupt is served within a
+/// byte time. The transmit side of the `Uarte` can still be used.
+pub struct UarteReceiver<T> {
+    buffers: &'static mut [u8; 2],
+    /// Index of the buffer being filled
+    receiving: usize,
+    _uarte: PhantomData<T>,
+}
+
+impl<T> UarteReceiver<T>
+where
+    T: uarte::Instance,
+{
+    /// Starts receiving on the UARTE of `_uarte`
+    ///
+    /// The UARTE interrupt is enabled for the ENDRX and RXSTARTED events,
+    /// call `on_interrupt` from its handler.
+    pub fn start(_uarte: &Uarte<T>, buffers: &'static mut [u8; 2]) -> Self {
+        let uarte = unsafe { &*T::ptr() };
+        uarte
+            .rxd
+            .ptr
+            .write(|w| unsafe { w.ptr().bits(buffers.as_ptr() as u32) });
+        uarte.rxd.maxcnt.write(|w| unsafe { w.maxcnt().bits(1) });
+        uarte.events_endrx.reset();
+        uarte.events_rxstarted.reset();
+        uarte.shorts.modify(|_, w| w.endrx_startrx().enabled());
+        uarte.intenset.write(|w| w.endrx().set().rxstarted().set());
+        uarte.tasks_startrx.write(|w| unsafe { w.bits(1) });
+        Self {
+            buffers,
+            receiving: 0,
+            _uarte: PhantomData,
+        }
+    }
+
+    /// Handles the UARTE interrupt, returns the received byte if any
+    pub fn on_interrupt(&mut self) -> Option<u8> {
+        let uarte = unsafe { &*T::ptr() };
+        let mut received = None;
+        if uarte.events_endrx.read().bits() != 0 {
+            uarte.events_endrx.reset();
+            if uarte.rxd.amount.read().bits() == 1 {
+                received = Some(self.buffers[self.receiving]);
+            }
+            // The short has already started reception into the other buffer
+            self.receiving ^= 1;
+        }
+        if uarte.events_rxstarted.read().bits() != 0 {
+            uarte.events_rxstarted.reset();
+            // The pointer is latched, point the next reception at the buffer
+            // that is not being filled
+            let next = &self.buffers[self.receiving ^ 1] as *const u8;
+            uarte
+                .rxd
+                .ptr
+                .write(|w| unsafe { w.ptr().bits(next as u32) });
+        }
+        received
+    }
+}
@@ -0,0 +1,57 @@
+//! Watchdog timer resetting the device when the application stops petting
+//! it.
+
+use crate::hal::pac;
+
+/// Reload request value
+const RELOAD_VALUE: u32 = 0x6E52_4635;
+
+/// Watchdog counter frequency, the watchdog runs from the LFCLK
+const WDT_HZ: u32 = 32_768;
+
+/// Watchdog using reload request register 0
+///
+/// Once started the watchdog can't be stopped or reconfigured, only a reset
+/// stops it. Choose a timeout well above the longest time the application
+/// may go without petting, including busy periods such as joining a
+/// network, or the device resets during normal operation.
+///
+/// The watchdog keeps running while the CPU sleeps and is paused while the
+/// CPU is halted by a debugger.
+pub struct Watchdog {
+    wdt: pac::WDT,
+}
+
+impl Watchdog {
+    /// Starts the watchdog with a timeout in milliseconds
+    ///
+    /// The timeout is at least about 0.5 ms and at most 36 hours. If the
+    /// watchdog is already running, as it is after a soft reset, the
+    /// running configuration is kept and the watchdog is petted.
+    pub fn start(wdt: pac::WDT, timeout_ms: u32) -> Self {
+        let mut watchdog = Self { wdt };
+        if watchdog.is_running() {
+            watchdog.pet();
+            return watchdog;
+        }
+        let ticks = (u64::from(timeout_ms) * u64::from(WDT_HZ) / 1000)
+            .max(0xf)
+            .min(u64::from(u32::MAX)) as u32;
+        let wdt = &watchdog.wdt;
+        wdt.crv.write(|w| unsafe { w.bits(ticks) });
+        wdt.config.write(|w| w.sleep().run().halt().pause());
+        wdt.rren.write(|w| w.rr0().enabled());
+        wdt.tasks_start.write(|w| unsafe { w.bits(1) });
+        watchdog
+    }
+
+    /// Restarts the timeout
+    pub fn pet(&mut self) {
+        self.wdt.rr[0].write(|w| unsafe { w.bits(RELOAD_VALUE) });
+    }
+
+    /// Is the watchdog running?
+    pub fn is_running(&self) -> bool {
+        self.wdt.runstatus.read().bits() & 1 == 1
+    }
+}
@@ -0,0 +1,456 @@
+//! Zigbee cluster library helpers.
+
+use core::convert::TryFrom;
+
+use heapless::Vec;
+use psila_data::cluster_library::{AttributeDataType, ClusterLibraryStatus};
+
+/// Raw ZCL status byte, as found in responses from other devices
+///
+/// Convert a status received from a peer with `ClusterLibraryStatus::try_from`
+/// and a status to send with `ZclStatus::from`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ZclStatus(pub u8);
+
+impl From<ClusterLibraryStatus> for ZclStatus {
+    fn from(status: ClusterLibraryStatus) -> Self {
+        ZclStatus(u8::from(status))
+    }
+}
+
+/// Only the standard status codes are converted, other codes are returned
+/// as the error so that an unknown status is never taken for success
+impl TryFrom<ZclStatus> for ClusterLibraryStatus {
+    type Error = ZclStatus;
+
+    fn try_from(status: ZclStatus) -> Result<Self, Self::Error> {
+        match status.0 {
+            0x00 => Ok(ClusterLibraryStatus::Success),
+            0x01 => Ok(ClusterLibraryStatus::Failure),
+            0x80 => Ok(ClusterLibraryStatus::MalformedCommand),
+            0x81 => Ok(ClusterLibraryStatus::UnsupportedClusterCommand),
+            0x86 => Ok(ClusterLibraryStatus::UnsupportedAttribute),
+            0x87 => Ok(ClusterLibraryStatus::InvalidValue),
+            0x88 => Ok(ClusterLibraryStatus::ReadOnly),
+            0x89 => Ok(ClusterLibraryStatus::InsufficientSpace),
+            0x8d => Ok(ClusterLibraryStatus::InvalidDataType),
+            _ => Err(status),
+        }
+    }
+}
+
+/// Longest ZCL character string, a length of 0xff marks an invalid string
+pub const MAX_STRING_LENGTH: usize = 0xfe;
+
+/// Writes a ZCL character string to `buffer`, returns the number of bytes
+/// used
+///
+/// The string is prefixed with its length in a single octet, so the size is
+/// the string length plus one. Fails with `InsufficientSpace` when the
+/// buffer is too small and with `InvalidValue` when the string is longer
+/// than `MAX_STRING_LENGTH`, nothing is written then.
+pub fn write_zcl_string(buffer: &mut [u8], s: &str) -> Result<usize, ClusterLibraryStatus> {
+    if s.len() > MAX_STRING_LENGTH {
+        return Err(ClusterLibraryStatus::InvalidValue);
+    }
+    let size = s.len() + 1;
+    if buffer.len() < size {
+        return Err(ClusterLibraryStatus::InsufficientSpace);
+    }
+    buffer[0] = s.len() as u8;
+    buffer[1..size].copy_from_slice(s.as_bytes());
+    Ok(size)
+}
+
+/// Typed attribute value
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AttributeValue {
+    Boolean(bool),
+    Unsigned8(u8),
+    Unsigned16(u16),
+    Enumeration8(u8),
+    CharacterString(&'static str),
+}
+
+impl AttributeValue {
+    /// The ZCL data type of the value
+    pub fn data_type(&self) -> AttributeDataType {
+        match self {
+            AttributeValue::Boolean(_) => AttributeDataType::Boolean,
+            AttributeValue::Unsigned8(_) => AttributeDataType::Unsigned8,
+            AttributeValue::Unsigned16(_) => AttributeDataType::Unsigned16,
+            AttributeValue::Enumeration8(_) => AttributeDataType::Enumeration8,
+            AttributeValue::CharacterString(_) => AttributeDataType::CharacterString,
+        }
+    }
+
+    /// Encodes the value into `buffer`, returns the number of bytes used
+    ///
+    /// Character strings are prefixed with their length, see `write_zcl_string`.
+    pub fn encode(&self, buffer: &mut [u8]) -> Result<usize, ClusterLibraryStatus> {
+        let size = match self {
+            AttributeValue::Boolean(_)
+            | AttributeValue::Unsigned8(_)
+            | AttributeValue::Enumeration8(_) => 1,
+            AttributeValue::Unsigned16(_) => 2,
+            AttributeValue::CharacterString(s) => s.len() + 1,
+        };
+        if buffer.len() < size {
+            return Err(ClusterLibraryStatus::InsufficientSpace);
+        }
+        match self {
+            AttributeValue::Boolean(value) => {
+                buffer[0] = if *value { 0x01 } else { 0x00 };
+            }
+            AttributeValue::Unsigned8(value) | AttributeValue::Enumeration8(value) => {
+                buffer[0] = *value;
+            }
+            AttributeValue::Unsigned16(value) => {
+                buffer[..2].copy_from_slice(&value.to_le_bytes());
+            }
+            AttributeValue::CharacterString(s) => {
+                write_zcl_string(buffer, s)?;
+            }
+        }
+        Ok(size)
+    }
+
+    /// Decodes a value of the same type from `data`
+    ///
+    /// Character strings can't be decoded as the value only refers to static
+    /// strings.
+    fn decode_as(
+        &self,
+        data_type: AttributeDataType,
+        data: &[u8],
+    ) -> Result<Self, ClusterLibraryStatus> {
+        if data_type != self.data_type() {
+            return Err(ClusterLibraryStatus::InvalidDataType);
+        }
+        let value = match self {
+            AttributeValue::Boolean(_) => match data.first() {
+                Some(0x00) => AttributeValue::Boolean(false),
+                Some(0x01) => AttributeValue::Boolean(true),
+                _ => return Err(ClusterLibraryStatus::InvalidValue),
+            },
+            AttributeValue::Unsigned8(_) => match data.first() {
+                Some(value) => AttributeValue::Unsigned8(*value),
+                None => return Err(ClusterLibraryStatus::InvalidValue),
+            },
+            AttributeValue::Enumeration8(_) => match data.first() {
+                Some(value) => AttributeValue::Enumeration8(*value),
+                None => return Err(ClusterLibraryStatus::InvalidValue),
+            },
+            AttributeValue::Unsigned16(_) => {
+                if data.len() < 2 {
+                    return Err(ClusterLibraryStatus::InvalidValue);
+                }
+                AttributeValue::Unsigned16(u16::from_le_bytes([data[0], data[1]]))
+            }
+            AttributeValue::CharacterString(_) => return Err(ClusterLibraryStatus::ReadOnly),
+        };
+        Ok(value)
+    }
+}
+
+/// Attribute access from other devices
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+    /// Writes are refused with `ReadOnly`
+    ReadOnly,
+    /// The attribute can be read and written
+    ReadWrite,
+}
+
+/// Attribute table entry
+///
+/// The value holds both the data type and the current value of the
+/// attribute. A device declares its attributes as a table of entries, for
+/// example in a `const` array, and creates a store from it with
+/// `AttributeStore::from_table`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Attribute {
+    /// Cluster identifier
+    pub cluster: u16,
+    /// Attribute identifier
+    pub attribute: u16,
+    /// Access from other devices
+    pub access: Access,
+    /// Current value
+    pub value: AttributeValue,
+}
+
+impl Attribute {
+    /// Entry for an attribute other devices can only read
+    pub const fn read_only(cluster: u16, attribute: u16, value: AttributeValue) -> Self {
+        Self {
+            cluster,
+            attribute,
+            access: Access::ReadOnly,
+            value,
+        }
+    }
+
+    /// Entry for an attribute other devices can read and write
+    pub const fn read_write(cluster: u16, attribute: u16, value: AttributeValue) -> Self {
+        Self {
+            cluster,
+            attribute,
+            access: Access::ReadWrite,
+            value,
+        }
+    }
+}
+
+/// Storage for up to `N` attributes
+///
+/// `read` and `write` implement the attribute handling of
+/// `ClusterLibraryHandler` for the stored attributes, encoding values and
+/// checking data types and access.
+pub struct AttributeStore<const N: usize> {
+    attributes: Vec<Attribute, N>,
+}
+
+impl<const N: usize> AttributeStore<N> {
+    /// Creates an empty store
+    pub fn new() -> Self {
+        Self {
+            attributes: Vec::new(),
+        }
+    }
+
+    /// Creates a store holding the entries of `table`, the first entry that
+    /// doesn't fit is returned if the table is longer than `N`
+    pub fn from_table(table: &[Attribute]) -> Result<Self, Attribute> {
+        let mut store = Self::new();
+        for entry in table {
+            store.insert_entry(*entry)?;
+        }
+        Ok(store)
+    }
+
+    /// Adds a writable attribute, the value is returned if the store is full
+    pub fn insert(
+        &mut self,
+        cluster: u16,
+        attribute: u16,
+        value: AttributeValue,
+    ) -> Result<(), AttributeValue> {
+        self.insert_entry(Attribute::read_write(cluster, attribute, value))
+            .map_err(|entry| entry.value)
+    }
+
+    /// Adds a table entry, the entry is returned if the store is full
+    pub fn insert_entry(&mut self, entry: Attribute) -> Result<(), Attribute> {
+        self.attributes.push(entry)
+    }
+
+    fn find(&self, cluster: u16, attribute: u16) -> Option<&Attribute> {
+        self.attributes
+            .iter()
+            .find(|a| a.cluster == cluster && a.attribute == attribute)
+    }
+
+    fn find_mut(&mut self, cluster: u16, attribute: u16) -> Option<&mut Attribute> {
+        self.attributes
+            .iter_mut()
+            .find(|a| a.cluster == cluster && a.attribute == attribute)
+    }
+
+    /// Get an attribute value
+    pub fn get(&self, cluster: u16, attribute: u16) -> Option<AttributeValue> {
+        self.find(cluster, attribute).map(|a| a.value)
+    }
+
+    /// Set an attribute value, the type must match the stored value
+    ///
+    /// This is for the device itself, read only attributes can be set too.
+    pub fn set(
+        &mut self,
+        cluster: u16,
+        attribute: u16,
+        value: AttributeValue,
+    ) -> Result<(), ClusterLibraryStatus> {
+        match self.find_mut(cluster, attribute) {
+            Some(entry) => {
+                if entry.value.data_type() != value.data_type() {
+                    return Err(ClusterLibraryStatus::InvalidDataType);
+                }
+                entry.value = value;
+                Ok(())
+            }
+            None => Err(ClusterLibraryStatus::UnsupportedAttribute),
+        }
+    }
+
+    /// Encodes an attribute value into `value`, as expected by
+    /// `ClusterLibraryHandler::read_attribute`
+    pub fn read(
+        &self,
+        cluster: u16,
+        attribute: u16,
+        value: &mut [u8],
+    ) -> Result<(AttributeDataType, usize), ClusterLibraryStatus> {
+        match self.find(cluster, attribute) {
+            Some(entry) => {
+                let size = entry.value.encode(value)?;
+                Ok((entry.value.data_type(), size))
+            }
+            None => Err(ClusterLibraryStatus::UnsupportedAttribute),
+        }
+    }
+
+    /// Decodes and stores an attribute value, as expected by
+    /// `ClusterLibraryHandler::write_attribute`
+    ///
+    /// Writes to read only attributes are refused with `ReadOnly`.
+    pub fn write(
+        &mut self,
+        cluster: u16,
+        attribute: u16,
+        data_type: AttributeDataType,
+        value: &[u8],
+    ) -> Result<(), ClusterLibraryStatus> {
+        match self.find_mut(cluster, attribute) {
+            Some(entry) if entry.access == Access::ReadOnly => Err(ClusterLibraryStatus::ReadOnly),
+            Some(entry) => {
+                entry.value = entry.value.decode_as(data_type, value)?;
+                Ok(())
+            }
+            None => Err(ClusterLibraryStatus::UnsupportedAttribute),
+        }
+    }
+}
+
+impl<const N: usize> Default for AttributeStore<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Writes read attributes status records for `attributes` to `output`,
+/// returns the number of bytes used
+///
+/// Each record holds the attribute identifier and the status, followed by
+/// the data type and the value when `read` succeeds. An attribute that
+/// can't be read, also when its value doesn't fit in the remaining space,
+/// gets a record with only the status returned by `read`. Attributes that
+/// follow are left out once not even a status record fits.
+pub fn read_attributes<F>(attributes: &[u16], output: &mut [u8], mut read: F) -> usize
+where
+    F: FnMut(u16, &mut [u8]) -> Result<(AttributeDataType, usize), ClusterLibraryStatus>,
+{
+    let mut offset = 0;
+    for attribute in attributes {
+        let record = &mut output[offset..];
+        if record.len() < 3 {
+            break;
+        }
+        record[..2].copy_from_slice(&attribute.to_le_bytes());
+        let result = match record.get_mut(4..) {
+            Some(value) => read(*attribute, value),
+            None => Err(ClusterLibraryStatus::InsufficientSpace),
+        };
+        match result {
+            Ok((data_type, size)) => {
+                record[2] = u8::from(ClusterLibraryStatus::Success);
+                record[3] = u8::from(data_type);
+                offset += 4 + size;
+            }
+            Err(status) => {
+                record[2] = u8::from(status);
+                offset += 3;
+            }
+        }
+    }
+    offset
+}
+
+/// Converts a ZCL transition time, in tenths of a second, to ticks at
+/// `tick_hz`
+///
+/// 0xffff means no transition time was given and the level is changed as
+/// fast as possible. The result is limited to half the tick range, so that
+/// wrapping tick counters can be used.
+pub fn transition_ticks(transition_time: u16, tick_hz: u32) -> u32 {
+    if transition_time == 0xffff {
+        return 0;
+    }
+    let ticks = u64::from(transition_time) * u64::from(tick_hz) / 10;
+    ticks.min(u64::from(u32::MAX / 2)) as u32
+}
+
+/// Level control transition
+///
+/// Moves the level linearly from its current value to a target value over
+/// a number of ticks, as the level control cluster move to level commands
+/// do. Any tick source can be used, the tick counter may wrap around.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LevelControl {
+    /// Level at the start of the transition
+    from: u8,
+    /// Level at the end of the transition
+    to: u8,
+    /// Tick when the transition started
+    start: u32,
+    /// Length of the transition in ticks
+    duration: u32,
+}
+
+impl LevelControl {
+    /// Creates a level control resting at `level`
+    pub fn new(level: u8) -> Self {
+        Self {
+            from: level,
+            to: level,
+            start: 0,
+            duration: 0,
+        }
+    }
+
+    /// Starts a transition from the current level to `level`, taking
+    /// `duration` ticks
+    pub fn move_to(&mut self, level: u8, duration: u32, now: u32) {
+        self.from = self.level(now);
+        self.to = level;
+        self.start = now;
+        self.duration = duration;
+    }
+
+    /// Stops the transition at the current level
+    pub fn stop(&mut self, now: u32) {
+        let level = self.level(now);
+        *self = Self::new(level);
+    }
+
+    /// The level at the end of the transition
+    pub fn target(&self) -> u8 {
+        self.to
+    }
+
+    /// Is the level still changing?
+    pub fn is_moving(&self, now: u32) -> bool {
+        self.level(now) != self.to
+    }
+
+    /// The level at `now`
+    pub fn level(&self, now: u32) -> u8 {
+        let elapsed = now.wrapping_sub(self.start);
+        if elapsed >= self.duration {
+            return self.to;
+        }
+        let span = i64::from(self.to) - i64::from(self.from);
+        let level = i64::from(self.from) + span * i64::from(elapsed) / i64::from(self.duration);
+        level as u8
+    }
+
+    /// Advances to `now` and returns the current level, the transition is
+    /// finished once the target is reached
+    pub fn tick(&mut self, now: u32) -> u8 {
+        let level = self.level(now);
+        if level == self.to {
+            *self = Self::new(level);
+        }
+        level
+    }
+}